@@ -0,0 +1,175 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
+
+//! The moderators actions on the alerts, shared between the notifiers. Each
+//! action publishes its decision to the other notifiers.
+
+use reqwest::Client;
+
+use super::{Decision, DecisionKind};
+use crate::{
+    config::BanAction,
+    db::{
+        AlertedUsersTableTrait,
+        BannedUsersTableTrait,
+        IgnoredUsersTableTrait,
+        PurgedUsersTableTrait,
+    },
+    forgejo_api,
+    instance::Instance,
+    snapshots,
+};
+
+/// Returns `true` if the user can be banned, the user in the purge queue can
+/// only be undone
+pub fn can_ban(instance: &Instance, username: &str) -> bool {
+    !instance.database.is_lazy_purged(username).is_ok_and(|y| y)
+}
+
+/// Returns `true` if the user alert can be ignored, the user is still alerted
+/// and not banned by another moderator
+pub fn can_ignore(instance: &Instance, username: &str) -> bool {
+    instance.database.is_alerted(username).is_ok_and(|y| y) && can_ban(instance, username)
+}
+
+/// Returns `true` if the user purge can be undone
+pub fn can_undo(instance: &Instance, username: &str) -> bool {
+    instance.config().lazy_purge.enabled && !can_ban(instance, username)
+}
+
+/// Returns the ban action of the user alert, the action of its matched
+/// expressions, or the global ban action if the user hasn't been alerted with
+/// one, e.g. the ban command requests
+pub fn ban_action(instance: &Instance, username: &str) -> BanAction {
+    instance
+        .database
+        .alert_action(username)
+        .ok()
+        .flatten()
+        .unwrap_or(instance.config().expressions.ban_action)
+}
+
+/// Ban the user with the action of its alert, or add them to the purge queue if
+/// the lazy purge is enabled. Returns `None` if the ban has been failed
+pub async fn ban(
+    instance: &Instance,
+    username: &str,
+    moderator: &str,
+    notifier: &'static str,
+) -> Option<DecisionKind> {
+    let (config, database) = (&instance.config(), &instance.database);
+    let ban_action = ban_action(instance, username);
+    let rule = database.alert_rule(username).ok().flatten();
+    if !(config.dry_run || config.lazy_purge.enabled) {
+        let client = Client::new();
+        if ban_action.is_purge() {
+            snapshots::take_by_username(&client, config, database, username).await;
+        }
+        forgejo_api::ban_user(
+            &client,
+            &config.forgejo.instance,
+            &config.forgejo.token,
+            username,
+            &ban_action,
+            &mut 0,
+        )
+        .await
+        .ok()?;
+        database.add_banned_user(username, ban_action).ok();
+    }
+
+    let kind = if config.lazy_purge.enabled {
+        tracing::info!("The moderator {moderator} has added @{username} to purge queue");
+        database.add_purged_user(username).ok();
+        DecisionKind::AddedToPurgeQueue
+    } else {
+        tracing::info!("The moderator {moderator} has banned @{username}");
+        database.remove_alerted_user(username).ok();
+        DecisionKind::Banned
+    };
+    instance.decide(
+        Decision::new(username, kind, moderator, notifier)
+            .ban_action(ban_action)
+            .rule(rule),
+    );
+    Some(kind)
+}
+
+/// Ignore the user, the user will not be alerted again
+pub fn ignore(instance: &Instance, username: &str, moderator: &str, notifier: &'static str) {
+    tracing::info!("The moderator {moderator} has ignored @{username}");
+    instance.database.add_ignored_user(username).ok();
+    instance.database.remove_alerted_user(username).ok();
+    instance.decide(Decision::new(
+        username,
+        DecisionKind::Ignored,
+        moderator,
+        notifier,
+    ));
+}
+
+/// Undo the user purge, remove them from the purge queue
+pub fn undo(instance: &Instance, username: &str, moderator: &str, notifier: &'static str) {
+    tracing::info!("The moderator {moderator} has undone @{username} purge");
+    instance.database.remove_purged_user(username).ok();
+    instance.decide(Decision::new(
+        username,
+        DecisionKind::Undone,
+        moderator,
+        notifier,
+    ));
+}
+
+/// Returns `true` if the user can be unignored
+pub fn can_unignore(instance: &Instance, username: &str) -> bool {
+    instance.database.is_ignored(username).is_ok_and(|y| y)
+}
+
+/// Unignore the user, the user will be alerted again if they still match the
+/// expressions
+pub fn unignore(instance: &Instance, username: &str, moderator: &str, notifier: &'static str) {
+    tracing::info!("The moderator {moderator} has unignored @{username}");
+    instance.database.remove_ignored_user(username).ok();
+    instance.decide(Decision::new(
+        username,
+        DecisionKind::Unignored,
+        moderator,
+        notifier,
+    ));
+}
+
+/// Unsuspend the suspended user and add them to the ignored users, so the
+/// checker doesn't ban them again for the same content. Returns `false` if the
+/// unsuspend has been failed
+pub async fn unsuspend(
+    instance: &Instance,
+    username: &str,
+    moderator: &str,
+    notifier: &'static str,
+) -> bool {
+    let config = &instance.config();
+    if !config.dry_run {
+        if let Err(err) = forgejo_api::unsuspend_user(
+            &Client::new(),
+            &config.forgejo.instance,
+            &config.forgejo.token,
+            username,
+        )
+        .await
+        {
+            tracing::error!("Failed to unsuspend @{username}: {err}");
+            return false;
+        }
+    }
+
+    tracing::info!("The moderator {moderator} has unsuspended @{username}");
+    instance.database.remove_banned_user(username).ok();
+    instance.database.add_ignored_user(username).ok();
+    instance.decide(Decision::new(
+        username,
+        DecisionKind::Unsuspended,
+        moderator,
+        notifier,
+    ));
+    true
+}
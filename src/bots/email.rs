@@ -0,0 +1,508 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
+
+//! Email (SMTP) notifier, sends the alerts to the moderators emails with signed
+//! links to take an action, the links are served by the embedded HTTP server.
+
+use std::fmt;
+
+use hmac::{Hmac, Mac};
+use lettre::{
+    message::header::ContentType,
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport,
+    AsyncTransport,
+    Message,
+    Tokio1Executor,
+};
+use sha2::Sha256;
+use url::Url;
+
+use super::{action_word, notifier::Notifier, user_details, Decision, UserAlert};
+use crate::{
+    config::{Config, Email, SmtpTls},
+    db::PurgedUsersTableTrait,
+    error::GuardResult,
+    instance::Instance,
+    metrics,
+};
+
+/// The action of an email link
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkAction {
+    Ban,
+    Ignore,
+    Undo,
+}
+
+impl LinkAction {
+    /// Returns the action from its link path
+    pub fn from_path(path: &str) -> Option<Self> {
+        match path {
+            "ban" => Some(Self::Ban),
+            "ignore" => Some(Self::Ignore),
+            "undo" => Some(Self::Undo),
+            _ => None,
+        }
+    }
+
+    /// Returns the translated action button
+    pub fn button(&self, lang: &str, ban_action: &str) -> String {
+        match self {
+            Self::Ban => t!("buttons.ban", locale = lang, action = ban_action).into_owned(),
+            Self::Ignore => t!("buttons.ignore", locale = lang).into_owned(),
+            Self::Undo => t!("buttons.undo", locale = lang).into_owned(),
+        }
+    }
+}
+
+impl fmt::Display for LinkAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ban => write!(f, "ban"),
+            Self::Ignore => write!(f, "ignore"),
+            Self::Undo => write!(f, "undo"),
+        }
+    }
+}
+
+/// Returns the HMAC of the link
+fn link_mac(
+    secret: &str,
+    action: LinkAction,
+    user_id: &str,
+    recipient: &str,
+    expires: i64,
+) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size");
+    mac.update(format!("{action}\n{user_id}\n{recipient}\n{expires}").as_bytes());
+    mac
+}
+
+/// Returns a signed link of the action on the user, for the email recipient
+/// who takes the action. The link expires after the `link_ttl`
+pub fn action_link(email: &Email, action: LinkAction, user_id: &str, recipient: &str) -> Url {
+    let expires = chrono::Utc::now().timestamp() + (email.link_ttl * 60 * 60) as i64;
+    let signature = hex::encode(
+        link_mac(&email.secret, action, user_id, recipient, expires)
+            .finalize()
+            .into_bytes(),
+    );
+
+    let mut url = email.base_url.clone();
+    url.path_segments_mut()
+        .expect("The base URL is an HTTP URL")
+        .pop_if_empty()
+        .push("email")
+        .push(&action.to_string());
+    url.query_pairs_mut()
+        .append_pair("user", user_id)
+        .append_pair("to", recipient)
+        .append_pair("expires", &expires.to_string())
+        .append_pair("signature", &signature);
+    url
+}
+
+/// Returns `true` if the link signature is valid and the link isn't expired
+pub fn is_valid_link(
+    email: &Email,
+    action: LinkAction,
+    user_id: &str,
+    recipient: &str,
+    expires: i64,
+    signature: &str,
+) -> bool {
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+    expires > chrono::Utc::now().timestamp()
+        && link_mac(&email.secret, action, user_id, recipient, expires)
+            .verify_slice(&signature)
+            .is_ok()
+}
+
+/// The email notifier of an instance
+pub struct EmailNotifier<T = AsyncSmtpTransport<Tokio1Executor>> {
+    /// The email transport, the SMTP transport out of the tests
+    transport: T,
+    /// The notifier instance
+    instance:  Instance,
+}
+
+impl EmailNotifier {
+    /// Create a new email notifier, the instance must have an enabled email
+    /// notifier
+    pub fn new(instance: Instance) -> GuardResult<Self> {
+        let config = instance.config();
+        let email = email_config(&config);
+        let mut builder = match email.tls {
+            SmtpTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&email.host)?,
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&email.host)?,
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&email.host),
+        };
+        if let Some(port) = email.port {
+            builder = builder.port(port);
+        }
+        if let Some(username) = &email.username {
+            builder = builder.credentials(Credentials::new(
+                username.clone(),
+                email.password.clone().unwrap_or_default(),
+            ));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            instance,
+        })
+    }
+}
+
+impl<T> EmailNotifier<T>
+where
+    T: AsyncTransport + Send + Sync + 'static,
+    T::Error: fmt::Display,
+{
+    /// Returns the emails language
+    fn lang(&self) -> &'static str {
+        email_config(&self.instance.config()).lang.as_str()
+    }
+
+    /// Send the alert email to each recipient, with the recipient actions links
+    async fn send_alert(&self, subject: &str, msg: &str, alert: UserAlert, links: &[LinkAction]) {
+        let config = &self.instance.config();
+        let email = email_config(config);
+        let lang = email.lang.as_str();
+        let action = action_word(lang, &alert.action(config));
+        let user_id = self.instance.user_id(&alert.user.username);
+        let details = user_details(lang, msg, &alert, &action, config)
+            .trim_end()
+            .to_owned();
+
+        let subject = t!(
+            subject,
+            locale = lang,
+            username = alert.user.username,
+            action = action
+        );
+        let subject = match self.instance.name() {
+            Some(name) => format!("[{name}] {subject}"),
+            None => subject.into_owned(),
+        };
+
+        // Each recipient has its own links, to know who took the action
+        for to in &email.to {
+            let recipient = to.email.to_string();
+            let mut body = details.clone();
+            if !links.is_empty() {
+                body.push('\n');
+            }
+            for link in links {
+                body.push_str(&format!(
+                    "\n{}: {}",
+                    link.button(lang, &action),
+                    action_link(email, *link, &user_id, &recipient)
+                ));
+            }
+
+            let message = match Message::builder()
+                .from(email.from.clone())
+                .to(to.clone())
+                .subject(&subject)
+                .header(ContentType::TEXT_PLAIN)
+                .body(body)
+            {
+                Ok(message) => message,
+                Err(err) => {
+                    tracing::error!("Failed to build the alert email to {recipient}: {err}");
+                    metrics::notifier_failed(Self::NAME);
+                    continue;
+                }
+            };
+            if let Err(err) = self.transport.send(message).await {
+                tracing::error!("Failed to send the alert email to {recipient}: {err}");
+                metrics::notifier_failed(Self::NAME);
+            }
+        }
+    }
+}
+
+/// Returns the email configuration of the instance config, the instance must
+/// have an enabled email notifier
+pub fn email_config(config: &Config) -> &Email {
+    config
+        .email()
+        .expect("The instance has an enabled email notifier")
+}
+
+impl<T> Notifier for EmailNotifier<T>
+where
+    T: AsyncTransport + Send + Sync + 'static,
+    T::Error: fmt::Display,
+{
+    const NAME: &'static str = "email";
+
+    async fn send_sus_alert(&self, alert: UserAlert) {
+        let lang = self.lang();
+        let msg = if alert.is_active {
+            format!(
+                "({}) {}",
+                t!("words.active", locale = lang),
+                t!("messages.sus_alert", locale = lang)
+            )
+        } else {
+            t!("messages.sus_alert", locale = lang).into_owned()
+        };
+        self.send_alert(
+            "email.sus_subject",
+            &msg,
+            alert,
+            &[LinkAction::Ban, LinkAction::Ignore],
+        )
+        .await
+    }
+
+    async fn send_ban_notify(&self, alert: UserAlert) {
+        self.send_alert("email.ban_subject", "messages.ban_notify", alert, &[])
+            .await
+    }
+
+    async fn send_ban_request(&self, alert: UserAlert) {
+        let lang = self.lang();
+        let msg = if !alert.has_expressions() {
+            t!("messages.ban_request", locale = lang)
+                .split("\n")
+                .skip(1)
+                .collect::<Vec<_>>()
+                .join("\n")
+        } else {
+            t!("messages.ban_request", locale = lang).into_owned()
+        };
+        let links = if self
+            .instance
+            .database
+            .is_lazy_purged(&alert.user.username)
+            .is_ok_and(|y| y)
+        {
+            [LinkAction::Undo].as_slice()
+        } else {
+            [LinkAction::Ban, LinkAction::Ignore].as_slice()
+        };
+
+        self.send_alert("email.ban_request_subject", &msg, alert, links)
+            .await
+    }
+
+    async fn on_decision(&self, _decision: Decision) {
+        // The sent emails can't be edited
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use lettre::transport::stub::AsyncStubTransport;
+    use redb::{backends::InMemoryBackend, Database};
+
+    use super::*;
+    use crate::{db::GuardDb, forgejo_api::ForgejoUser};
+
+    fn instance(lang: &str, link_ttl: u64) -> Instance {
+        let config: Config = toml::from_str(&format!(
+            r#"
+            [forgejo]
+            instance_url = "https://forgejo.example"
+            token = "token"
+
+            [email]
+            host = "smtp.example"
+            from = "Guardian <guardian@example.com>"
+            to = ["Admin <admin@example.com>", "mod@example.com"]
+            lang = "{lang}"
+            base_url = "https://guardian.example/"
+            secret = "s3cret"
+            link_ttl = {link_ttl}
+            "#
+        ))
+        .unwrap();
+        let database = Database::builder()
+            .create_with_backend(InMemoryBackend::new())
+            .unwrap();
+        Instance::new(config, GuardDb::new(Arc::new(database), None).unwrap())
+    }
+
+    fn notifier(lang: &str, transport: AsyncStubTransport) -> EmailNotifier<AsyncStubTransport> {
+        EmailNotifier {
+            transport,
+            instance: instance(lang, 24),
+        }
+    }
+
+    fn alert() -> UserAlert {
+        let user: ForgejoUser = serde_json::from_str(
+            r#"{
+                "id": 42,
+                "avatar_url": "https://forgejo.example/avatar.png",
+                "html_url": "https://forgejo.example/spammer",
+                "is_admin": false,
+                "source_id": 0,
+                "login": "spammer",
+                "full_name": "",
+                "description": "cheap followers",
+                "email": "spammer@example.com",
+                "website": "",
+                "location": "",
+                "created": "2025-01-01T00:00:00Z"
+            }"#,
+        )
+        .unwrap();
+        UserAlert::new(user, Vec::new())
+    }
+
+    /// Decode the quoted-printable body of the sent email
+    fn decode_body(raw: &str) -> String {
+        let (_, body) = raw.split_once("\r\n\r\n").unwrap();
+        let body = body.replace("=\r\n", "");
+        let mut bytes = Vec::with_capacity(body.len());
+        let mut iter = body.bytes();
+        while let Some(byte) = iter.next() {
+            if byte == b'=' {
+                let hex = [iter.next().unwrap(), iter.next().unwrap()];
+                bytes.extend(hex::decode(hex).unwrap());
+            } else {
+                bytes.push(byte);
+            }
+        }
+        String::from_utf8(bytes).unwrap()
+    }
+
+    /// Returns the action links of the email body
+    fn body_links(body: &str) -> Vec<Url> {
+        body.split_whitespace()
+            .filter(|word| word.starts_with("https://guardian.example/email/"))
+            .map(|link| Url::parse(link).unwrap())
+            .collect()
+    }
+
+    /// Returns the value of the link query parameter
+    fn query(link: &Url, name: &str) -> String {
+        link.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+            .unwrap()
+    }
+
+    /// Returns `true` if the link is a valid signed link of the action
+    fn is_valid(email: &Email, link: &Url, action: LinkAction) -> bool {
+        let query = |name: &str| query(link, name);
+        link.path() == format!("/email/{action}")
+            && is_valid_link(
+                email,
+                action,
+                &query("user"),
+                &query("to"),
+                query("expires").parse().unwrap(),
+                &query("signature"),
+            )
+    }
+
+    #[tokio::test]
+    async fn sus_alert_email() {
+        let notifier = notifier("en-us", AsyncStubTransport::new_ok());
+        notifier.send_sus_alert(alert()).await;
+
+        let messages = notifier.transport.messages().await;
+        let config = notifier.instance.config();
+        let email = email_config(&config);
+        // An email for each recipient, with its own links
+        assert_eq!(messages.len(), 2);
+        for ((envelope, raw), recipient) in messages
+            .iter()
+            .zip(["admin@example.com", "mod@example.com"])
+        {
+            assert_eq!(envelope.from().unwrap().to_string(), "guardian@example.com");
+            assert_eq!(envelope.to().len(), 1);
+            assert_eq!(envelope.to()[0].to_string(), recipient);
+            assert!(raw.contains("Subject: Suspicious user @spammer\r\n"));
+
+            let body = decode_body(raw);
+            assert!(body.contains("Suspicious user detected!"));
+            let links = body_links(&body);
+            assert_eq!(links.len(), 2);
+            assert!(is_valid(email, &links[0], LinkAction::Ban));
+            assert!(is_valid(email, &links[1], LinkAction::Ignore));
+            assert!(links.iter().all(|link| query(link, "to") == recipient));
+        }
+    }
+
+    #[tokio::test]
+    async fn ban_notify_email() {
+        let notifier = notifier("en-us", AsyncStubTransport::new_ok());
+        notifier.send_ban_notify(alert()).await;
+
+        let messages = notifier.transport.messages().await;
+        assert_eq!(messages.len(), 2);
+        let body = decode_body(&messages[0].1);
+        assert!(body.contains("User has been banned"));
+        // The banned users emails have no actions links
+        assert!(body_links(&body).is_empty());
+    }
+
+    #[tokio::test]
+    async fn translated_email() {
+        let notifier = notifier("de-de", AsyncStubTransport::new_ok());
+        notifier.send_sus_alert(alert()).await;
+
+        let messages = notifier.transport.messages().await;
+        let body = decode_body(&messages[0].1);
+        assert!(body.contains("Verdächtiger Benutzer erkannt!"));
+        assert!(!body.contains("Suspicious user detected!"));
+    }
+
+    #[tokio::test]
+    async fn failed_transport() {
+        let notifier = notifier("en-us", AsyncStubTransport::new_error());
+        // The failure is logged, the alert is dropped
+        notifier.send_sus_alert(alert()).await;
+    }
+
+    #[test]
+    fn signed_links() {
+        let instance = instance("en-us", 24);
+        let config = instance.config();
+        let email = email_config(&config);
+        let link = action_link(email, LinkAction::Ban, "spammer", "admin@example.com");
+
+        assert!(is_valid(email, &link, LinkAction::Ban));
+        // The signature is bound to the action
+        assert!(!is_valid(email, &link, LinkAction::Undo));
+
+        // The signature is bound to the user and the recipient
+        for (param, value) in [("user", "someone"), ("to", "mod@example.com")] {
+            let mut tampered = link.clone();
+            let pairs = link
+                .query_pairs()
+                .map(|(key, old_value)| {
+                    if key == param {
+                        (key.into_owned(), value.to_owned())
+                    } else {
+                        (key.into_owned(), old_value.into_owned())
+                    }
+                })
+                .collect::<Vec<_>>();
+            tampered.query_pairs_mut().clear().extend_pairs(pairs);
+            assert!(!is_valid(email, &tampered, LinkAction::Ban));
+        }
+    }
+
+    #[test]
+    fn expired_links() {
+        let instance = instance("en-us", 0);
+        let config = instance.config();
+        let email = email_config(&config);
+        let link = action_link(email, LinkAction::Ban, "spammer", "admin@example.com");
+
+        assert!(!is_valid(email, &link, LinkAction::Ban));
+    }
+}
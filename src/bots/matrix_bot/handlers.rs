@@ -2,7 +2,6 @@
 // Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
 
 use matrix_sdk::{
-    event_handler::Ctx,
     ruma::{
         events::{
             reaction::OriginalSyncReactionEvent,
@@ -13,14 +12,15 @@ use matrix_sdk::{
     Room,
     RoomState,
 };
-use reqwest::Client;
 
 use super::{utils, MatrixBot};
 use crate::{
-    bots::{matrix_bot::users_handler, UserAlert},
+    bots::{actions, matrix_bot::users_handler, notifier::Notifier, DecisionKind, UserAlert},
     config::RegexReason,
-    db::{AlertedUsersTableTrait, EventsTableTrait, IgnoredUsersTableTrait, PurgedUsersTableTrait},
+    db::EventsTableTrait,
     forgejo_api,
+    instance,
+    snapshots,
 };
 
 /// Ban command handler
@@ -35,13 +35,21 @@ pub async fn ban_command_handler(
     }
 
     tracing::info!("{moderator} requesting a ban request for `@{username}`");
-    let Ok(user) = forgejo_api::get_user(username, &bot.config.forgejo).await else {
-        bot.reply_to(event_id, t!("messages.user_not_found", username = username))
-            .await;
+    let (config, lang) = (bot.config(), bot.lang());
+    let Ok(user) = forgejo_api::get_user(username, &config.forgejo).await else {
+        bot.reply_to(
+            event_id,
+            t!(
+                "messages.user_not_found",
+                locale = lang,
+                username = username
+            ),
+        )
+        .await;
         return;
     };
     if user.is_admin {
-        bot.reply_to(event_id, t!("messages.can_not_ban_admin"))
+        bot.reply_to(event_id, t!("messages.can_not_ban_admin", locale = lang))
             .await;
         return;
     }
@@ -49,29 +57,99 @@ pub async fn ban_command_handler(
         bot,
         UserAlert::new(
             user,
-            RegexReason::new(
+            vec![RegexReason::new(
                 Vec::new(),
                 Some(
                     t!(
                         "messages.ban_command_reason",
+                        locale = lang,
                         moderator = moderator,
                         prefix = "!"
                     )
                     .into_owned(),
                 ),
-            ),
+            )],
         ),
-        &bot.config.expressions.ban_action,
+        &config.expressions.ban_action,
     )
     .await;
 }
 
+/// Snapshot command handler
+pub async fn snapshot_command_handler(
+    event_id: &EventId,
+    bot: &MatrixBot,
+    moderator: &str,
+    username: &str,
+) {
+    if username.is_empty() {
+        return;
+    }
+
+    tracing::info!("{moderator} requesting the snapshot of `@{username}`");
+    let lang = bot.lang();
+    match snapshots::get(&bot.config(), &bot.instance.database, username) {
+        Ok(Some(snapshot)) => {
+            bot.send_file(
+                format!("{username}.json"),
+                "application/json",
+                snapshot.into_bytes(),
+            )
+            .await
+        }
+        Ok(None) => {
+            bot.reply_to(
+                event_id,
+                t!(
+                    "messages.snapshot_not_found",
+                    locale = lang,
+                    username = username
+                ),
+            )
+            .await
+        }
+        Err(err) => {
+            tracing::error!("Failed to get the snapshot of @{username}: {err}");
+            bot.reply_to(event_id, t!("messages.snapshot_failed", locale = lang))
+                .await
+        }
+    }
+}
+
+/// Unban command handler, unsuspend a suspended user
+pub async fn unban_command_handler(
+    event_id: &EventId,
+    bot: &MatrixBot,
+    moderator: &str,
+    username: &str,
+) {
+    if username.is_empty() {
+        return;
+    }
+
+    tracing::info!("{moderator} requesting to unsuspend `@{username}`");
+    let lang = bot.lang();
+    let reply = match forgejo_api::get_user(username, &bot.config().forgejo).await {
+        Err(_) => {
+            t!(
+                "messages.user_not_found",
+                locale = lang,
+                username = username
+            )
+        }
+        Ok(user) if !user.prohibit_login => {
+            t!("messages.not_suspended", locale = lang, username = username)
+        }
+        Ok(_) if actions::unsuspend(&bot.instance, username, moderator, MatrixBot::NAME).await => {
+            t!("messages.unsuspend_success", locale = lang)
+        }
+        Ok(_) => t!("messages.unsuspend_failed", locale = lang),
+    };
+    bot.reply_to(event_id, reply).await;
+}
+
 impl MatrixBot {
-    pub async fn on_room_reaction(
-        event: OriginalSyncReactionEvent,
-        room: Room,
-        Ctx(bot): Ctx<MatrixBot>,
-    ) {
+    pub async fn on_room_reaction(event: OriginalSyncReactionEvent, room: Room, bot: MatrixBot) {
         if bot.client.user_id().is_some_and(|u| u == event.sender) {
             // Reaction from the bot
             return;
@@ -102,8 +180,11 @@ impl MatrixBot {
             return;
         };
 
-        let username = match bot.db.get_username(&reply_to_event_id) {
+        let lang = bot.lang();
+        let username = match bot.instance.database.get_username(&reply_to_event_id) {
             Ok(Some(username)) => username,
+            // The event may belong to another instance that shares the room
+            Ok(None) if bot.is_shared_room() => return,
             Ok(None) => {
                 tracing::warn!(
                     "{moderator} react to `{reply_to_event_id}` while there is no user for this \
@@ -117,41 +198,25 @@ impl MatrixBot {
             }
         };
 
-        if reaction == &bot.ban_reaction() && !bot.db.is_lazy_purged(&username).is_ok_and(|y| y) {
-            let ban_status = if bot.config.dry_run
-                || bot.config.lazy_purge.enabled
-                || forgejo_api::ban_user(
-                    &Client::new(),
-                    &bot.config.forgejo.instance,
-                    &bot.config.forgejo.token,
-                    &username,
-                    &bot.config.expressions.ban_action,
-                )
-                .await
-                .is_ok()
-            {
-                if bot.config.lazy_purge.enabled {
-                    tracing::info!(
-                        "The moderator {moderator} has added @{username} to purge queue",
-                    );
-                    bot.db.add_purged_user(&username).ok();
-                    bot.moderation_room
-                        .send(utils::make_reaction(
-                            &reply_to_event_id,
-                            &bot.undo_reaction(),
-                        ))
-                        .await
-                        .ok();
-                    t!("messages.added_to_purge_queue")
-                } else {
-                    tracing::info!("The moderator {moderator} has banned @{username}",);
-                    bot.db.remove_alerted_user(&username).ok();
-                    bot.db.remove_user_events(&username).ok();
-                    t!("messages.ban_success")
-                }
-            } else {
-                t!("messages.ban_failed")
-            };
+        if reaction == &bot.ban_reaction() && actions::can_ban(&bot.instance, &username) {
+            let ban_status =
+                match actions::ban(&bot.instance, &username, moderator, MatrixBot::NAME).await {
+                    Some(kind @ DecisionKind::AddedToPurgeQueue) => {
+                        bot.moderation_room
+                            .send(utils::make_reaction(
+                                &reply_to_event_id,
+                                &bot.undo_reaction(),
+                            ))
+                            .await
+                            .ok();
+                        kind.message(lang)
+                    }
+                    Some(kind) => {
+                        bot.instance.database.remove_user_events(&username).ok();
+                        kind.message(lang)
+                    }
+                    None => t!("messages.ban_failed", locale = lang),
+                };
             let new_caption = format!("{ban_status} ({moderator})\n\n{msg_text}");
             bot.edit_msg_caption(
                 &reply_to_event_id,
@@ -159,28 +224,23 @@ impl MatrixBot {
                 Some([event.sender.clone()]),
             )
             .await;
-        } else if reaction == &bot.ignore_reaction()
-            && !bot.db.is_lazy_purged(&username).is_ok_and(|y| y)
-        {
-            tracing::info!("The moderator {moderator} has ignored @{username}",);
-
-            let new_caption = format!("{} ({moderator})\n\n{msg_text}", t!("messages.ban_denied"));
+        } else if reaction == &bot.ignore_reaction() && actions::can_ban(&bot.instance, &username) {
+            let new_caption = format!(
+                "{} ({moderator})\n\n{msg_text}",
+                t!("messages.ban_denied", locale = lang)
+            );
             bot.edit_msg_caption(
                 &reply_to_event_id,
                 new_caption,
                 Some([event.sender.clone()]),
             )
             .await;
-            bot.db.add_ignored_user(&username).ok();
-            bot.db.remove_alerted_user(&username).ok();
-            bot.db.remove_user_events(&username).ok();
-        } else if reaction == &bot.undo_reaction()
-            && (bot.config.lazy_purge.enabled && bot.db.is_lazy_purged(&username).is_ok_and(|y| y))
-        {
-            tracing::info!("The moderator {moderator} has undone @{username} purge",);
+            actions::ignore(&bot.instance, &username, moderator, MatrixBot::NAME);
+            bot.instance.database.remove_user_events(&username).ok();
+        } else if reaction == &bot.undo_reaction() && actions::can_undo(&bot.instance, &username) {
             let new_caption = format!(
                 "{} ({moderator})\n\n{msg_text}",
-                t!("messages.undo_success")
+                t!("messages.undo_success", locale = lang)
             );
             bot.edit_msg_caption(
                 &reply_to_event_id,
@@ -189,16 +249,40 @@ impl MatrixBot {
             )
             .await;
 
-            bot.db.remove_purged_user(&username).ok();
-            bot.db.remove_user_events(&username).ok();
+            actions::undo(&bot.instance, &username, moderator, MatrixBot::NAME);
+            bot.instance.database.remove_user_events(&username).ok();
+        } else if reaction == &bot.unsuspend_reaction() {
+            let status = match forgejo_api::get_user(&username, &bot.config().forgejo).await {
+                Err(_) => {
+                    t!(
+                        "messages.user_not_found",
+                        locale = lang,
+                        username = username
+                    )
+                }
+                Ok(user) if !user.prohibit_login => {
+                    t!("messages.not_suspended", locale = lang, username = username)
+                }
+                Ok(_)
+                    if actions::unsuspend(&bot.instance, &username, moderator, MatrixBot::NAME)
+                        .await =>
+                {
+                    bot.instance.database.remove_user_events(&username).ok();
+                    t!("messages.unsuspend_success", locale = lang)
+                }
+                Ok(_) => t!("messages.unsuspend_failed", locale = lang),
+            };
+            let new_caption = format!("{status} ({moderator})\n\n{msg_text}");
+            bot.edit_msg_caption(
+                &reply_to_event_id,
+                new_caption,
+                Some([event.sender.clone()]),
+            )
+            .await;
         }
     }
 
-    pub async fn on_room_message(
-        event: OriginalSyncRoomMessageEvent,
-        room: Room,
-        Ctx(bot): Ctx<MatrixBot>,
-    ) {
+    pub async fn on_room_message(event: OriginalSyncRoomMessageEvent, room: Room, bot: MatrixBot) {
         if room.state() != RoomState::Joined
             || bot.client.user_id().is_some_and(|u| u == event.sender)
         {
@@ -213,9 +297,40 @@ impl MatrixBot {
             tracing::info!("Moderator {moderator} requested a ping");
             bot.reply_to(&event.event_id, "Pong!").await;
         }
-        if let Some(("!ban", username)) = text.body.split_once(" ") {
-            tracing::info!("{moderator} requested a ban request for `@{username}`");
-            ban_command_handler(&event.event_id, &bot, moderator, username).await;
+        if let Some((command @ ("!ban" | "!snapshot" | "!unban"), user_id)) =
+            text.body.split_once(" ")
+        {
+            let (instance_name, username) = instance::split_user_id(user_id);
+            let (config, lang) = (bot.config(), bot.lang());
+            match instance_name {
+                Some(name) if config.forgejo.name.as_deref() != Some(name) => {}
+                None if bot.is_shared_room() => {
+                    // Only the first instance of the room replies
+                    if bot.room_instances.first() == config.forgejo.name.as_ref() {
+                        bot.reply_to(
+                            &event.event_id,
+                            t!(
+                                "messages.instance_required",
+                                locale = lang,
+                                prefix = "!",
+                                command = &command[1..],
+                                instances = bot.room_instances.join(", ")
+                            ),
+                        )
+                        .await;
+                    }
+                }
+                _ if command == "!ban" => {
+                    tracing::info!("{moderator} requested a ban request for `@{username}`");
+                    ban_command_handler(&event.event_id, &bot, moderator, username).await;
+                }
+                _ if command == "!unban" => {
+                    unban_command_handler(&event.event_id, &bot, moderator, username).await;
+                }
+                _ => {
+                    snapshot_command_handler(&event.event_id, &bot, moderator, username).await;
+                }
+            }
         }
     }
 }
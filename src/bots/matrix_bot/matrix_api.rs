@@ -33,6 +33,14 @@ impl MatrixBot {
         }
     }
 
+    /// Send a text message to the moderation room
+    pub async fn send_message(&self, msg: impl Into<String>) {
+        let content = RoomMessageEventContent::text_plain(msg);
+        if let Err(err) = self.moderation_room.send(content).await {
+            tracing::error!("Failed to send a message: {err}");
+        }
+    }
+
     pub async fn send_image(
         &self,
         image: url::Url,
@@ -68,6 +76,21 @@ impl MatrixBot {
         None
     }
 
+    pub async fn send_file(&self, filename: impl Into<String>, content_type: &str, data: Vec<u8>) {
+        if let Err(err) = self
+            .moderation_room
+            .send_attachment(
+                filename,
+                &content_type.parse().unwrap(),
+                data,
+                AttachmentConfig::new(),
+            )
+            .await
+        {
+            tracing::error!("Falied to send a file message: {err}")
+        }
+    }
+
     pub async fn edit_msg_caption(
         &self,
         msg_id: &EventId,
@@ -4,8 +4,6 @@
 use std::{borrow::Cow, sync::Arc, time::Duration};
 
 use matrix_sdk::{config::SyncSettings, Client as MatrixClient, Room};
-use redb::Database;
-use tokio::sync::mpsc::Receiver;
 use tokio_util::sync::CancellationToken;
 
 mod handlers;
@@ -13,10 +11,11 @@ mod matrix_api;
 mod users_handler;
 mod utils;
 
-use super::UserAlert;
+use super::{notifier::run_notifier, InstanceReceivers, Lang};
 use crate::{
     config::{Config, MatrixData},
     error::{GuardError, GuardResult},
+    instance::Instance,
 };
 
 /// Maximum retries for matrix sync
@@ -27,65 +26,98 @@ const RETRY_INTERVAL: u64 = 5;
 #[derive(Clone)]
 pub struct MatrixBot {
     client:          MatrixClient,
-    config:          Arc<Config>,
-    db:              Arc<Database>,
+    /// The guarded instance, its config, database and decisions
+    instance:        Instance,
     moderation_room: Room,
+    /// Names of the instances that share the moderation room
+    room_instances:  Arc<Vec<String>>,
+    /// The bot language
+    lang:            Lang,
+}
+
+/// Login to the homeserver and sync the client
+async fn login(matrix: &MatrixData) -> GuardResult<MatrixClient> {
+    let client = MatrixClient::builder()
+        .homeserver_url(&matrix.homeserver)
+        .build()
+        .await
+        .map_err(|err| GuardError::Other(err.to_string()))?;
+
+    client
+        .matrix_auth()
+        .login_username(&matrix.username, &matrix.password)
+        .initial_device_display_name("Forgejo Guardian <git.4rs.nl/awiteb/forgejo-guardian>")
+        .await?;
+
+    client.sync_once(SyncSettings::new()).await?;
+    Ok(client)
 }
 
 impl MatrixBot {
-    /// Create a new matrix bot
-    pub async fn new(
-        config: Arc<Config>,
-        matrix: &MatrixData,
-        database: Arc<Database>,
+    /// Create a new matrix bot for the instance, the client must be logged in
+    pub fn new(
+        client: MatrixClient,
+        instance: &Instance,
+        room_instances: Arc<Vec<String>>,
+        lang: Lang,
     ) -> GuardResult<Self> {
-        let client = MatrixClient::builder()
-            .homeserver_url(&matrix.homeserver)
-            .build()
-            .await
-            .map_err(|err| GuardError::Other(err.to_string()))?;
-
-        client
-            .matrix_auth()
-            .login_username(&matrix.username, &matrix.password)
-            .initial_device_display_name("Forgejo Guardian <git.4rs.nl/awiteb/forgejo-guardian>")
-            .await?;
-
-        client.sync_once(SyncSettings::new()).await?;
-
+        let config = instance.config();
+        let matrix = matrix_data(&config);
         let moderation_room = client
             .get_room(&matrix.room)
             .ok_or_else(|| GuardError::Matrix("Falied to get the moderation room".to_owned()))?;
 
         Ok(Self {
-            config,
             client,
-            db: database,
+            instance: instance.clone(),
             moderation_room,
+            room_instances,
+            lang,
         })
     }
 
+    /// Returns the current instance configuration
+    pub fn config(&self) -> Arc<Config> {
+        self.instance.config()
+    }
+
+    /// Returns the bot language
+    pub fn lang(&self) -> &'static str {
+        self.lang.as_str()
+    }
+
+    /// Returns `true` if the moderation room is shared with other instances
+    pub fn is_shared_room(&self) -> bool {
+        self.room_instances.len() > 1
+    }
+
     /// Returns the ban reaction
     pub fn ban_reaction(&self) -> Cow<'_, str> {
-        t!("buttons.ban", action = self.config.expressions.ban_action)
+        t!(
+            "buttons.ban",
+            locale = self.lang(),
+            action = self.config().expressions.ban_action
+        )
     }
 
     /// Returns the ignore reaction
     pub fn ignore_reaction(&self) -> Cow<'_, str> {
-        t!("buttons.ignore")
+        t!("buttons.ignore", locale = self.lang())
     }
 
     /// Returns the undo reaction
     pub fn undo_reaction(&self) -> Cow<'_, str> {
-        t!("buttons.undo")
+        t!("buttons.undo", locale = self.lang())
     }
 
-    /// Run the matrix bot, this will join the moderation room and start
-    /// listening to events
-    pub async fn run(self, cancellation_token: CancellationToken) {
-        let mut retries = 0;
-        let client = self.client.clone();
-        for room in client.invited_rooms() {
+    /// Returns the unsuspend reaction
+    pub fn unsuspend_reaction(&self) -> Cow<'_, str> {
+        t!("buttons.unsuspend", locale = self.lang())
+    }
+
+    /// Join the moderation room and listen to its events
+    pub async fn register(self) {
+        for room in self.client.invited_rooms() {
             if room.room_id() == self.moderation_room.room_id() {
                 room.join()
                     .await
@@ -93,53 +125,97 @@ impl MatrixBot {
             }
         }
 
-        client.add_room_event_handler(self.moderation_room.room_id(), Self::on_room_message);
-        client.add_room_event_handler(self.moderation_room.room_id(), Self::on_room_reaction);
-        client.add_event_handler_context(self);
+        let client = self.client.clone();
+        let room_id = self.moderation_room.room_id().to_owned();
+        let bot = self.clone();
+        client.add_room_event_handler(&room_id, move |event, room| {
+            Self::on_room_message(event, room, bot.clone())
+        });
+        client.add_room_event_handler(&room_id, move |event, room| {
+            Self::on_room_reaction(event, room, self.clone())
+        });
+    }
+}
 
-        loop {
-            if cancellation_token.is_cancelled() || retries > MAX_RETRIES {
-                break;
-            }
+/// Returns the Matrix data of the instance config, the instance must have an
+/// enabled Matrix bot
+fn matrix_data(config: &Config) -> &MatrixData {
+    config
+        .matrix
+        .data()
+        .expect("The instance has an enabled Matrix bot")
+}
 
-            if let Err(err) = client.sync(SyncSettings::default()).await {
-                retries += 1;
-                tracing::error!(
-                    "Falied to sync the matrix bot (retries {retries}/{MAX_RETRIES}): {err}"
-                )
-            }
-            tracing::info!("Retrying in {} seconds.", RETRY_INTERVAL * retries);
-            tokio::time::sleep(Duration::from_secs(RETRY_INTERVAL * retries)).await;
+/// Sync the client, to receive the events, until the cancellation token is
+/// cancelled
+async fn sync(client: MatrixClient, cancellation_token: CancellationToken) {
+    let mut retries = 0;
+    loop {
+        if cancellation_token.is_cancelled() || retries > MAX_RETRIES {
+            break;
+        }
+
+        if let Err(err) = client.sync(SyncSettings::default()).await {
+            retries += 1;
+            tracing::error!(
+                "Falied to sync the matrix bot (retries {retries}/{MAX_RETRIES}): {err}"
+            )
         }
+        tracing::info!("Retrying in {} seconds.", RETRY_INTERVAL * retries);
+        tokio::time::sleep(Duration::from_secs(RETRY_INTERVAL * retries)).await;
     }
 }
 
-/// Start the matrix bot
+/// Start the matrix bot, for all the instances that use it
 pub async fn start_bot(
-    database: Arc<Database>,
-    config: Arc<Config>,
     matrix: MatrixData,
+    instances: Vec<InstanceReceivers>,
     cancellation_token: CancellationToken,
-    sus_receiver: Receiver<UserAlert>,
-    ban_receiver: Receiver<UserAlert>,
 ) {
     tracing::info!("Starting the matrix bot");
 
-    let bot = match MatrixBot::new(Arc::clone(&config), &matrix, database).await {
-        Ok(bot) => bot,
+    let client = match login(&matrix).await {
+        Ok(client) => client,
         Err(err) => {
             tracing::error!("Falied to run the matrix bot: {err}");
             return;
         }
     };
 
-    tokio::spawn(users_handler::users_handler(
-        bot.clone(),
-        config,
-        cancellation_token.clone(),
-        sus_receiver,
-        ban_receiver,
-    ));
+    // Names of the instances that share the moderation room of each instance
+    let rooms_instances: Vec<_> = instances
+        .iter()
+        .map(|(instance, _)| {
+            let room = matrix_data(&instance.config()).room.clone();
+            instances
+                .iter()
+                .filter(|(i, _)| matrix_data(&i.config()).room == room)
+                .filter_map(|(i, _)| i.name().map(String::from))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    for ((instance, receivers), room_instances) in instances.into_iter().zip(rooms_instances) {
+        let bot = match MatrixBot::new(
+            client.clone(),
+            &instance,
+            Arc::new(room_instances),
+            matrix.lang,
+        ) {
+            Ok(bot) => bot,
+            Err(err) => {
+                tracing::error!("Falied to run the matrix bot: {err}");
+                return;
+            }
+        };
+
+        tokio::spawn(run_notifier(
+            bot.clone(),
+            receivers,
+            cancellation_token.clone(),
+        ));
+        bot.register().await;
+    }
 
-    bot.run(cancellation_token.clone()).await
+    sync(client, cancellation_token).await
 }
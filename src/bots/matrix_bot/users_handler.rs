@@ -1,17 +1,22 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
 
-use std::sync::Arc;
-
-use matrix_sdk::ruma::OwnedEventId;
-use tokio::sync::mpsc::Receiver;
-use tokio_util::sync::CancellationToken;
+use matrix_sdk::ruma::{OwnedEventId, OwnedUserId};
 
 use super::{utils, MatrixBot};
 use crate::{
-    bots::{action_word, user_details, UserAlert},
-    config::{BanAction, Config},
+    bots::{
+        action_word,
+        notifier::Notifier,
+        user_details,
+        Decision,
+        DecisionKind,
+        Report,
+        UserAlert,
+    },
+    config::BanAction,
     db::{EventsTableTrait, PurgedUsersTableTrait},
+    metrics,
 };
 
 /// Send an alert to the moderation room
@@ -21,29 +26,37 @@ async fn send_alert(
     action: &BanAction,
     msg: &str,
 ) -> Option<OwnedEventId> {
-    let caption = user_details(
-        msg,
-        &alert.user,
-        &alert.reason,
-        &action_word(action),
-        &bot.config,
-    );
-    bot.send_image(alert.user.avatar_url.clone(), caption).await
+    let lang = bot.lang();
+    let caption = user_details(lang, msg, alert, &action_word(lang, action), &bot.config());
+    let event_id = bot.send_image(alert.user.avatar_url.clone(), caption).await;
+    if event_id.is_none() {
+        metrics::notifier_failed(MatrixBot::NAME);
+    }
+    event_id
 }
 
 /// Send a suspicious alert and add the event to the database
 pub async fn send_sus_alert(bot: &MatrixBot, alert: UserAlert, action: &BanAction) {
+    let lang = bot.lang();
     let msg = if alert.is_active {
-        format!("({}) {}", t!("words.active"), t!("messages.sus_alert"))
+        format!(
+            "({}) {}",
+            t!("words.active", locale = lang),
+            t!("messages.sus_alert", locale = lang)
+        )
     } else {
-        t!("messages.sus_alert").into_owned()
+        t!("messages.sus_alert", locale = lang).into_owned()
     };
 
     let Some(event_id) = send_alert(bot, &alert, action, &msg).await else {
         return;
     };
     bot.send_ok_no_reaction(&event_id).await;
-    if let Err(err) = bot.db.add_event(&event_id, &alert.user.username) {
+    if let Err(err) = bot
+        .instance
+        .database
+        .add_event(&event_id, &alert.user.username)
+    {
         tracing::error!("{err}");
     }
 }
@@ -52,21 +65,22 @@ pub async fn send_sus_alert(bot: &MatrixBot, alert: UserAlert, action: &BanActio
 /// empty then this is a ban request with non-matching users, so the first line
 /// of the message will be deleted
 pub async fn send_ban_request(bot: &MatrixBot, alert: UserAlert, action: &BanAction) {
-    let msg = if alert.reason.re_vec.is_empty() {
-        t!("messages.ban_request")
+    let lang = bot.lang();
+    let msg = if !alert.has_expressions() {
+        t!("messages.ban_request", locale = lang)
             .split("\n")
             .skip(1)
             .collect::<Vec<_>>()
             .join("\n")
     } else {
-        t!("messages.ban_request").into_owned()
+        t!("messages.ban_request", locale = lang).into_owned()
     };
 
     let Some(event_id) = send_alert(bot, &alert, action, &msg).await else {
         return;
     };
 
-    if let Ok(true) = bot.db.is_lazy_purged(&alert.user.username) {
+    if let Ok(true) = bot.instance.database.is_lazy_purged(&alert.user.username) {
         bot.moderation_room
             .send(utils::make_reaction(&event_id, &bot.undo_reaction()))
             .await
@@ -75,40 +89,98 @@ pub async fn send_ban_request(bot: &MatrixBot, alert: UserAlert, action: &BanAct
         bot.send_ok_no_reaction(&event_id).await;
     }
 
-    if let Err(err) = bot.db.add_event(&event_id, &alert.user.username) {
+    if let Err(err) = bot
+        .instance
+        .database
+        .add_event(&event_id, &alert.user.username)
+    {
         tracing::error!("{err}");
     }
 }
 
-/// Send a ban notify alert
+/// Send a ban notify alert. The suspended user notify will have the unsuspend
+/// reaction and will be added to the database
 pub async fn send_ban_notify(bot: &MatrixBot, alert: UserAlert, action: &BanAction) {
-    send_alert(bot, &alert, action, "messages.ban_notify").await;
+    let Some(event_id) = send_alert(bot, &alert, action, "messages.ban_notify").await else {
+        return;
+    };
+    if bot.config().dry_run || !action.is_suspend() {
+        return;
+    }
+
+    bot.moderation_room
+        .send(utils::make_reaction(&event_id, &bot.unsuspend_reaction()))
+        .await
+        .ok();
+    if let Err(err) = bot
+        .instance
+        .database
+        .add_event(&event_id, &alert.user.username)
+    {
+        tracing::error!("{err}");
+    }
 }
 
-/// Handle the suspicious and banned users
-pub async fn users_handler(
-    bot: MatrixBot,
-    config: Arc<Config>,
-    cancellation_token: CancellationToken,
-    mut sus_receiver: Receiver<UserAlert>,
-    mut ban_receiver: Receiver<UserAlert>,
-) {
-    loop {
-        tokio::select! {
-            Some(alert) = sus_receiver.recv() => {
-                send_sus_alert(&bot, alert, &config.expressions.ban_action).await;
-            }
-            Some(alert) = ban_receiver.recv() => {
-                if alert.is_active {
-                    send_ban_request(&bot, alert, &config.expressions.ban_action).await;
-                } else {
-                    send_ban_notify(&bot, alert, &config.expressions.ban_action).await;
-                }
-            }
-            _ = cancellation_token.cancelled() => {
-                tracing::info!("Matrix users handler has been stopped successfully.");
-                break;
-            }
+/// Reflect a decision taken on another platform on the user alerts
+async fn reflect_decision(bot: &MatrixBot, decision: Decision) {
+    let Ok(events) = bot.instance.database.user_events(&decision.username) else {
+        return;
+    };
+
+    for event_id in events {
+        let Some(event) = utils::get_msg_event(&bot.moderation_room, &event_id).await else {
+            continue;
+        };
+        let Some(msg_text) = utils::get_image_caption(&event.content) else {
+            continue;
+        };
+        let new_caption = format!(
+            "{} ({})\n\n{msg_text}",
+            decision.kind.message(bot.lang()),
+            decision.moderator
+        );
+        bot.edit_msg_caption(&event_id, new_caption, None::<[OwnedUserId; 0]>)
+            .await;
+        if decision.kind == DecisionKind::AddedToPurgeQueue {
+            bot.moderation_room
+                .send(utils::make_reaction(&event_id, &bot.undo_reaction()))
+                .await
+                .ok();
         }
     }
+    if decision.kind.is_final() {
+        bot.instance
+            .database
+            .remove_user_events(&decision.username)
+            .ok();
+    }
+}
+
+impl Notifier for MatrixBot {
+    const NAME: &'static str = "matrix";
+
+    async fn send_sus_alert(&self, alert: UserAlert) {
+        let action = alert.action(&self.config());
+        send_sus_alert(self, alert, &action).await
+    }
+
+    async fn send_ban_notify(&self, alert: UserAlert) {
+        let action = alert.action(&self.config());
+        send_ban_notify(self, alert, &action).await
+    }
+
+    async fn send_ban_request(&self, alert: UserAlert) {
+        let action = alert.action(&self.config());
+        send_ban_request(self, alert, &action).await
+    }
+
+    async fn on_decision(&self, decision: Decision) {
+        reflect_decision(self, decision).await
+    }
+
+    async fn send_report(&self, report: Report) {
+        let name = self.config().forgejo.name.clone();
+        self.send_message(report.message(self.lang(), name.as_deref()))
+            .await
+    }
 }
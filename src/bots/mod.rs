@@ -1,25 +1,34 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
 
-use std::{borrow::Cow, sync::Arc};
+use std::borrow::Cow;
 
-use redb::Database;
-use serde::Deserialize;
-use tokio::sync::mpsc::Receiver;
+use serde::{Deserialize, Serialize};
 use tokio_util::sync::CancellationToken;
 
 use crate::{
     config::{BanAction, Config, MatrixData, RegexReason, TelegramData},
     forgejo_api::ForgejoUser,
+    health,
+    instance::Instance,
+    users_fetcher,
 };
 
+pub mod actions;
+pub mod email;
 pub mod matrix_bot;
+pub mod notifier;
 pub mod telegram_bot;
+pub mod webhook;
 
-/// Language of the bots
-#[derive(Clone, Deserialize)]
+use notifier::NotifierReceivers;
+
+/// Language of the bots. The messages are translated per call, with the
+/// language of their notifier, so the notifiers can have different languages
+#[derive(Clone, Copy, Default, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub enum Lang {
+    #[default]
     EnUs,
     ArSa,
     RuRu,
@@ -28,7 +37,7 @@ pub enum Lang {
 
 impl Lang {
     /// Get the language as a string
-    pub fn as_str(&self) -> &str {
+    pub fn as_str(&self) -> &'static str {
         match self {
             Lang::EnUs => "en-us",
             Lang::ArSa => "ar-sa",
@@ -38,159 +47,545 @@ impl Lang {
     }
 }
 
+/// The moderator decision on a user alert
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DecisionKind {
+    /// The user has been banned
+    Banned,
+    /// The user has been added to the purge queue
+    AddedToPurgeQueue,
+    /// The user has been ignored
+    Ignored,
+    /// The user purge has been undone
+    Undone,
+    /// The user has been removed from the ignored users
+    Unignored,
+    /// The suspended user has been unsuspended
+    Unsuspended,
+}
+
+impl DecisionKind {
+    /// Returns the translated decision message
+    pub fn message(&self, lang: &str) -> Cow<'static, str> {
+        match self {
+            DecisionKind::Banned => t!("messages.ban_success", locale = lang),
+            DecisionKind::AddedToPurgeQueue => t!("messages.added_to_purge_queue", locale = lang),
+            DecisionKind::Ignored => t!("messages.ban_denied", locale = lang),
+            DecisionKind::Undone => t!("messages.undo_success", locale = lang),
+            DecisionKind::Unignored => t!("messages.unignore_success", locale = lang),
+            DecisionKind::Unsuspended => t!("messages.unsuspend_success", locale = lang),
+        }
+    }
+
+    /// Returns the decision name, as it serialized
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DecisionKind::Banned => "banned",
+            DecisionKind::AddedToPurgeQueue => "added_to_purge_queue",
+            DecisionKind::Ignored => "ignored",
+            DecisionKind::Undone => "undone",
+            DecisionKind::Unignored => "unignored",
+            DecisionKind::Unsuspended => "unsuspended",
+        }
+    }
+
+    /// Returns `true` if there is no further decision on the user, the user
+    /// in the purge queue can be undone
+    pub fn is_final(&self) -> bool {
+        !matches!(self, DecisionKind::AddedToPurgeQueue)
+    }
+}
+
+/// A moderator decision taken on a notifier, to be reflected on the other
+/// notifiers alerts
+#[derive(Debug, Clone)]
+pub struct Decision {
+    /// The decided user
+    pub username:   String,
+    /// The decision
+    pub kind:       DecisionKind,
+    /// The moderator who took the decision
+    pub moderator:  String,
+    /// The notifier where the decision has been taken, see
+    /// [`notifier::Notifier::NAME`]
+    pub notifier:   &'static str,
+    /// The ban action of the ban decisions
+    pub ban_action: Option<BanAction>,
+    /// The matched rule of the alert, for the ban decisions of the alerted
+    /// users
+    pub rule:       Option<String>,
+}
+
+impl Decision {
+    /// Create a new decision
+    pub fn new(
+        username: impl Into<String>,
+        kind: DecisionKind,
+        moderator: impl Into<String>,
+        notifier: &'static str,
+    ) -> Self {
+        Self {
+            username: username.into(),
+            kind,
+            moderator: moderator.into(),
+            notifier,
+            ban_action: None,
+            rule: None,
+        }
+    }
+
+    /// Set the ban action of the ban decision
+    pub fn ban_action(mut self, ban_action: BanAction) -> Self {
+        self.ban_action = Some(ban_action);
+        self
+    }
+
+    /// Set the matched rule of the alerted user
+    pub fn rule(mut self, rule: Option<String>) -> Self {
+        self.rule = rule;
+        self
+    }
+}
+
+/// A report to the moderation team, not related to a user
+#[derive(Debug, Clone)]
+pub enum Report {
+    /// The reloaded config is invalid and has been rejected, with the error
+    ConfigRejected(String),
+}
+
+impl Report {
+    /// Returns the translated report message, prefixed with the instance name
+    /// if any
+    pub fn message(&self, lang: &str, instance: Option<&str>) -> String {
+        let message = match self {
+            Report::ConfigRejected(err) => {
+                t!("messages.config_rejected", locale = lang, error = err)
+            }
+        };
+        match instance {
+            Some(name) => {
+                format!(
+                    "{}\n{message}",
+                    t!("messages.instance", locale = lang, instance = name)
+                )
+            }
+            None => message.into_owned(),
+        }
+    }
+}
+
+/// An instance with its notifier receivers
+pub type InstanceReceivers = (Instance, NotifierReceivers);
+
+/// Maximum characters of the matched substring in the alerts
+const MAX_MATCHED_LEN: usize = 50;
+
 /// Type to represent a user alert
+#[derive(Clone)]
 pub struct UserAlert {
     /// The user that has been alerted, suspect or banned
-    user:      ForgejoUser,
-    /// The reason why the user has been alerted
-    reason:    RegexReason,
+    user:       ForgejoUser,
+    /// The reasons why the user has been alerted, all the matched expressions
+    reasons:    Vec<RegexReason>,
+    /// The user score, if the user has been alerted because of the score
+    /// expressions
+    score:      Option<f64>,
+    /// The allow expressions that saved the user from being banned
+    allowed_by: Vec<RegexReason>,
     /// Is the user active, for ban this will send a ban request. For sus user
     /// this will add an active notice
-    is_active: bool,
+    is_active:  bool,
+    /// The ban action taken on the banned user, if it's not the global ban
+    /// action
+    ban_action: Option<BanAction>,
 }
 
 impl UserAlert {
     /// Create a new user alert
-    pub fn new(user: ForgejoUser, reason: RegexReason) -> Self {
+    pub fn new(user: ForgejoUser, reasons: Vec<RegexReason>) -> Self {
         Self {
             user,
-            reason,
+            reasons,
+            score: None,
+            allowed_by: Vec::new(),
             is_active: false,
+            ban_action: None,
         }
     }
 
+    /// Set the user score
+    pub fn score(mut self, score: Option<f64>) -> Self {
+        self.score = score;
+        self
+    }
+
+    /// Set the allow expressions that saved the user from being banned
+    pub fn allowed_by(mut self, allowed_by: Vec<RegexReason>) -> Self {
+        self.allowed_by = allowed_by;
+        self
+    }
+
     /// Mark the user as active
     pub fn is_active(mut self, yes: bool) -> Self {
         self.is_active = yes;
         self
     }
+
+    /// Set the ban action taken on the banned user
+    pub fn ban_action(mut self, ban_action: BanAction) -> Self {
+        self.ban_action = Some(ban_action);
+        self
+    }
+
+    /// Returns the ban action of the alert, the global ban action if it's not
+    /// set
+    pub fn action(&self, config: &Config) -> BanAction {
+        self.ban_action.unwrap_or(config.expressions.ban_action)
+    }
+
+    /// Returns the matched rule of the alert, its joined reasons
+    pub fn rule(&self) -> String {
+        users_fetcher::join_reasons(&self.reasons, self.score)
+    }
+
+    /// Returns `true` if the alert reasons contain matched expressions, the
+    /// ban command alerts has no expressions
+    pub fn has_expressions(&self) -> bool {
+        self.reasons.iter().any(|re| !re.re_vec.is_empty())
+    }
 }
 
 /// If the text is empty, return a not found message
-pub fn not_found_if_empty(text: &str) -> Cow<'_, str> {
+pub fn not_found_if_empty<'a>(lang: &str, text: &'a str) -> Cow<'a, str> {
     if text.is_empty() {
-        t!("words.not_found")
+        t!("words.not_found", locale = lang)
     } else {
         Cow::Borrowed(text)
     }
 }
 
+/// Returns the reason of the matched expression, with its location and the
+/// matched substrings
+fn reason_details(lang: &str, re: &RegexReason) -> String {
+    let reason = re
+        .reason
+        .clone()
+        .unwrap_or_else(|| t!("words.not_specified", locale = lang).into_owned());
+    if re.re_vec.is_empty() {
+        return reason;
+    }
+
+    let matched = re
+        .matched
+        .iter()
+        .map(|matched| {
+            if matched.chars().count() > MAX_MATCHED_LEN {
+                format!(
+                    "\"{}…\"",
+                    matched.chars().take(MAX_MATCHED_LEN).collect::<String>()
+                )
+            } else {
+                format!("\"{matched}\"")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("{reason} ({}: {matched})", re.location.translated(lang))
+}
+
+/// Returns the reasons of the alert, if there is more than one reason or the
+/// alert has a score, each reason will be in a separate line
+fn reasons_details(lang: &str, alert: &UserAlert) -> String {
+    let mut reasons: Vec<_> = alert
+        .reasons
+        .iter()
+        .map(|re| reason_details(lang, re))
+        .collect();
+    let details = if reasons.len() == 1 && alert.score.is_none() {
+        reasons.remove(0)
+    } else {
+        reasons
+            .into_iter()
+            .map(|reason| format!("\n  - {reason}"))
+            .collect::<Vec<_>>()
+            .concat()
+    };
+
+    let details = if let Some(score) = alert.score {
+        format!("({}: {score}){details}", t!("words.score", locale = lang))
+    } else {
+        details
+    };
+
+    if alert.allowed_by.is_empty() {
+        return details;
+    }
+    let allowed_by = alert
+        .allowed_by
+        .iter()
+        .map(|re| reason_details(lang, re))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "{details}\n{}",
+        t!("messages.allowed_by", locale = lang, reasons = allowed_by)
+    )
+}
+
 /// Generate a user details message
 pub fn user_details(
+    lang: &str,
     msg: &str,
-    user: &ForgejoUser,
-    re: &RegexReason,
+    alert: &UserAlert,
     action: &str,
     config: &Config,
 ) -> String {
+    let user = &alert.user;
     let user_email = if config.hide_user_email {
-        t!("messages.hidden")
+        t!("messages.hidden", locale = lang)
     } else {
         Cow::Borrowed(user.email.as_str())
     };
 
-    t!(
+    let details = t!(
         msg,
+        locale = lang,
         action = action,
         user_id = user.id,
         username = user.username,
         email = user_email,
-        full_name = not_found_if_empty(&user.full_name),
-        bio = not_found_if_empty(&user.biography),
-        website = not_found_if_empty(&user.website),
+        full_name = not_found_if_empty(lang, &user.full_name),
+        bio = not_found_if_empty(lang, &user.biography),
+        website = not_found_if_empty(lang, &user.website),
         profile = user.html_url,
-        reason = re
-            .reason
-            .clone()
-            .unwrap_or_else(|| t!("words.not_specified").into_owned()),
-    )
-    .into_owned()
+        reason = reasons_details(lang, alert),
+    );
+
+    match &config.forgejo.name {
+        Some(name) => {
+            format!(
+                "{}\n{details}",
+                t!("messages.instance", locale = lang, instance = name)
+            )
+        }
+        None => details.into_owned(),
+    }
 }
 
 /// Get the action word from the ban action
-pub fn action_word(ban_action: &BanAction) -> String {
-    if ban_action.is_purge() {
-        t!("words.purge").into_owned()
-    } else {
-        t!("words.suspend").into_owned()
+pub fn action_word(lang: &str, ban_action: &BanAction) -> String {
+    match ban_action {
+        BanAction::Purge => t!("words.purge", locale = lang),
+        BanAction::Suspend => t!("words.suspend", locale = lang),
+        BanAction::Restrict => t!("words.restrict", locale = lang),
+        BanAction::DisableRepoCreation => t!("words.disable_repo_creation", locale = lang),
+        BanAction::PrivateRepos => t!("words.private_repos", locale = lang),
+        BanAction::DeleteRepos => t!("words.delete_repos", locale = lang),
+        BanAction::DeleteContent => t!("words.delete_content", locale = lang),
     }
+    .into_owned()
+}
+
+/// Returns the names of the instances, to be logged
+fn instances_names(instances: &[InstanceReceivers]) -> String {
+    instances
+        .iter()
+        .filter_map(|(instance, _)| instance.name())
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
 /// Run the telegram bot in a separate task
 pub fn run_telegram_bot(
-    database: Arc<Database>,
-    config: Arc<Config>,
     telegram: TelegramData,
+    instances: Vec<InstanceReceivers>,
     cancellation_token: CancellationToken,
-    sus_receiver: Receiver<UserAlert>,
-    ban_receiver: Receiver<UserAlert>,
 ) {
     tracing::info!(config = "telegram", "Bot lang: {}", telegram.lang.as_str());
-    tracing::info!(config = "telegram", "Receiver chat ID: {}", telegram.chat);
-
-    rust_i18n::set_locale(telegram.lang.as_str());
+    for (instance, _) in &instances {
+        if let Some(data) = instance.config().telegram.data() {
+            tracing::info!(
+                config = "telegram",
+                instance = instance.name(),
+                "Receiver chat ID: {}",
+                data.chat
+            );
+        }
+    }
+    if instances.len() > 1 {
+        tracing::info!(
+            config = "telegram",
+            "Instances: {}",
+            instances_names(&instances)
+        );
+    }
 
-    tokio::spawn(telegram_bot::start_bot(
-        database,
-        config,
-        telegram,
-        cancellation_token.clone(),
-        sus_receiver,
-        ban_receiver,
-    ));
+    health::spawn(
+        "telegram_bot",
+        Some(instances_names(&instances)).filter(|names| !names.is_empty()),
+        true,
+        &cancellation_token.clone(),
+        telegram_bot::start_bot(telegram, instances, cancellation_token),
+    );
 }
 
 /// Run the matrix bot in a separate task
 pub fn run_matrix_bot(
-    database: Arc<Database>,
-    config: Arc<Config>,
     matrix: MatrixData,
+    instances: Vec<InstanceReceivers>,
     cancellation_token: CancellationToken,
-    sus_receiver: Receiver<UserAlert>,
-    ban_receiver: Receiver<UserAlert>,
 ) {
     tracing::info!(config = "matrix", "Bot lang: {}", matrix.lang.as_str());
     tracing::info!(config = "matrix", "Bot username: {}", matrix.username);
     tracing::info!(config = "matrix", "Homeserver: {}", matrix.homeserver);
-    tracing::info!(config = "matrix", "Room: {}", matrix.room);
-
-    rust_i18n::set_locale(matrix.lang.as_str());
+    for (instance, _) in &instances {
+        if let Some(data) = instance.config().matrix.data() {
+            tracing::info!(
+                config = "matrix",
+                instance = instance.name(),
+                "Room: {}",
+                data.room
+            );
+        }
+    }
+    if instances.len() > 1 {
+        tracing::info!(
+            config = "matrix",
+            "Instances: {}",
+            instances_names(&instances)
+        );
+    }
 
-    tokio::spawn(matrix_bot::start_bot(
-        database,
-        config,
-        matrix,
-        cancellation_token.clone(),
-        sus_receiver,
-        ban_receiver,
-    ));
+    health::spawn(
+        "matrix_bot",
+        Some(instances_names(&instances)).filter(|names| !names.is_empty()),
+        true,
+        &cancellation_token.clone(),
+        matrix_bot::start_bot(matrix, instances, cancellation_token),
+    );
 }
 
-/// Run the enabled bot, if any
-pub fn run_bots(
-    database: Arc<Database>,
-    config: Arc<Config>,
-    cancellation_token: CancellationToken,
-    sus_receiver: Receiver<UserAlert>,
-    ban_receiver: Receiver<UserAlert>,
-) {
-    if let Some(telegram) = config.telegram.data().cloned() {
-        run_telegram_bot(
-            database,
-            config,
-            telegram,
+/// Run the outgoing webhook notifier of the instance in a separate task
+pub fn run_webhook_notifier(instance: &Instance, cancellation_token: CancellationToken) {
+    let config = instance.config();
+    let Some(webhook) = config.webhook() else {
+        return;
+    };
+    tracing::info!(
+        config = "webhook",
+        instance = instance.name(),
+        "Webhook URL: {}",
+        webhook.url
+    );
+    tracing::info!(
+        config = "webhook",
+        instance = instance.name(),
+        "Max retries: {}",
+        webhook.max_retries
+    );
+
+    health::spawn(
+        "webhook_notifier",
+        instance.name().map(String::from),
+        false,
+        &cancellation_token.clone(),
+        notifier::run_notifier(
+            webhook::WebhookNotifier::new(instance.clone()),
+            NotifierReceivers::subscribe(instance),
             cancellation_token,
-            sus_receiver,
-            ban_receiver,
-        )
-    } else if let Some(matrix) = config.matrix.data().cloned() {
-        run_matrix_bot(
-            database,
-            config,
-            matrix,
+        ),
+    );
+}
+
+/// Run the email notifier of the instance in a separate task
+pub fn run_email_notifier(instance: &Instance, cancellation_token: CancellationToken) {
+    let config = instance.config();
+    let Some(email) = config.email() else {
+        return;
+    };
+    tracing::info!(
+        config = "email",
+        instance = instance.name(),
+        "Lang: {}",
+        email.lang.as_str()
+    );
+    tracing::info!(
+        config = "email",
+        instance = instance.name(),
+        "SMTP host: {}",
+        email.host
+    );
+    tracing::info!(
+        config = "email",
+        instance = instance.name(),
+        "Recipients: {}",
+        email
+            .to
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    let notifier = match email::EmailNotifier::new(instance.clone()) {
+        Ok(notifier) => notifier,
+        Err(err) => {
+            tracing::error!("Falied to run the email notifier: {err}");
+            return;
+        }
+    };
+
+    health::spawn(
+        "email_notifier",
+        instance.name().map(String::from),
+        false,
+        &cancellation_token.clone(),
+        notifier::run_notifier(
+            notifier,
+            NotifierReceivers::subscribe(instance),
             cancellation_token,
-            sus_receiver,
-            ban_receiver,
-        )
+        ),
+    );
+}
+
+/// Run the enabled bots, the instances that use the same bot (the same
+/// Telegram token or the same Matrix account) share it. Each bot subscribes to
+/// the instances alerts, so they are sent to all of the enabled bots
+pub fn run_bots(instances: &[Instance], cancellation_token: CancellationToken) {
+    let mut telegram_bots: Vec<(TelegramData, Vec<InstanceReceivers>)> = Vec::new();
+    let mut matrix_bots: Vec<(MatrixData, Vec<InstanceReceivers>)> = Vec::new();
+
+    for instance in instances {
+        if let Some(telegram) = instance.config().telegram.data().cloned() {
+            let instance = (instance.clone(), NotifierReceivers::subscribe(instance));
+            match telegram_bots
+                .iter_mut()
+                .find(|(t, _)| t.token == telegram.token)
+            {
+                Some((_, group)) => group.push(instance),
+                None => telegram_bots.push((telegram, vec![instance])),
+            }
+        }
+        if let Some(matrix) = instance.config().matrix.data().cloned() {
+            let instance = (instance.clone(), NotifierReceivers::subscribe(instance));
+            match matrix_bots
+                .iter_mut()
+                .find(|(m, _)| m.homeserver == matrix.homeserver && m.username == matrix.username)
+            {
+                Some((_, group)) => group.push(instance),
+                None => matrix_bots.push((matrix, vec![instance])),
+            }
+        }
+        run_webhook_notifier(instance, cancellation_token.clone());
+        run_email_notifier(instance, cancellation_token.clone());
+    }
+
+    for (telegram, group) in telegram_bots {
+        run_telegram_bot(telegram, group, cancellation_token.clone());
+    }
+    for (matrix, group) in matrix_bots {
+        run_matrix_bot(matrix, group, cancellation_token.clone());
     }
 }
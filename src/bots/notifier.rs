@@ -0,0 +1,167 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
+
+//! The notifiers, the backends that alert the moderation team and receive
+//! their decisions.
+
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::{
+    broadcast::{error::RecvError, Receiver},
+    mpsc,
+};
+use tokio_util::sync::CancellationToken;
+
+use super::{Decision, Report, UserAlert};
+use crate::instance::Instance;
+
+/// A notifier backend, receives the instance alerts and the decisions taken on
+/// the other notifiers
+pub trait Notifier: Send + Sync + 'static {
+    /// The notifier name, to identify its decisions
+    const NAME: &'static str;
+
+    /// Send a suspicious user alert
+    fn send_sus_alert(&self, alert: UserAlert) -> impl Future<Output = ()> + Send;
+
+    /// Send a banned user notification
+    fn send_ban_notify(&self, alert: UserAlert) -> impl Future<Output = ()> + Send;
+
+    /// Send a ban request of an active user
+    fn send_ban_request(&self, alert: UserAlert) -> impl Future<Output = ()> + Send;
+
+    /// Handle a decision taken on another notifier, to reflect it on the user
+    /// alerts
+    fn on_decision(&self, decision: Decision) -> impl Future<Output = ()> + Send;
+
+    /// Send a report to the moderation team. The notifiers without a
+    /// moderation chat ignore the reports
+    fn send_report(&self, _report: Report) -> impl Future<Output = ()> + Send {
+        async {}
+    }
+}
+
+/// The capacity of each notifier alerts queue
+const ALERTS_QUEUE_CAPACITY: usize = 100;
+
+/// The sender of the instance alerts, it fans out each alert to the notifiers.
+/// Each notifier has its own bounded queue, and the sender waits for a full
+/// queue instead of dropping the alert
+#[derive(Clone, Default)]
+pub struct AlertSender {
+    /// The notifiers queues senders
+    senders: Arc<Mutex<Vec<mpsc::Sender<UserAlert>>>>,
+}
+
+impl AlertSender {
+    /// Subscribe a notifier to the alerts, returns its queue receiver
+    pub fn subscribe(&self) -> mpsc::Receiver<UserAlert> {
+        let (sender, receiver) = mpsc::channel(ALERTS_QUEUE_CAPACITY);
+        self.senders.lock().expect("Not poisoned").push(sender);
+        receiver
+    }
+
+    /// Send the alert to all the notifiers, waits if a notifier queue is full.
+    /// Returns the alert back if there is no running notifier
+    pub async fn send(&self, alert: UserAlert) -> Result<(), UserAlert> {
+        let senders = {
+            let mut senders = self.senders.lock().expect("Not poisoned");
+            senders.retain(|sender| !sender.is_closed());
+            senders.clone()
+        };
+
+        let mut delivered = false;
+        for sender in senders {
+            // An error means that the notifier has been stopped
+            delivered |= sender.send(alert.clone()).await.is_ok();
+        }
+        if delivered {
+            Ok(())
+        } else {
+            Err(alert)
+        }
+    }
+}
+
+/// The receivers of the instance alerts and decisions, they must be subscribed
+/// before running the instance to not miss any alert
+pub struct NotifierReceivers {
+    /// Suspicious users alerts receiver
+    sus:       mpsc::Receiver<UserAlert>,
+    /// Banned users alerts and ban requests receiver
+    ban:       mpsc::Receiver<UserAlert>,
+    /// Moderators decisions receiver
+    decisions: Receiver<Decision>,
+    /// Moderation team reports receiver
+    reports:   Receiver<Report>,
+}
+
+impl NotifierReceivers {
+    /// Subscribe to the instance alerts and decisions
+    pub fn subscribe(instance: &Instance) -> Self {
+        Self {
+            sus:       instance.sus_sender.subscribe(),
+            ban:       instance.ban_sender.subscribe(),
+            decisions: instance.decisions.subscribe(),
+            reports:   instance.reports.subscribe(),
+        }
+    }
+}
+
+/// Returns the received value, `None` if the channel is lagged. Returns
+/// `Err(())` if the channel is closed
+fn received<T>(result: Result<T, RecvError>) -> Result<Option<T>, ()> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(RecvError::Lagged(skipped)) => {
+            tracing::warn!("The notifier is lagging behind, {skipped} messages are skipped");
+            Ok(None)
+        }
+        Err(RecvError::Closed) => Err(()),
+    }
+}
+
+/// Run the notifier, until the cancellation token is cancelled or the instance
+/// channels are closed
+pub async fn run_notifier<N: Notifier>(
+    notifier: N,
+    mut receivers: NotifierReceivers,
+    cancellation_token: CancellationToken,
+) {
+    loop {
+        tokio::select! {
+            alert = receivers.sus.recv() => {
+                let Some(alert) = alert else { break };
+                notifier.send_sus_alert(alert).await;
+            }
+            alert = receivers.ban.recv() => {
+                let Some(alert) = alert else { break };
+                if alert.is_active {
+                    notifier.send_ban_request(alert).await;
+                } else {
+                    notifier.send_ban_notify(alert).await;
+                }
+            }
+            decision = receivers.decisions.recv() => {
+                let Ok(decision) = received(decision) else { break };
+                if let Some(decision) = decision.filter(|d| d.notifier != N::NAME) {
+                    notifier.on_decision(decision).await;
+                }
+            }
+            report = receivers.reports.recv() => {
+                let Ok(report) = received(report) else { break };
+                if let Some(report) = report {
+                    notifier.send_report(report).await;
+                }
+            }
+            _ = cancellation_token.cancelled() => {
+                break;
+            }
+        }
+    }
+
+    tracing::info!("The {} notifier has been stopped successfully.", N::NAME);
+}
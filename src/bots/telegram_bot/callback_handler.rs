@@ -3,8 +3,6 @@
 
 use std::sync::Arc;
 
-use redb::Database;
-use reqwest::Client;
 use teloxide::{
     prelude::*,
     types::{
@@ -16,15 +14,21 @@ use teloxide::{
     },
 };
 
+use super::users_handler::TelegramNotifier;
 use crate::{
-    config::Config,
-    db::{AlertedUsersTableTrait, IgnoredUsersTableTrait, PurgedUsersTableTrait},
+    bots::{actions, notifier::Notifier, DecisionKind, Lang},
+    db::TelegramMessagesTableTrait,
     forgejo_api,
+    instance::{self, Instance},
 };
 
 /// Inline keyboard with a single button that links to the Forgejo Guardian
-/// repository. The undo button will be added if the username is `Some`
-fn source_inline_keyboard(text: &str, username: Option<&str>) -> InlineKeyboardMarkup {
+/// repository. The undo button will be added if the user id is `Some`
+pub fn source_inline_keyboard(
+    lang: &str,
+    text: &str,
+    user_id: Option<&str>,
+) -> InlineKeyboardMarkup {
     let mut keyboard = vec![[InlineKeyboardButton::new(
         text,
         InlineKeyboardButtonKind::Url(
@@ -32,10 +36,10 @@ fn source_inline_keyboard(text: &str, username: Option<&str>) -> InlineKeyboardM
         ),
     )]];
 
-    if let Some(username) = username {
+    if let Some(user_id) = user_id {
         keyboard.push([InlineKeyboardButton::new(
-            t!("buttons.undo"),
-            InlineKeyboardButtonKind::CallbackData(format!("u {username}")),
+            t!("buttons.undo", locale = lang),
+            InlineKeyboardButtonKind::CallbackData(format!("u {user_id}")),
         )]);
     }
 
@@ -46,58 +50,52 @@ fn source_inline_keyboard(text: &str, username: Option<&str>) -> InlineKeyboardM
 pub async fn callback_handler(
     bot: Bot,
     callback_query: CallbackQuery,
-    config: Arc<Config>,
-    database: Arc<Database>,
+    instances: Arc<Vec<Instance>>,
+    lang: Lang,
 ) -> ResponseResult<()> {
+    let lang = lang.as_str();
     let Some(callback_data) = callback_query.data else {
         return Ok(());
     };
 
-    let Some((command, data)) = callback_data.split_once(' ') else {
+    let Some((command, user_id)) = callback_data.split_once(' ') else {
         // Invalid callback data
         return Ok(());
     };
+    let (instance_name, data) = instance::split_user_id(user_id);
+    let Some(instance) = instance::find(&instances, instance_name) else {
+        tracing::warn!("Received a callback query of unknown instance: {user_id}");
+        return Ok(());
+    };
+    let moderator = callback_query
+        .from
+        .username
+        .map(|u| format!("@{u}"))
+        .unwrap_or_else(|| format!("id={}", callback_query.from.id));
+    let remove_messages = |kind: DecisionKind| {
+        if kind.is_final() {
+            instance.database.remove_telegram_messages(data).ok();
+        }
+    };
 
     match command {
         // Ban
-        "b" if !database.is_lazy_purged(data).is_ok_and(|y| y) => {
-            // ban the user
-            let button_text = if config.dry_run
-                || config.lazy_purge.enabled
-                || forgejo_api::ban_user(
-                    &Client::new(),
-                    &config.forgejo.instance,
-                    &config.forgejo.token,
-                    data,
-                    &config.expressions.ban_action,
-                )
-                .await
-                .is_ok()
-            {
-                let moderator = callback_query
-                    .from
-                    .username
-                    .map(|u| format!("@{u}"))
-                    .unwrap_or_else(|| format!("id={}", callback_query.from.id));
-
-                if config.lazy_purge.enabled {
-                    tracing::info!("The moderator {moderator} has added @{data} to purge queue",);
-                    database.add_purged_user(data).ok();
-                    t!("messages.added_to_purge_queue")
-                } else {
-                    tracing::info!("The moderator {moderator} has banned @{data}",);
-                    database.remove_alerted_user(data).ok();
-                    t!("messages.ban_success")
+        "b" if actions::can_ban(instance, data) => {
+            let kind = actions::ban(instance, data, &moderator, TelegramNotifier::NAME).await;
+            let button_text = match kind {
+                Some(kind) => {
+                    remove_messages(kind);
+                    kind.message(lang)
                 }
-            } else {
-                t!("messages.ban_failed")
+                None => t!("messages.ban_failed", locale = lang),
             };
 
             if let Some(MaybeInaccessibleMessage::Regular(msg)) = callback_query.message {
                 bot.edit_message_reply_markup(msg.chat.id, msg.id)
                     .reply_markup(source_inline_keyboard(
+                        lang,
                         &button_text,
-                        config.lazy_purge.enabled.then_some(data),
+                        instance.config().lazy_purge.enabled.then_some(user_id),
                     ))
                     .await?;
             }
@@ -106,20 +104,51 @@ pub async fn callback_handler(
         "i" => {
             if let Some(MaybeInaccessibleMessage::Regular(msg)) = callback_query.message {
                 bot.edit_message_reply_markup(msg.chat.id, msg.id)
-                    .reply_markup(source_inline_keyboard(&t!("messages.ban_denied"), None))
+                    .reply_markup(source_inline_keyboard(
+                        lang,
+                        &t!("messages.ban_denied", locale = lang),
+                        None,
+                    ))
                     .await?;
             }
-            database.add_ignored_user(data).ok();
-            database.remove_alerted_user(data).ok();
+            actions::ignore(instance, data, &moderator, TelegramNotifier::NAME);
+            remove_messages(DecisionKind::Ignored);
         }
         // Undo a purge
-        "u" if config.lazy_purge.enabled && database.is_lazy_purged(data).is_ok_and(|y| y) => {
+        "u" if actions::can_undo(instance, data) => {
+            if let Some(MaybeInaccessibleMessage::Regular(msg)) = callback_query.message {
+                bot.edit_message_reply_markup(msg.chat.id, msg.id)
+                    .reply_markup(source_inline_keyboard(
+                        lang,
+                        &t!("messages.undo_success", locale = lang),
+                        None,
+                    ))
+                    .await?;
+            }
+            actions::undo(instance, data, &moderator, TelegramNotifier::NAME);
+            remove_messages(DecisionKind::Undone);
+        }
+        // Unsuspend a suspended user
+        "s" => {
+            let button_text = match forgejo_api::get_user(data, &instance.config().forgejo).await {
+                Err(_) => t!("messages.user_not_found", locale = lang, username = data),
+                Ok(user) if !user.prohibit_login => {
+                    t!("messages.not_suspended", locale = lang, username = data)
+                }
+                Ok(_)
+                    if actions::unsuspend(instance, data, &moderator, TelegramNotifier::NAME)
+                        .await =>
+                {
+                    remove_messages(DecisionKind::Unsuspended);
+                    t!("messages.unsuspend_success", locale = lang)
+                }
+                Ok(_) => t!("messages.unsuspend_failed", locale = lang),
+            };
             if let Some(MaybeInaccessibleMessage::Regular(msg)) = callback_query.message {
                 bot.edit_message_reply_markup(msg.chat.id, msg.id)
-                    .reply_markup(source_inline_keyboard(&t!("messages.undo_success"), None))
+                    .reply_markup(source_inline_keyboard(lang, &button_text, None))
                     .await?;
             }
-            database.remove_purged_user(data).ok();
         }
         _ => {}
     };
@@ -3,18 +3,20 @@
 
 use std::sync::Arc;
 
-use redb::Database;
 use teloxide::{
     prelude::*,
-    types::{Me, ReplyParameters},
+    types::{InputFile, Me, ReplyParameters},
     utils::command::BotCommands,
 };
 
+use super::{telegram_data, users_handler::TelegramNotifier};
 use crate::{
-    bots::telegram_bot::users_handler,
-    config::{Config, RegexReason},
+    bots::{actions, notifier::Notifier, telegram_bot::users_handler, Lang, UserAlert},
+    config::RegexReason,
     db::PurgedUsersTableTrait,
     forgejo_api,
+    instance::{self, Instance},
+    snapshots,
 };
 
 #[derive(BotCommands, Clone, Debug, PartialEq)]
@@ -25,85 +27,236 @@ enum Command {
     Help,
     Ping,
     Ban(String),
+    Snapshot(String),
+    Unban(String),
 }
 
 /// Help and start commands handler
-pub async fn help_start_handler(bot: &Bot, msg: &Message) -> ResponseResult<()> {
-    bot.send_message(msg.chat.id, t!("messages.help_start"))
+pub async fn help_start_handler(bot: &Bot, msg: &Message, lang: &str) -> ResponseResult<()> {
+    bot.send_message(msg.chat.id, t!("messages.help_start", locale = lang))
         .reply_parameters(ReplyParameters::new(msg.id))
         .await?;
 
     Ok(())
 }
 
+/// Returns the name of the moderator who sent the message
+fn moderator_name(msg: &Message) -> String {
+    msg.from
+        .as_ref()
+        .map(|u| {
+            u.username
+                .as_ref()
+                .map(|n| format!("@{n}"))
+                .unwrap_or_else(|| u.full_name())
+        })
+        .unwrap_or_else(|| "N/A".to_owned())
+}
+
+/// Returns the chat instance of the user ID. If the chat has multiple
+/// instances and the user ID has no instance name, the moderator will be asked
+/// to specify it
+async fn chat_instance(
+    instances: &[Instance],
+    bot: &Bot,
+    msg: &Message,
+    lang: &str,
+    command: &str,
+    user_id: &str,
+) -> ResponseResult<Option<Instance>> {
+    let chat_instances: Vec<_> = instances
+        .iter()
+        .filter(|i| telegram_data(&i.config()).chat == msg.chat.id)
+        .cloned()
+        .collect();
+    let (instance_name, _) = instance::split_user_id(user_id);
+    let instance = instance::find(&chat_instances, instance_name).cloned();
+    if instance.is_none() && instance_name.is_none() && !chat_instances.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            t!(
+                "messages.instance_required",
+                locale = lang,
+                prefix = "/",
+                command = command,
+                instances = chat_instances
+                    .iter()
+                    .filter_map(Instance::name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        )
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+    }
+    Ok(instance)
+}
+
 /// Ban command handler
 pub async fn ban_handler(
-    database: &Database,
-    config: &Config,
+    instances: &[Instance],
     bot: &Bot,
     msg: &Message,
-    username: String,
+    lang: &str,
+    user_id: String,
 ) -> ResponseResult<()> {
-    if username.is_empty() {
+    if user_id.is_empty() {
         return Ok(());
     }
 
-    let moderator = msg
-        .from
-        .as_ref()
-        .map(|u| {
-            u.username
-                .as_ref()
-                .map(|n| format!("@{n}"))
-                .unwrap_or_else(|| u.full_name())
-        })
-        .unwrap_or_else(|| "N/A".to_owned());
+    let Some(instance) = chat_instance(instances, bot, msg, lang, "ban", &user_id).await? else {
+        return Ok(());
+    };
+    let (_, username) = instance::split_user_id(&user_id);
+    let (config, database) = (&instance.config(), &instance.database);
+    let moderator = moderator_name(msg);
 
     tracing::info!("{moderator} requesting a ban request for `@{username}`");
-    let Ok(user) = forgejo_api::get_user(&username, &config.forgejo).await else {
+    let Ok(user) = forgejo_api::get_user(username, &config.forgejo).await else {
         bot.send_message(
             msg.chat.id,
-            t!("messages.user_not_found", username = username),
+            t!(
+                "messages.user_not_found",
+                locale = lang,
+                username = username
+            ),
         )
         .reply_parameters(ReplyParameters::new(msg.id))
         .await?;
         return Ok(());
     };
     if user.is_admin {
-        bot.send_message(msg.chat.id, t!("messages.can_not_ban_admin"))
+        bot.send_message(msg.chat.id, t!("messages.can_not_ban_admin", locale = lang))
             .reply_parameters(ReplyParameters::new(msg.id))
             .await?;
         return Ok(());
     }
     users_handler::send_ban_request(
         bot,
-        config.telegram.data().expect("telegram is enabled"),
-        &RegexReason::new(
-            Vec::new(),
-            Some(
-                t!(
-                    "messages.ban_command_reason",
-                    moderator = moderator,
-                    prefix = "/"
-                )
-                .into_owned(),
-            ),
+        &instance,
+        lang,
+        database.is_lazy_purged(username).is_ok_and(|y| y),
+        UserAlert::new(
+            user,
+            vec![RegexReason::new(
+                Vec::new(),
+                Some(
+                    t!(
+                        "messages.ban_command_reason",
+                        locale = lang,
+                        moderator = moderator,
+                        prefix = "/"
+                    )
+                    .into_owned(),
+                ),
+            )],
         ),
-        database.is_lazy_purged(&username).is_ok_and(|y| y),
-        user,
-        config,
     )
     .await
 }
 
+/// Unban command handler, unsuspend a suspended user
+pub async fn unban_handler(
+    instances: &[Instance],
+    bot: &Bot,
+    msg: &Message,
+    lang: &str,
+    user_id: String,
+) -> ResponseResult<()> {
+    if user_id.is_empty() {
+        return Ok(());
+    }
+
+    let Some(instance) = chat_instance(instances, bot, msg, lang, "unban", &user_id).await? else {
+        return Ok(());
+    };
+    let (_, username) = instance::split_user_id(&user_id);
+    let moderator = moderator_name(msg);
+
+    tracing::info!("{moderator} requesting to unsuspend `@{username}`");
+    let reply = match forgejo_api::get_user(username, &instance.config().forgejo).await {
+        Err(_) => {
+            t!(
+                "messages.user_not_found",
+                locale = lang,
+                username = username
+            )
+        }
+        Ok(user) if !user.prohibit_login => {
+            t!("messages.not_suspended", locale = lang, username = username)
+        }
+        Ok(_)
+            if actions::unsuspend(&instance, username, &moderator, TelegramNotifier::NAME)
+                .await =>
+        {
+            t!("messages.unsuspend_success", locale = lang)
+        }
+        Ok(_) => t!("messages.unsuspend_failed", locale = lang),
+    };
+    bot.send_message(msg.chat.id, reply)
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+
+    Ok(())
+}
+
+/// Snapshot command handler
+pub async fn snapshot_handler(
+    instances: &[Instance],
+    bot: &Bot,
+    msg: &Message,
+    lang: &str,
+    user_id: String,
+) -> ResponseResult<()> {
+    if user_id.is_empty() {
+        return Ok(());
+    }
+
+    let Some(instance) = chat_instance(instances, bot, msg, lang, "snapshot", &user_id).await?
+    else {
+        return Ok(());
+    };
+    let (_, username) = instance::split_user_id(&user_id);
+
+    tracing::info!("Requesting the snapshot of `@{username}`");
+    let reply = match snapshots::get(&instance.config(), &instance.database, username) {
+        Ok(Some(snapshot)) => {
+            bot.send_document(
+                msg.chat.id,
+                InputFile::memory(snapshot.into_bytes()).file_name(format!("{username}.json")),
+            )
+            .reply_parameters(ReplyParameters::new(msg.id))
+            .await?;
+            return Ok(());
+        }
+        Ok(None) => {
+            t!(
+                "messages.snapshot_not_found",
+                locale = lang,
+                username = username
+            )
+        }
+        Err(err) => {
+            tracing::error!("Failed to get the snapshot of @{username}: {err}");
+            t!("messages.snapshot_failed", locale = lang)
+        }
+    };
+    bot.send_message(msg.chat.id, reply)
+        .reply_parameters(ReplyParameters::new(msg.id))
+        .await?;
+
+    Ok(())
+}
+
 /// Handle text messages
 pub async fn text_handler(
     bot: Bot,
     me: Me,
     msg: Message,
-    config: Arc<Config>,
-    database: Arc<Database>,
+    instances: Arc<Vec<Instance>>,
+    lang: Lang,
 ) -> ResponseResult<()> {
+    let lang = lang.as_str();
     if msg.forward_origin().is_some() {
         return Ok(());
     }
@@ -114,21 +267,17 @@ pub async fn text_handler(
     };
 
     match command {
-        Command::Help | Command::Start => help_start_handler(&bot, &msg).await?,
+        Command::Help | Command::Start => help_start_handler(&bot, &msg, lang).await?,
         Command::Ping => {
             bot.send_message(msg.chat.id, "Pong!")
                 .reply_parameters(ReplyParameters::new(msg.id))
                 .await?;
         }
-        Command::Ban(username)
-            if config
-                .telegram
-                .data()
-                .is_some_and(|d| d.chat == msg.chat.id) =>
-        {
-            ban_handler(&database, &config, &bot, &msg, username).await?
+        Command::Ban(user_id) => ban_handler(&instances, &bot, &msg, lang, user_id).await?,
+        Command::Snapshot(user_id) => {
+            snapshot_handler(&instances, &bot, &msg, lang, user_id).await?
         }
-        _ => {}
+        Command::Unban(user_id) => unban_handler(&instances, &bot, &msg, lang, user_id).await?,
     };
 
     Ok(())
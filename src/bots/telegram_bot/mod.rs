@@ -10,26 +10,30 @@ mod users_handler;
 use std::sync::Arc;
 
 use callback_handler::callback_handler;
-use redb::Database;
 use teloxide::{dispatching::UpdateFilterExt, prelude::*};
-use tokio::sync::mpsc::Receiver;
 use tokio_util::sync::CancellationToken;
+use users_handler::TelegramNotifier;
 
-use super::UserAlert;
+use super::{notifier::run_notifier, InstanceReceivers};
 use crate::config::{Config, TelegramData};
 
-/// Start the telegram bot
+/// Returns the Telegram data of the instance config, the instance must have an
+/// enabled Telegram bot
+fn telegram_data(config: &Config) -> &TelegramData {
+    config
+        .telegram
+        .data()
+        .expect("The instance has an enabled Telegram bot")
+}
+
+/// Start the telegram bot, for all the instances that use it
 pub async fn start_bot(
-    database: Arc<Database>,
-    config: Arc<Config>,
     telegram: TelegramData,
+    instances: Vec<InstanceReceivers>,
     cancellation_token: CancellationToken,
-    sus_receiver: Receiver<UserAlert>,
-    ban_receiver: Receiver<UserAlert>,
 ) {
     tracing::info!("Starting the telegram bot");
 
-    let telegram = Arc::new(telegram);
     let bot = Bot::new(&telegram.token);
     let handler = dptree::entry()
         .branch(
@@ -38,18 +42,23 @@ pub async fn start_bot(
         )
         .branch(Update::filter_callback_query().endpoint(callback_handler));
 
-    tokio::spawn(users_handler::users_handler(
-        bot.clone(),
-        Arc::clone(&database),
-        Arc::clone(&config),
-        Arc::clone(&telegram),
-        cancellation_token,
-        sus_receiver,
-        ban_receiver,
-    ));
+    let mut bot_instances = Vec::with_capacity(instances.len());
+    for (instance, receivers) in instances {
+        let notifier = TelegramNotifier {
+            bot:      bot.clone(),
+            instance: instance.clone(),
+            lang:     telegram.lang,
+        };
+        tokio::spawn(run_notifier(
+            notifier,
+            receivers,
+            cancellation_token.clone(),
+        ));
+        bot_instances.push(instance);
+    }
 
     Dispatcher::builder(bot, handler)
-        .dependencies(dptree::deps![config, database])
+        .dependencies(dptree::deps![Arc::new(bot_instances), telegram.lang])
         .enable_ctrlc_handler()
         .build()
         .dispatch()
@@ -1,38 +1,48 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
 
-use std::sync::Arc;
-
-use redb::Database;
 use teloxide::{
     prelude::*,
-    types::{InlineKeyboardButton, InlineKeyboardButtonKind, InlineKeyboardMarkup, InputFile},
+    types::{
+        InlineKeyboardButton,
+        InlineKeyboardButtonKind,
+        InlineKeyboardMarkup,
+        InputFile,
+        MessageId,
+    },
 };
-use tokio::sync::mpsc::Receiver;
-use tokio_util::sync::CancellationToken;
 
-use super::UserAlert;
+use super::{callback_handler::source_inline_keyboard, telegram_data};
 use crate::{
-    bots::{action_word, user_details},
-    config::{Config, RegexReason, TelegramData},
-    db::PurgedUsersTableTrait,
-    forgejo_api::ForgejoUser,
+    bots::{
+        action_word,
+        notifier::Notifier,
+        user_details,
+        Decision,
+        DecisionKind,
+        Lang,
+        Report,
+        UserAlert,
+    },
+    db::{PurgedUsersTableTrait, TelegramMessagesTableTrait},
+    instance::Instance,
+    metrics,
 };
 
 /// Create an inline keyboard ask to ban or ignore the user
-fn make_ban_ignore_keyboard(user: &ForgejoUser, action: &str) -> InlineKeyboardMarkup {
+fn make_ban_ignore_keyboard(lang: &str, user_id: &str, action: &str) -> InlineKeyboardMarkup {
     let button = |text: &str, callback: String| {
         InlineKeyboardButton::new(text, InlineKeyboardButtonKind::CallbackData(callback))
     };
 
     InlineKeyboardMarkup::new([[
         button(
-            t!("buttons.ban", action = action).as_ref(),
-            format!("b {}", user.username),
+            t!("buttons.ban", locale = lang, action = action).as_ref(),
+            format!("b {user_id}"),
         ),
         button(
-            t!("buttons.ignore").as_ref(),
-            format!("i {}", user.username),
+            t!("buttons.ignore", locale = lang).as_ref(),
+            format!("i {user_id}"),
         ),
     ]])
 }
@@ -40,21 +50,29 @@ fn make_ban_ignore_keyboard(user: &ForgejoUser, action: &str) -> InlineKeyboardM
 /// Send a suspicious user alert to the admins
 pub async fn send_sus_alert(
     bot: &Bot,
-    telegram: &TelegramData,
-    re: &RegexReason,
-    sus_user: ForgejoUser,
-    config: &Config,
+    instance: &Instance,
+    lang: &str,
+    alert: UserAlert,
 ) -> ResponseResult<()> {
     tracing::info!("Sending suspicious user alert to the admins chat");
 
-    let action = action_word(&config.expressions.ban_action);
-    let keyboard = make_ban_ignore_keyboard(&sus_user, &action);
+    let config = &instance.config();
+    let action = action_word(lang, &alert.action(config));
+    let keyboard = make_ban_ignore_keyboard(lang, &instance.user_id(&alert.user.username), &action);
 
-    let caption = user_details("messages.sus_alert", &sus_user, re, &action, config);
-    bot.send_photo(telegram.chat, InputFile::url(sus_user.avatar_url))
+    let caption = user_details(lang, "messages.sus_alert", &alert, &action, config);
+    let msg = bot
+        .send_photo(
+            telegram_data(&instance.config()).chat,
+            InputFile::url(alert.user.avatar_url),
+        )
         .caption(caption)
         .reply_markup(keyboard)
         .await?;
+    instance
+        .database
+        .add_telegram_message(&alert.user.username, msg.id.0)
+        .ok();
 
     Ok(())
 }
@@ -62,18 +80,40 @@ pub async fn send_sus_alert(
 /// Send a ban notification to the admins chat
 pub async fn send_ban_notify(
     bot: &Bot,
-    telegram: &TelegramData,
-    re: &RegexReason,
-    sus_user: ForgejoUser,
-    config: &Config,
+    instance: &Instance,
+    lang: &str,
+    alert: UserAlert,
 ) -> ResponseResult<()> {
     tracing::info!("Sending ban notification to the admins chat");
 
-    let action = action_word(&config.expressions.ban_action);
-    let caption = user_details("messages.ban_notify", &sus_user, re, &action, config);
-    bot.send_photo(telegram.chat, InputFile::url(sus_user.avatar_url))
-        .caption(caption)
-        .await?;
+    let config = &instance.config();
+    let ban_action = alert.action(config);
+    let action = action_word(lang, &ban_action);
+    let caption = user_details(lang, "messages.ban_notify", &alert, &action, config);
+    let mut request = bot
+        .send_photo(
+            telegram_data(&instance.config()).chat,
+            InputFile::url(alert.user.avatar_url),
+        )
+        .caption(caption);
+    // Only the suspension can be reversed by the guardian
+    let can_unsuspend = !config.dry_run && ban_action.is_suspend();
+    if can_unsuspend {
+        request = request.reply_markup(InlineKeyboardMarkup::new([[InlineKeyboardButton::new(
+            t!("buttons.unsuspend", locale = lang),
+            InlineKeyboardButtonKind::CallbackData(format!(
+                "s {}",
+                instance.user_id(&alert.user.username)
+            )),
+        )]]));
+    }
+    let msg = request.await?;
+    if can_unsuspend {
+        instance
+            .database
+            .add_telegram_message(&alert.user.username, msg.id.0)
+            .ok();
+    }
 
     Ok(())
 }
@@ -81,78 +121,144 @@ pub async fn send_ban_notify(
 /// Send a ban request to the admins chat
 pub async fn send_ban_request(
     bot: &Bot,
-    telegram: &TelegramData,
-    re: &RegexReason,
+    instance: &Instance,
+    lang: &str,
     is_layz_purged: bool,
-    user: ForgejoUser,
-    config: &Config,
+    alert: UserAlert,
 ) -> ResponseResult<()> {
     tracing::info!("Sending ban request to the admins chat");
 
-    let msg = if re.re_vec.is_empty() {
-        t!("messages.ban_request")
+    let config = &instance.config();
+    let msg = if !alert.has_expressions() {
+        t!("messages.ban_request", locale = lang)
             .split("\n")
             .skip(1)
             .collect::<Vec<_>>()
             .join("\n")
     } else {
-        t!("messages.ban_request").into_owned()
+        t!("messages.ban_request", locale = lang).into_owned()
     };
 
-    let action = action_word(&config.expressions.ban_action);
-    let caption = user_details(&msg, &user, re, &action, config);
+    let action = action_word(lang, &alert.action(config));
+    let caption = user_details(lang, &msg, &alert, &action, config);
+    let user_id = instance.user_id(&alert.user.username);
     let keyboard = if is_layz_purged {
         InlineKeyboardMarkup::new([[InlineKeyboardButton::new(
-            t!("buttons.undo"),
-            InlineKeyboardButtonKind::CallbackData(format!("u {}", user.username)),
+            t!("buttons.undo", locale = lang),
+            InlineKeyboardButtonKind::CallbackData(format!("u {user_id}")),
         )]])
     } else {
-        make_ban_ignore_keyboard(&user, &action)
+        make_ban_ignore_keyboard(lang, &user_id, &action)
     };
 
-    bot.send_photo(telegram.chat, InputFile::url(user.avatar_url))
+    let msg = bot
+        .send_photo(
+            telegram_data(&instance.config()).chat,
+            InputFile::url(alert.user.avatar_url),
+        )
         .caption(caption)
         .reply_markup(keyboard)
         .await?;
+    instance
+        .database
+        .add_telegram_message(&alert.user.username, msg.id.0)
+        .ok();
 
     Ok(())
 }
 
-/// Handle the suspicious and banned users
-pub async fn users_handler(
-    bot: Bot,
-    database: Arc<Database>,
-    config: Arc<Config>,
-    telegram: Arc<TelegramData>,
-    cancellation_token: CancellationToken,
-    mut sus_receiver: Receiver<UserAlert>,
-    mut ban_receiver: Receiver<UserAlert>,
-) {
-    loop {
-        tokio::select! {
-            Some(alert) = sus_receiver.recv() => {
-                send_sus_alert(&bot, &telegram, &alert.reason, alert.user, &config).await.ok();
-            }
-            Some(alert) = ban_receiver.recv() => {
-                if alert.is_active {
-                    send_ban_request(
-                        &bot,
-                        &telegram,
-                        &alert.reason,
-                        database.is_lazy_purged(&alert.user.username).is_ok_and(|y|y),
-                        alert.user,
-                        &config
-                    )
-                    .await
-                    .ok();
-                } else {
-                    send_ban_notify(&bot,&telegram, &alert.reason, alert.user, &config).await.ok();
-                }
-            }
-            _ = cancellation_token.cancelled() => {
-                tracing::info!("Telegram users handler has been stopped successfully.");
-                break;
-            }
+/// Reflect a decision taken on another platform on the user alerts
+async fn reflect_decision(bot: &Bot, instance: &Instance, lang: &str, decision: Decision) {
+    let database = &instance.database;
+    let Ok(messages) = database.telegram_messages(&decision.username) else {
+        return;
+    };
+    let text = format!("{} ({})", decision.kind.message(lang), decision.moderator);
+    let user_id = instance.user_id(&decision.username);
+    let undo = (decision.kind == DecisionKind::AddedToPurgeQueue).then_some(user_id.as_str());
+
+    for message_id in messages {
+        if let Err(err) = bot
+            .edit_message_reply_markup(
+                telegram_data(&instance.config()).chat,
+                MessageId(message_id),
+            )
+            .reply_markup(source_inline_keyboard(lang, &text, undo))
+            .await
+        {
+            tracing::error!("Failed to reflect the decision on the alert: {err}");
+        }
+    }
+    if decision.kind.is_final() {
+        database.remove_telegram_messages(&decision.username).ok();
+    }
+}
+
+/// The Telegram notifier of an instance
+pub struct TelegramNotifier {
+    /// The Telegram bot
+    pub bot:      Bot,
+    /// The notifier instance
+    pub instance: Instance,
+    /// The bot language
+    pub lang:     Lang,
+}
+
+impl Notifier for TelegramNotifier {
+    const NAME: &'static str = "telegram";
+
+    async fn send_sus_alert(&self, alert: UserAlert) {
+        if let Err(err) = send_sus_alert(&self.bot, &self.instance, self.lang.as_str(), alert).await
+        {
+            tracing::error!("Failed to send the suspicious user alert: {err}");
+            metrics::notifier_failed(Self::NAME);
+        }
+    }
+
+    async fn send_ban_notify(&self, alert: UserAlert) {
+        if let Err(err) =
+            send_ban_notify(&self.bot, &self.instance, self.lang.as_str(), alert).await
+        {
+            tracing::error!("Failed to send the ban notification: {err}");
+            metrics::notifier_failed(Self::NAME);
+        }
+    }
+
+    async fn send_ban_request(&self, alert: UserAlert) {
+        let is_lazy_purged = self
+            .instance
+            .database
+            .is_lazy_purged(&alert.user.username)
+            .is_ok_and(|y| y);
+        if let Err(err) = send_ban_request(
+            &self.bot,
+            &self.instance,
+            self.lang.as_str(),
+            is_lazy_purged,
+            alert,
+        )
+        .await
+        {
+            tracing::error!("Failed to send the ban request: {err}");
+            metrics::notifier_failed(Self::NAME);
+        }
+    }
+
+    async fn on_decision(&self, decision: Decision) {
+        reflect_decision(&self.bot, &self.instance, self.lang.as_str(), decision).await
+    }
+
+    async fn send_report(&self, report: Report) {
+        let result = self
+            .bot
+            .send_message(
+                telegram_data(&self.instance.config()).chat,
+                report.message(self.lang.as_str(), self.instance.name()),
+            )
+            .await;
+        if let Err(err) = result {
+            tracing::error!("Failed to send the report: {err}");
+            metrics::notifier_failed(Self::NAME);
         }
     }
 }
@@ -0,0 +1,275 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
+
+//! Outgoing webhook notifier, POSTs the alerts and the moderators decisions as
+//! JSON documents to an external service.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::{Client, StatusCode};
+use serde::Serialize;
+use sha2::Sha256;
+use url::Url;
+
+use super::{notifier::Notifier, Decision, DecisionKind, UserAlert};
+use crate::{config::RegexReason, instance::Instance, metrics};
+
+/// The header of the request signature, HMAC-SHA256 hex digest of the
+/// timestamp and the body
+const SIGNATURE_HEADER: &str = "X-Guardian-Signature";
+/// The header of the request timestamp, the unix time of the document
+/// creation, to reject the replayed requests
+const TIMESTAMP_HEADER: &str = "X-Guardian-Timestamp";
+/// The header of the event type
+const EVENT_HEADER: &str = "X-Guardian-Event";
+/// The requests timeout
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Maximum backoff exponent, the maximum delay between the retries is 64
+/// seconds
+const MAX_BACKOFF_EXP: u32 = 6;
+
+/// The user in the alert document
+#[derive(Serialize)]
+struct UserDocument<'a> {
+    id:             usize,
+    username:       &'a str,
+    /// `None` if `hide_user_email` is enabled
+    email:          Option<&'a str>,
+    full_name:      &'a str,
+    biography:      &'a str,
+    website:        &'a str,
+    location:       &'a str,
+    avatar_url:     &'a Url,
+    html_url:       &'a Url,
+    created:        DateTime<Utc>,
+    is_admin:       bool,
+    prohibit_login: bool,
+}
+
+/// A matched expression in the alert document
+#[derive(Serialize)]
+pub(crate) struct ReasonDocument<'a> {
+    reason:      Option<&'a str>,
+    expressions: Vec<&'a str>,
+    weight:      f64,
+    location:    String,
+    matched:     &'a [String],
+}
+
+/// The user alert document
+#[derive(Serialize)]
+struct AlertDocument<'a> {
+    event:      &'static str,
+    instance:   Option<&'a str>,
+    forgejo:    Url,
+    dry_run:    bool,
+    ban_action: String,
+    user:       UserDocument<'a>,
+    reasons:    Vec<ReasonDocument<'a>>,
+    allowed_by: Vec<ReasonDocument<'a>>,
+    score:      Option<f64>,
+    is_active:  bool,
+}
+
+/// The moderator decision document
+#[derive(Serialize)]
+struct DecisionDocument<'a> {
+    event:      &'static str,
+    instance:   Option<&'a str>,
+    forgejo:    &'a Url,
+    username:   &'a str,
+    decision:   DecisionKind,
+    /// The ban action of the `banned` decision
+    ban_action: Option<String>,
+    moderator:  &'a str,
+    notifier:   &'static str,
+}
+
+impl<'a> From<&'a RegexReason> for ReasonDocument<'a> {
+    fn from(re: &'a RegexReason) -> Self {
+        Self {
+            reason:      re.reason.as_deref(),
+            expressions: re.re_vec.iter().map(|re| re.as_str()).collect(),
+            weight:      re.score_weight(),
+            location:    re.location.to_string(),
+            matched:     &re.matched,
+        }
+    }
+}
+
+/// Returns the request signature, the HMAC-SHA256 hex digest of the timestamp
+/// and the body separated by a dot
+fn signature(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size");
+    mac.update(format!("{timestamp}.").as_bytes());
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// The outgoing webhook notifier of an instance
+pub struct WebhookNotifier {
+    /// The HTTP client
+    client:   Client,
+    /// The notifier instance
+    instance: Instance,
+}
+
+impl WebhookNotifier {
+    /// Create a new webhook notifier, the instance must have an enabled
+    /// outgoing webhook
+    pub fn new(instance: Instance) -> Self {
+        Self {
+            client: Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .expect("Valid client"),
+            instance,
+        }
+    }
+
+    /// Returns the alert document of the event
+    fn alert_document<'a>(
+        &'a self,
+        event: &'static str,
+        alert: &'a UserAlert,
+    ) -> AlertDocument<'a> {
+        let config = &self.instance.config();
+        let user = &alert.user;
+        AlertDocument {
+            event,
+            instance: self.instance.name(),
+            forgejo: config.forgejo.instance.clone(),
+            dry_run: config.dry_run,
+            ban_action: alert.action(config).to_string(),
+            user: UserDocument {
+                id:             user.id,
+                username:       &user.username,
+                email:          (!config.hide_user_email).then_some(user.email.as_str()),
+                full_name:      &user.full_name,
+                biography:      &user.biography,
+                website:        &user.website,
+                location:       &user.location,
+                avatar_url:     &user.avatar_url,
+                html_url:       &user.html_url,
+                created:        user.created,
+                is_admin:       user.is_admin,
+                prohibit_login: user.prohibit_login,
+            },
+            reasons: alert.reasons.iter().map(ReasonDocument::from).collect(),
+            allowed_by: alert.allowed_by.iter().map(ReasonDocument::from).collect(),
+            score: alert.score,
+            is_active: alert.is_active,
+        }
+    }
+
+    /// Send the document in a separate task, retrying with exponential backoff
+    /// on failure
+    fn send(&self, event: &'static str, document: &impl Serialize) {
+        let config = self.instance.config();
+        let webhook = config
+            .webhook()
+            .expect("The instance has an enabled outgoing webhook");
+        let body = match serde_json::to_vec(document) {
+            Ok(body) => body,
+            Err(err) => {
+                tracing::error!("Failed to serialize the `{event}` webhook document: {err}");
+                return;
+            }
+        };
+        let timestamp = Utc::now().timestamp();
+        let signature = signature(&webhook.secret, timestamp, &body);
+
+        let request = self
+            .client
+            .post(webhook.url.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, event)
+            .header(TIMESTAMP_HEADER, timestamp)
+            .header(SIGNATURE_HEADER, signature)
+            .body(body);
+        let max_retries = webhook.max_retries;
+
+        tokio::spawn(async move {
+            for attempt in 0..=max_retries {
+                if attempt != 0 {
+                    tokio::time::sleep(Duration::from_secs(
+                        2u64.pow((attempt - 1).min(MAX_BACKOFF_EXP)),
+                    ))
+                    .await;
+                }
+
+                let err = match request.try_clone().expect("Not a stream").send().await {
+                    Ok(res) if res.status().is_success() => return,
+                    Ok(res) => {
+                        let status = res.status();
+                        if status.is_client_error() && status != StatusCode::TOO_MANY_REQUESTS {
+                            tracing::error!("The `{event}` webhook request is rejected: {status}");
+                            metrics::notifier_failed(Self::NAME);
+                            return;
+                        }
+                        status.to_string()
+                    }
+                    Err(err) => err.to_string(),
+                };
+                tracing::warn!(
+                    "Failed to send the `{event}` webhook request (retries \
+                     {attempt}/{max_retries}): {err}"
+                );
+            }
+            tracing::error!("Failed to send the `{event}` webhook request, giving up");
+            metrics::notifier_failed(Self::NAME);
+        });
+    }
+}
+
+impl Notifier for WebhookNotifier {
+    const NAME: &'static str = "webhook";
+
+    async fn send_sus_alert(&self, alert: UserAlert) {
+        self.send("sus_alert", &self.alert_document("sus_alert", &alert));
+    }
+
+    async fn send_ban_notify(&self, alert: UserAlert) {
+        self.send("ban_notify", &self.alert_document("ban_notify", &alert));
+    }
+
+    async fn send_ban_request(&self, alert: UserAlert) {
+        self.send("ban_request", &self.alert_document("ban_request", &alert));
+    }
+
+    async fn on_decision(&self, decision: Decision) {
+        self.send(
+            "decision",
+            &DecisionDocument {
+                event:      "decision",
+                instance:   self.instance.name(),
+                forgejo:    &self.instance.config().forgejo.instance,
+                username:   &decision.username,
+                decision:   decision.kind,
+                ban_action: decision.ban_action.as_ref().map(ToString::to_string),
+                moderator:  &decision.moderator,
+                notifier:   decision.notifier,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signed_timestamp() {
+        let body = br#"{"event":"decision"}"#;
+        let signature = signature("s3cret", 1_700_000_000, body);
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"s3cret").unwrap();
+        mac.update(br#"1700000000.{"event":"decision"}"#);
+        assert_eq!(signature, hex::encode(mac.finalize().into_bytes()));
+        // A replayed body with another timestamp doesn't match
+        assert_ne!(signature, super::signature("s3cret", 1_700_000_001, body));
+    }
+}
@@ -0,0 +1,237 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
+
+//! The `check` subcommand, checks a user against the expressions without
+//! taking any action, to test the expressions before deploying them.
+//!
+//! The user is fetched from Forgejo by its username, or read as a Forgejo user
+//! JSON from a file (`-` for the stdin).
+
+use std::io::Read;
+
+use crate::{
+    config::{Config, RegexReason},
+    email_domains::{self, DohResolver},
+    error::{GuardError, GuardResult},
+    feeds,
+    forgejo_api::{self, ForgejoUser},
+    inactive_users,
+    traits::{ExprChecker, ScoreChecker},
+    users_fetcher::{self, Verdict},
+    utils,
+};
+
+/// The usage of the `check` subcommand
+const USAGE: &str = "Usage: forgejo-guardian check [OPTIONS] <USERNAME>
+       forgejo-guardian check [OPTIONS] --file <PATH>
+
+Check a user against the expressions without taking any action.
+
+Options:
+  --file <PATH>        Read the user from a Forgejo user JSON file, `-` for the stdin
+  --instance <NAME>    The instance to check the user in, required if there are many instances
+  --inactive           Check if the user is inactive too
+  -h, --help           Print this help";
+
+/// The source of the checked user
+enum UserSource {
+    /// Fetch the user from Forgejo
+    Username(String),
+    /// Read the user JSON from a file, `-` for the stdin
+    File(String),
+}
+
+/// The `check` subcommand arguments
+struct CheckArgs {
+    /// The source of the user
+    source:   UserSource,
+    /// The instance name
+    instance: Option<String>,
+    /// Check if the user is inactive
+    inactive: bool,
+}
+
+impl CheckArgs {
+    /// Parse the subcommand arguments, returns `None` if the help is requested
+    fn parse(mut args: impl Iterator<Item = String>) -> GuardResult<Option<Self>> {
+        let mut source = None;
+        let mut instance = None;
+        let mut inactive = false;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "--inactive" => inactive = true,
+                "--file" | "--instance" => {
+                    let value = args.next().ok_or_else(|| {
+                        GuardError::Other(format!("The `{arg}` option requires a value\n\n{USAGE}"))
+                    })?;
+                    if arg == "--file" {
+                        source = Some(UserSource::File(value));
+                    } else {
+                        instance = Some(value);
+                    }
+                }
+                _ if arg.starts_with('-') => {
+                    return Err(GuardError::Other(format!(
+                        "Unknown option `{arg}`\n\n{USAGE}"
+                    )));
+                }
+                _ if source.is_none() => {
+                    source = Some(UserSource::Username(arg.trim_start_matches('@').to_owned()))
+                }
+                _ => {
+                    return Err(GuardError::Other(format!(
+                        "Unexpected argument `{arg}`\n\n{USAGE}"
+                    )));
+                }
+            }
+        }
+
+        let source = source.ok_or_else(|| {
+            GuardError::Other(format!("The username or `--file` is required\n\n{USAGE}"))
+        })?;
+        Ok(Some(Self {
+            source,
+            instance,
+            inactive,
+        }))
+    }
+}
+
+/// Returns the config of the instance, the instance name is required if there
+/// are many instances
+pub(crate) fn instance_config(configs: Vec<Config>, name: Option<&str>) -> GuardResult<Config> {
+    let names = || {
+        configs
+            .iter()
+            .filter_map(|config| config.forgejo.name.as_deref())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let found = match name {
+        Some(name) => {
+            configs
+                .iter()
+                .position(|config| config.forgejo.name.as_deref() == Some(name))
+        }
+        None if configs.len() == 1 => Some(0),
+        None => None,
+    };
+
+    match found {
+        Some(idx) => Ok(configs.into_iter().nth(idx).expect("The index exists")),
+        None => {
+            Err(GuardError::Other(format!(
+                "Unknown instance, choose one of the instances with `--instance`: {}",
+                names()
+            )))
+        }
+    }
+}
+
+/// Read the user JSON from the file, or from the stdin if the path is `-`
+fn read_user(path: &str) -> GuardResult<ForgejoUser> {
+    let content = if path == "-" {
+        let mut content = String::new();
+        std::io::stdin().read_to_string(&mut content)?;
+        content
+    } else {
+        std::fs::read_to_string(path)?
+    };
+
+    serde_json::from_str(&content)
+        .map_err(|err| GuardError::Other(format!("Invalid Forgejo user JSON: {err}")))
+}
+
+/// Print the matched expressions
+fn print_matches(name: &str, matches: &[RegexReason]) {
+    if matches.is_empty() {
+        println!("{name}: no matches");
+        return;
+    }
+    println!("{name}:");
+    for re in matches {
+        println!("  - {re}");
+    }
+}
+
+/// Returns the verdict name
+fn verdict_name(verdict: &Verdict) -> String {
+    match verdict {
+        Verdict::Ban { .. } => "ban".to_owned(),
+        Verdict::Downgrade { .. } => {
+            "sus (downgraded from ban by the allow expressions)".to_owned()
+        }
+        Verdict::Sus { .. } => "sus".to_owned(),
+        Verdict::Allow { expressions, .. } => {
+            format!("allowed (matched the {expressions} expressions)")
+        }
+        Verdict::Clean => "clean".to_owned(),
+    }
+}
+
+/// Run the `check` subcommand with its arguments
+pub async fn run(args: impl Iterator<Item = String>) -> GuardResult<()> {
+    let Some(args) = CheckArgs::parse(args)? else {
+        println!("{USAGE}");
+        return Ok(());
+    };
+    let config = instance_config(utils::get_configs()?, args.instance.as_deref())?;
+    let client = reqwest::Client::new();
+    feeds::fetch_all(&client, &config.expressions.feeds).await;
+
+    let (user, verdict) = match args.source {
+        UserSource::Username(ref username) => {
+            let user = forgejo_api::get_user(username, &config.forgejo).await?;
+            users_fetcher::dry_check_user(user, &client, &config).await
+        }
+        UserSource::File(ref path) => {
+            // The user content can't be read from the file, only the profile
+            // fields are checked
+            let user = read_user(path)?;
+            let verdict = users_fetcher::user_verdict(&user, &client, &config).await;
+            (user, verdict)
+        }
+    };
+
+    let exprs = &config.expressions;
+    println!("User: @{} ({})", user.username, user.html_url);
+    print_matches("ban", &exprs.ban_matches(&user));
+    print_matches("sus", &exprs.sus_matches(&user));
+    if exprs.score.enabled {
+        let (score, matches) = exprs.score.score(&user);
+        print_matches(&format!("score ({score})"), &matches);
+    }
+    if exprs.allow.enabled {
+        print_matches("allow", &exprs.allow.matches(&user));
+    }
+    if let Some(email_domains) = exprs.email_domains.as_ref() {
+        let blocked = email_domains::check_email(
+            email_domains,
+            &DohResolver::new(&client, &email_domains.mx_resolver),
+            &user.email,
+        )
+        .await;
+        print_matches(
+            &format!("email domains ({})", email_domains.action),
+            blocked.as_slice(),
+        );
+    }
+    println!("Verdict: {}", verdict_name(&verdict));
+
+    if args.inactive {
+        let inactive = inactive_users::is_inactive(
+            &client,
+            &config.forgejo.instance,
+            &config.forgejo.token,
+            &user.username,
+            config.check_tokens,
+            config.check_oauth2,
+        )
+        .await?;
+        println!("Inactive: {}", if inactive { "yes" } else { "no" });
+    }
+
+    Ok(())
+}
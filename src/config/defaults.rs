@@ -15,7 +15,7 @@ pub fn database() -> PathBuf {
 
 /// Default configuration for expressions section
 pub mod expressions {
-    use crate::config::BanAction;
+    use crate::config::{AllowAction, BanAction};
 
     /// Default interval for checking for new users.
     pub const fn interval() -> u32 {
@@ -31,6 +31,12 @@ pub mod expressions {
         BanAction::Purge
     }
 
+    /// Default action to take when a banned user matches the allow
+    /// expressions.
+    pub const fn allow_action() -> AllowAction {
+        AllowAction::Skip
+    }
+
     pub const fn req_limit() -> u32 {
         200
     }
@@ -38,6 +44,56 @@ pub mod expressions {
     pub const fn req_interval() -> u32 {
         10 * 60
     }
+
+    /// Default maximum number of the user repositories to check.
+    pub const fn repos_limit() -> u32 {
+        5
+    }
+
+    /// Default maximum number of the user activities to check.
+    pub const fn activities_limit() -> u32 {
+        20
+    }
+}
+
+/// Default configuration for the rule feeds.
+pub mod feeds {
+    /// Default interval to fetch the feed.
+    pub const fn interval() -> u32 {
+        60 * 60
+    }
+}
+
+/// Default configuration for email domains section.
+pub mod email_domains {
+    use url::Url;
+
+    use crate::config::DomainAction;
+
+    /// Default action to take when the email domain is blocked.
+    pub const fn action() -> DomainAction {
+        DomainAction::Ban
+    }
+
+    /// Default interval to check if the blocklist file got modified.
+    pub const fn reload_interval() -> u32 {
+        5 * 60
+    }
+
+    /// Default DNS over HTTPS resolver.
+    pub fn mx_resolver() -> Url {
+        Url::parse("https://cloudflare-dns.com/dns-query").expect("Valid url")
+    }
+}
+
+/// Default configuration for server section.
+pub mod server {
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    /// Default address to listen on. `127.0.0.1:8070`
+    pub const fn listen() -> SocketAddr {
+        SocketAddr::new(std::net::IpAddr::V4(Ipv4Addr::LOCALHOST), 8070)
+    }
 }
 
 /// Default configuration for inactive section.
@@ -76,3 +132,36 @@ pub mod lazy_purge {
         (60 * 60) * 2
     }
 }
+
+/// Default configuration for the outgoing webhook notifier section.
+pub mod webhook {
+    /// Default maximum retries of a failed request.
+    pub const fn max_retries() -> u32 {
+        5
+    }
+}
+
+/// Default configuration for the email notifier section.
+pub mod email {
+    /// Default lifetime of the emails links in hours, a week.
+    pub const fn link_ttl() -> u64 {
+        7 * 24
+    }
+}
+
+/// Default configuration for the snapshots section.
+pub mod snapshots {
+    /// Default retention of the snapshots, 30 days.
+    pub const fn retention() -> u32 {
+        30 * 24 * 60 * 60
+    }
+}
+
+/// Default configuration for the reload section.
+pub mod reload {
+    /// Default interval between each config file modification check, 10
+    /// seconds.
+    pub const fn interval() -> u32 {
+        10
+    }
+}
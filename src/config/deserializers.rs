@@ -8,7 +8,8 @@ use serde::{de, Deserialize};
 use toml::Value;
 use url::Url;
 
-use super::{utils, RegexReason};
+use super::{utils, BanAction, RegexReason};
+use crate::utils::{add_rules_file, resolve_pattern};
 
 /// Deserialize a string into a `url::Url`
 ///
@@ -25,6 +26,14 @@ where
     Ok(url)
 }
 
+/// Deserialize an optional URL, see [`url`]
+pub fn option_url<'de, D>(deserializer: D) -> Result<Option<Url>, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    url(deserializer).map(Some)
+}
+
 /// Parse the `re` key in the table, which can be a string or an array of string
 fn parse_re<'de, D>(toml_value: &Value) -> Result<Vec<String>, D::Error>
 where
@@ -63,6 +72,75 @@ where
         .collect()
 }
 
+/// Parse the `weight` key in the table, which can be an integer or a float
+/// greater than zero
+fn parse_weight<'de, D>(toml_value: &Value) -> Result<f64, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    let weight = match toml_value {
+        Value::Integer(weight) => *weight as f64,
+        Value::Float(weight) => *weight,
+        value => {
+            return Err(<D::Error as de::Error>::custom(format!(
+                "expected a number value for `weight`, found `{value}`"
+            )))
+        }
+    };
+
+    if !weight.is_finite() || weight <= 0.0 {
+        return Err(<D::Error as de::Error>::custom(format!(
+            "`weight` must be greater than zero, found `{weight}`"
+        )));
+    }
+    Ok(weight)
+}
+
+/// Parse the plain-text patterns files of the glob pattern, a regex per line
+/// with an optional ` # reason` comment at its end, split at the last ` # ` so
+/// the regex can contain it. The empty lines and the lines that start with `#`
+/// are ignored. The relative pattern is resolved against the config file
+/// directory
+fn parse_patterns_files<'de, D>(pattern: &str) -> Result<Vec<(String, Option<String>)>, D::Error>
+where
+    D: de::Deserializer<'de>,
+{
+    let mut paths = glob::glob(&resolve_pattern(pattern))
+        .map_err(|err| {
+            <D::Error as de::Error>::custom(format!("Invalid `file` pattern `{pattern}`: {err}"))
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(utils::custom_de_err::<'de, D>)?;
+    if paths.is_empty() {
+        tracing::warn!("The patterns file `{pattern}` doesn't match any file");
+    }
+    paths.sort();
+
+    let mut patterns = Vec::new();
+    for path in paths {
+        add_rules_file(&path);
+        let content = std::fs::read_to_string(&path).map_err(|err| {
+            <D::Error as de::Error>::custom(format!(
+                "Failed to read the patterns file `{}`: {err}",
+                path.display()
+            ))
+        })?;
+        patterns.extend(
+            content
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(|line| {
+                    match line.rsplit_once(" # ") {
+                        Some((re, reason)) => (re.trim_end().to_owned(), Some(reason.to_owned())),
+                        None => (line.to_owned(), None),
+                    }
+                }),
+        );
+    }
+    Ok(patterns)
+}
+
 /// Deserialize `RegexReason`
 pub fn regex_reason<'de, D>(deserializer: D) -> Result<Vec<RegexReason>, D::Error>
 where
@@ -71,20 +149,14 @@ where
     let Ok(toml_value) = Vec::<Value>::deserialize(deserializer) else {
         return Err(de::Error::custom(
             "expected an array contains strings or arrays of string or tables with the keys `re` \
-             and optional `reason`",
+             or `file` and optional `reason`, `weight` and `action`",
         ));
     };
 
-    toml_value
-        .into_iter()
-        .map(|value| {
+    let mut regexes = Vec::with_capacity(toml_value.len());
+    for value in toml_value {
+        regexes.extend(
             if let Value::Table(table) = value {
-                let re_vec = table.get("re").map(parse_re::<D>).ok_or_else(|| {
-                    <D::Error as de::Error>::custom(
-                        "The table must contain a `re` key with a string value or an array of \
-                         string",
-                    )
-                })??;
                 let reason = table
                     .get("reason")
                     .map(|reason| {
@@ -95,28 +167,73 @@ where
                         })
                     })
                     .transpose()?;
+                let weight = table.get("weight").map(parse_weight::<D>).transpose()?;
+                let action = table
+                    .get("action")
+                    .map(|action| {
+                        action.clone().try_into::<BanAction>().map_err(|_| {
+                            <D::Error as de::Error>::custom(format!(
+                                "expected a ban action for `action`, found `{action}`"
+                            ))
+                        })
+                    })
+                    .transpose()?;
 
                 // Warn for unused keys
                 for key in table.keys() {
-                    if !["re", "reason"].contains(&key.as_str()) {
+                    if !["re", "file", "reason", "weight", "action"].contains(&key.as_str()) {
                         tracing::warn!("Unused key `{key}` in the configuration");
                     }
                 }
 
-                Ok(RegexReason::new(parse_re_vec::<D>(re_vec)?, reason))
+                match (table.get("re"), table.get("file")) {
+                    (Some(re), None) => {
+                        vec![
+                            RegexReason::new(parse_re_vec::<D>(parse_re::<D>(re)?)?, reason)
+                                .weight(weight)
+                                .action(action),
+                        ]
+                    }
+                    (None, Some(Value::String(file))) => {
+                        parse_patterns_files::<D>(file)?
+                            .into_iter()
+                            .map(|(re, line_reason)| {
+                                Ok(RegexReason::new(
+                                    parse_re_vec::<D>(vec![re])?,
+                                    line_reason.or_else(|| reason.clone()),
+                                )
+                                .weight(weight)
+                                .action(action))
+                            })
+                            .collect::<Result<Vec<_>, D::Error>>()?
+                    }
+                    (None, Some(file)) => {
+                        return Err(de::Error::custom(format!(
+                            "expected a string value for `file`, found `{file}`"
+                        )))
+                    }
+                    _ => {
+                        return Err(de::Error::custom(
+                            "The table must contain either a `re` key with a string value or an \
+                             array of string, or a `file` key with a patterns file path",
+                        ))
+                    }
+                }
             } else if matches!(value, Value::String(_) | Value::Array(_)) {
-                Ok(RegexReason::new(
+                vec![RegexReason::new(
                     parse_re_vec::<D>(parse_re::<D>(&value)?)?,
                     None,
-                ))
+                )]
             } else {
-                Err(de::Error::custom(format!(
+                return Err(de::Error::custom(format!(
                     "unexpected value in the regex list, expected a string or an array of string \
-                     or a table with `re` (string) and optional `reason` (string), found `{value}`"
-                )))
-            }
-        })
-        .collect()
+                     or a table with `re` (string) or `file` (string) and optional `reason` \
+                     (string), `weight` (number) and `action` (string), found `{value}`"
+                )));
+            },
+        );
+    }
+    Ok(regexes)
 }
 
 pub fn suffix_interval<'de, D>(des: D) -> Result<u32, D::Error>
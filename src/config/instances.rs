@@ -0,0 +1,239 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
+
+//! Multiple Forgejo instances, each `[[instances]]` table is merged over the
+//! root configuration to produce the instance configuration.
+
+use std::collections::HashSet;
+
+use toml::{Table, Value};
+
+use crate::error::{GuardError, GuardResult};
+
+/// The instances array key
+const INSTANCES_KEY: &str = "instances";
+/// Maximum length of the instance name, the name is used in the bots callback
+/// data which has a limited length
+const MAX_NAME_LEN: usize = 20;
+
+/// Merge the `other` table over the `base` table. The nested tables are merged
+/// and the other values (including the arrays) are replaced
+fn deep_merge(base: &mut Table, other: Table) {
+    for (key, value) in other {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base_table)), Value::Table(other_table)) => {
+                deep_merge(base_table, other_table)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Returns a configuration error
+fn config_err(msg: impl Into<String>) -> GuardError {
+    GuardError::Other(format!("Configuration Error: {}", msg.into()))
+}
+
+/// Check the instance table, returns its name
+fn instance_name(instance: &mut Table, names: &mut HashSet<String>) -> GuardResult<String> {
+    let name = match instance.remove("name") {
+        Some(Value::String(name)) => name,
+        Some(value) => {
+            return Err(config_err(format!(
+                "expected a string value for the instance `name`, found `{value}`"
+            )))
+        }
+        None => return Err(config_err("Each instance must have a `name`")),
+    };
+
+    if name.is_empty()
+        || name.len() > MAX_NAME_LEN
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(config_err(format!(
+            "Invalid instance name `{name}`, it must be 1 to {MAX_NAME_LEN} characters of ASCII \
+             letters, digits, `-` and `_`"
+        )));
+    }
+    if !names.insert(name.clone()) {
+        return Err(config_err(format!("Duplicate instance name `{name}`")));
+    }
+
+    for key in ["database", "reload"] {
+        if instance.contains_key(key) {
+            return Err(config_err(format!(
+                "The `{key}` is global and can't be overridden in the `{name}` instance"
+            )));
+        }
+    }
+    if let Some(server) = instance.get("server").and_then(Value::as_table) {
+        if server.keys().any(|key| key != "webhook") {
+            return Err(config_err(format!(
+                "Only the `server.webhook` can be overridden in the `{name}` instance, the server \
+                 itself is global"
+            )));
+        }
+    }
+
+    Ok(name)
+}
+
+/// Split the root configuration into the instances configurations, with their
+/// names. If there is no `[[instances]]` the root configuration will be
+/// returned without a name
+pub fn split_instances(mut root: Table) -> GuardResult<Vec<(Option<String>, Table)>> {
+    let Some(instances) = root.remove(INSTANCES_KEY) else {
+        return Ok(vec![(None, root)]);
+    };
+    let Value::Array(instances) = instances else {
+        return Err(config_err(
+            "The `instances` must be an array of tables, `[[instances]]`",
+        ));
+    };
+    if instances.is_empty() {
+        return Err(config_err("The `instances` array is empty"));
+    }
+
+    let mut names = HashSet::new();
+    instances
+        .into_iter()
+        .map(|instance| {
+            let Value::Table(mut instance) = instance else {
+                return Err(config_err(format!(
+                    "The `instances` must be an array of tables, found `{instance}`"
+                )));
+            };
+            let name = instance_name(&mut instance, &mut names)?;
+            let mut config = root.clone();
+            deep_merge(&mut config, instance);
+            Ok((Some(name), config))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(config: &str) -> GuardResult<Vec<(Option<String>, Table)>> {
+        split_instances(toml::from_str(config).unwrap())
+    }
+
+    fn split_err(config: &str) -> String {
+        split(config).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn single_instance() {
+        let instances = split(
+            r#"
+            [forgejo]
+            instance_url = "https://forgejo.example"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(instances.len(), 1);
+        assert_eq!(instances[0].0, None);
+        assert!(instances[0].1.contains_key("forgejo"));
+    }
+
+    #[test]
+    fn merged_instances() {
+        let instances = split(
+            r#"
+            dry_run = true
+
+            [forgejo]
+            token = "root-token"
+
+            [expressions]
+            interval = 60
+
+            [expressions.ban]
+            usernames = ["^spam"]
+
+            [[instances]]
+            name = "codeberg"
+            forgejo.instance_url = "https://codeberg.example"
+
+            [[instances]]
+            name = "local"
+            forgejo = { instance_url = "https://local.example", token = "local-token" }
+            expressions.ban.usernames = ["^bot"]
+            server.webhook.secret = "s3cret"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(instances.len(), 2);
+        let (name, codeberg) = &instances[0];
+        assert_eq!(name.as_deref(), Some("codeberg"));
+        assert!(!codeberg.contains_key(INSTANCES_KEY));
+        assert_eq!(codeberg["dry_run"].as_bool(), Some(true));
+        // The nested tables are merged
+        assert_eq!(codeberg["forgejo"]["token"].as_str(), Some("root-token"));
+        assert_eq!(
+            codeberg["forgejo"]["instance_url"].as_str(),
+            Some("https://codeberg.example")
+        );
+        assert_eq!(
+            codeberg["expressions"]["ban"]["usernames"][0].as_str(),
+            Some("^spam")
+        );
+
+        let (name, local) = &instances[1];
+        assert_eq!(name.as_deref(), Some("local"));
+        assert!(!local.contains_key("name"));
+        assert_eq!(local["forgejo"]["token"].as_str(), Some("local-token"));
+        assert_eq!(local["expressions"]["interval"].as_integer(), Some(60));
+        // The arrays are replaced
+        assert_eq!(
+            local["expressions"]["ban"]["usernames"].as_array().unwrap(),
+            &[Value::from("^bot")]
+        );
+        assert_eq!(
+            local["server"]["webhook"]["secret"].as_str(),
+            Some("s3cret")
+        );
+    }
+
+    #[test]
+    fn forbidden_overrides() {
+        let instance =
+            |table: &str| split_err(&format!("[[instances]]\nname = \"codeberg\"\n{table}"));
+
+        assert!(instance("database = \"other.redb\"")
+            .contains("The `database` is global and can't be overridden in the `codeberg`"));
+        assert!(instance("reload = true")
+            .contains("The `reload` is global and can't be overridden in the `codeberg`"));
+        assert!(instance("server.listen = \"0.0.0.0:80\"")
+            .contains("Only the `server.webhook` can be overridden in the `codeberg` instance"));
+    }
+
+    #[test]
+    fn invalid_instances() {
+        assert!(split_err("instances = 1").contains("must be an array of tables"));
+        assert!(split_err("instances = []").contains("The `instances` array is empty"));
+        assert!(split_err("instances = [1]").contains("must be an array of tables, found `1`"));
+        assert!(
+            split_err("[[instances]]\nforgejo = {}").contains("Each instance must have a `name`")
+        );
+        assert!(split_err("[[instances]]\nname = 1").contains("expected a string value"));
+        for name in ["", "a/b", "with space", "a-very-long-instance-name"] {
+            assert!(
+                split_err(&format!("[[instances]]\nname = \"{name}\""))
+                    .contains(&format!("Invalid instance name `{name}`")),
+                "`{name}` should be invalid"
+            );
+        }
+        assert!(split_err(
+            "[[instances]]\nname = \"codeberg\"\n[[instances]]\nname = \"codeberg\""
+        )
+        .contains("Duplicate instance name `codeberg`"));
+    }
+}
@@ -1,7 +1,7 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
 
-use std::fmt;
+use std::{borrow::Cow, fmt};
 
 /// The location where the regex got matched
 #[derive(Debug, Clone)]
@@ -14,6 +14,35 @@ pub enum Locations {
     Email,
     Website,
     Location,
+    EmailDomain,
+    EmailMx,
+    RepoName,
+    RepoDescription,
+    Readme,
+    IssueTitle,
+    CommentBody,
+}
+
+impl Locations {
+    /// Returns the translated location, to be used in the bots messages
+    pub fn translated(&self, lang: &str) -> Cow<'_, str> {
+        match self {
+            Locations::Unknown => Cow::Borrowed("N/A"),
+            Locations::Username => t!("locations.username", locale = lang),
+            Locations::FullName => t!("locations.full_name", locale = lang),
+            Locations::Biographie => t!("locations.biography", locale = lang),
+            Locations::Email => t!("locations.email", locale = lang),
+            Locations::Website => t!("locations.website", locale = lang),
+            Locations::Location => t!("locations.location", locale = lang),
+            Locations::EmailDomain => t!("locations.email_domain", locale = lang),
+            Locations::EmailMx => t!("locations.email_mx", locale = lang),
+            Locations::RepoName => t!("locations.repo_name", locale = lang),
+            Locations::RepoDescription => t!("locations.repo_description", locale = lang),
+            Locations::Readme => t!("locations.readme", locale = lang),
+            Locations::IssueTitle => t!("locations.issue_title", locale = lang),
+            Locations::CommentBody => t!("locations.comment_body", locale = lang),
+        }
+    }
 }
 
 impl fmt::Display for Locations {
@@ -26,6 +55,13 @@ impl fmt::Display for Locations {
             Locations::Email => write!(f, "email"),
             Locations::Website => write!(f, "website"),
             Locations::Location => write!(f, "location"),
+            Locations::EmailDomain => write!(f, "email domain"),
+            Locations::EmailMx => write!(f, "email MX host"),
+            Locations::RepoName => write!(f, "repository name"),
+            Locations::RepoDescription => write!(f, "repository description"),
+            Locations::Readme => write!(f, "repository README"),
+            Locations::IssueTitle => write!(f, "issue title"),
+            Locations::CommentBody => write!(f, "comment"),
         }
     }
 }
@@ -5,29 +5,38 @@
 pub(crate) const CONFIG_PATH_ENV: &str = "FORGEJO_GUARDIAN_CONFIG";
 /// Defult config path location
 pub(crate) const DEFAULT_CONFIG_PATH: &str = "/app/forgejo-guardian.toml";
+/// The default weight of the regular expression, used by the score expressions
+pub(crate) const DEFAULT_WEIGHT: f64 = 1.0;
 
-use std::{fmt::Display, path::PathBuf};
+use std::{fmt::Display, net::SocketAddr, path::PathBuf, sync::Arc};
 
+use lettre::message::Mailbox;
 use matrix_sdk::ruma::OwnedRoomId;
 use regex::Regex;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use teloxide::types::ChatId;
+use tokio::sync::watch;
 use url::Url;
 
-use crate::bots::Lang;
+use crate::{bots::Lang, email_domains::DomainList, feeds::FeedRules};
 
 mod boolean;
 mod defaults;
 mod deserializers;
+pub mod instances;
 pub mod locations;
 pub mod parse_invalid;
 mod utils;
 
 use locations::Locations;
 
+/// Receiver of the instance configuration, it always has the latest reloaded
+/// configuration
+pub type ConfigReceiver = watch::Receiver<Arc<Config>>;
+
 /// Ban action to take when banning a user
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "lowercase")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum BanAction {
     /// Purge the user (Forcibly delete user and any repositories,
     /// organizations, and packages owned by the user. All comments and issues
@@ -36,6 +45,40 @@ pub enum BanAction {
     /// Suspend the user (Block this user from interacting with this service
     /// through their account and prohibit signing in.)
     Suspend,
+    /// Restrict the user, the restricted user can only access the
+    /// repositories and organizations they are a member of
+    Restrict,
+    /// Prevent the user from creating repositories, by setting their maximum
+    /// number of repositories to zero
+    DisableRepoCreation,
+    /// Make all the user repositories private, keeping the account
+    PrivateRepos,
+    /// Delete all the user repositories, keeping the account
+    DeleteRepos,
+    /// Delete the user issues, pull requests and comments, keeping the account
+    /// and the repositories
+    DeleteContent,
+}
+
+/// Action to take when a banned or suspected user matches the allow expressions
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AllowAction {
+    /// Skip the user, neither ban them nor alert the moderation team
+    Skip,
+    /// Downgrade the ban to a suspicious alert, the suspected users are
+    /// skipped
+    Downgrade,
+}
+
+/// The expressions to add the matched email domain to
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DomainAction {
+    /// Ban the user
+    Ban,
+    /// Alert the moderation team about the user
+    Sus,
 }
 
 #[derive(Deserialize)]
@@ -86,6 +129,9 @@ pub struct Forgejo {
     /// The instance, e.g. `https://example.com` or `https://example.com/` or `http://example.com:8080`
     #[serde(rename = "instance_url", deserialize_with = "deserializers::url")]
     pub instance: Url,
+    /// The instance name, it's set only if there are multiple instances
+    #[serde(skip)]
+    pub name:     Option<String>,
 }
 
 /// The telegram bot data
@@ -152,11 +198,19 @@ pub enum Matrix {
 #[derive(Debug, Clone)]
 pub struct RegexReason {
     /// The regular expression
-    pub re_vec: Vec<Regex>,
+    pub re_vec:   Vec<Regex>,
     /// Optional reason
-    pub reason: Option<String>,
+    pub reason:   Option<String>,
+    /// The weight of the regex, summed into the user score when it's matched.
+    /// It's only set in the `score` expressions
+    pub weight:   Option<f64>,
     /// The location where the regex got matched
-    location:   Locations,
+    pub location: Locations,
+    /// The matched substrings, one for each regex
+    pub matched:  Vec<String>,
+    /// The ban action of the regex, overrides the global ban action when the
+    /// user is banned because of it
+    pub action:   Option<BanAction>,
 }
 
 /// The expression
@@ -165,6 +219,10 @@ pub struct Expr {
     /// Whether the expression is enabled
     #[serde(default = "defaults::bool_true")]
     pub enabled:   bool,
+    /// Glob patterns of the rules files to include, TOML files with the same
+    /// regexes keys of the expression
+    #[serde(default)]
+    pub include:   Vec<String>,
     /// The regular expressions that the action will be performed if they are
     /// present in the username
     #[serde(default)]
@@ -200,6 +258,70 @@ pub struct Expr {
     #[serde(default)]
     #[serde(deserialize_with = "deserializers::regex_reason")]
     pub locations: Vec<RegexReason>,
+
+    /// The regular expressions that the action will be performed if they are
+    /// present in one of the user repositories names
+    #[serde(default)]
+    #[serde(deserialize_with = "deserializers::regex_reason")]
+    pub repo_names: Vec<RegexReason>,
+
+    /// The regular expressions that the action will be performed if they are
+    /// present in one of the user repositories descriptions
+    #[serde(default)]
+    #[serde(deserialize_with = "deserializers::regex_reason")]
+    pub repo_descriptions: Vec<RegexReason>,
+
+    /// The regular expressions that the action will be performed if they are
+    /// present in one of the user repositories READMEs
+    #[serde(default)]
+    #[serde(deserialize_with = "deserializers::regex_reason")]
+    pub readmes: Vec<RegexReason>,
+
+    /// The regular expressions that the action will be performed if they are
+    /// present in one of the user issues or pull requests titles
+    #[serde(default)]
+    #[serde(deserialize_with = "deserializers::regex_reason")]
+    pub issue_titles: Vec<RegexReason>,
+
+    /// The regular expressions that the action will be performed if they are
+    /// present in one of the user comments
+    #[serde(default)]
+    #[serde(deserialize_with = "deserializers::regex_reason")]
+    pub comment_bodies: Vec<RegexReason>,
+}
+
+/// The email domains blocklist
+#[derive(Deserialize, Debug)]
+pub struct EmailDomains {
+    /// Whether the email domains blocklist is enabled
+    #[serde(default = "defaults::bool_true")]
+    pub enabled:         bool,
+    /// The blocklist file, contains a domain per line
+    pub blocklist:       PathBuf,
+    /// Whether to ban or alert the moderation team about the matched users
+    #[serde(default = "defaults::email_domains::action")]
+    pub action:          DomainAction,
+    /// Optional reason
+    #[serde(default)]
+    pub reason:          Option<String>,
+    /// Interval to check if the blocklist file got modified, to reload it
+    #[serde(
+        default = "defaults::email_domains::reload_interval",
+        deserialize_with = "deserializers::suffix_interval"
+    )]
+    pub reload_interval: u32,
+    /// Resolve the email domain MX hosts and check them against the blocklist
+    #[serde(default)]
+    pub check_mx:        bool,
+    /// DNS over HTTPS resolver, to resolve the MX hosts
+    #[serde(
+        default = "defaults::email_domains::mx_resolver",
+        deserialize_with = "deserializers::url"
+    )]
+    pub mx_resolver:     Url,
+    /// The loaded blocklist
+    #[serde(skip)]
+    pub list:            DomainList,
 }
 
 /// the expressions
@@ -259,6 +381,67 @@ pub struct Exprs {
     /// true
     #[serde(default)]
     pub sus: Expr,
+    /// Score expressions.
+    ///
+    /// The weights of all matched expressions are summed into the user score,
+    /// the score is compared with `ban_score` and `sus_score`
+    #[serde(default)]
+    pub score: Expr,
+    /// The minimum score to ban the user
+    #[serde(default)]
+    pub ban_score: Option<f64>,
+    /// The minimum score to alert the moderation team about the user
+    #[serde(default)]
+    pub sus_score: Option<f64>,
+    /// Allow expressions.
+    ///
+    /// Veto the `ban` and `sus` expressions, the users who match one of them
+    /// are skipped or downgraded from ban to sus, see `allow_action`
+    #[serde(default)]
+    pub allow: Expr,
+    /// Action to take when a banned user matches the allow expressions
+    #[serde(default = "defaults::expressions::allow_action")]
+    pub allow_action: AllowAction,
+    /// Email domains blocklist
+    #[serde(default)]
+    pub email_domains: Option<EmailDomains>,
+    /// Remote rule feeds, merged into the `ban` and `sus` expressions
+    #[serde(default)]
+    pub feeds: Vec<Feed>,
+    /// Maximum number of the user repositories to check, for `repo_names`,
+    /// `repo_descriptions` and `readmes` expressions
+    #[serde(default = "defaults::expressions::repos_limit")]
+    pub repos_limit: u32,
+    /// Maximum number of the user latest activities to check, for
+    /// `issue_titles` and `comment_bodies` expressions
+    #[serde(default = "defaults::expressions::activities_limit")]
+    pub activities_limit: u32,
+    /// The loaded rules files, the `include` files and the patterns files
+    #[serde(skip)]
+    pub rules_files: Vec<PathBuf>,
+}
+
+/// A remote rule feed, a signed rules file with `ban` and `sus` tables
+#[derive(Deserialize)]
+pub struct Feed {
+    /// The rules file URL
+    #[serde(deserialize_with = "deserializers::url")]
+    pub url:           Url,
+    /// The feed secret, used to verify the rules file signature
+    pub secret:        String,
+    /// The signature URL, the hex encoded HMAC-SHA256 of the rules file.
+    /// Defaults to the rules file URL with `.sig` suffix
+    #[serde(default, deserialize_with = "deserializers::option_url")]
+    pub signature_url: Option<Url>,
+    /// Interval to fetch the feed
+    #[serde(
+        default = "defaults::feeds::interval",
+        deserialize_with = "deserializers::suffix_interval"
+    )]
+    pub interval:      u32,
+    /// The last fetched rules of the feed
+    #[serde(skip)]
+    pub rules:         Arc<FeedRules>,
 }
 
 /// Lazy purge configuration
@@ -293,6 +476,166 @@ pub struct LazyPurge {
     pub purge_after:  u32,
 }
 
+/// Forgejo webhooks configuration
+#[derive(Deserialize)]
+pub struct Webhook {
+    /// Whether the webhooks receiver is enabled
+    #[serde(default = "defaults::bool_true")]
+    pub enabled: bool,
+    /// The webhook secret, used to verify the webhook signature
+    pub secret:  String,
+}
+
+/// The admin dashboard configuration
+#[derive(Deserialize)]
+pub struct Dashboard {
+    /// Whether the dashboard is enabled
+    #[serde(default = "defaults::bool_true")]
+    pub enabled: bool,
+    /// The admin token, required to access the dashboard
+    pub token:   String,
+    /// The dashboard language
+    #[serde(default)]
+    pub lang:    Lang,
+}
+
+/// The REST API configuration
+#[derive(Deserialize)]
+pub struct Api {
+    /// Whether the API is enabled
+    #[serde(default = "defaults::bool_true")]
+    pub enabled: bool,
+    /// The API token, required in the `Authorization` header as a bearer token
+    pub token:   String,
+}
+
+/// The outgoing webhook notifier configuration, to POST the alerts and the
+/// decisions to an external service
+#[derive(Deserialize)]
+pub struct OutgoingWebhook {
+    /// Whether the webhook notifier is enabled
+    #[serde(default = "defaults::bool_true")]
+    pub enabled:     bool,
+    /// The URL to POST the events to
+    #[serde(deserialize_with = "deserializers::url")]
+    pub url:         Url,
+    /// The shared secret, used to sign the requests body
+    pub secret:      String,
+    /// Maximum retries of a failed request, with exponential backoff
+    #[serde(default = "defaults::webhook::max_retries")]
+    pub max_retries: u32,
+}
+
+/// The SMTP connection security
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// Plain connection upgraded with `STARTTLS`
+    #[default]
+    Starttls,
+    /// Implicit TLS connection
+    Tls,
+    /// Plain connection without encryption, e.g. a local SMTP server
+    None,
+}
+
+/// The email (SMTP) notifier configuration
+#[derive(Deserialize)]
+pub struct Email {
+    /// Whether the email notifier is enabled
+    #[serde(default = "defaults::bool_true")]
+    pub enabled:  bool,
+    /// The SMTP server host
+    pub host:     String,
+    /// The SMTP server port, the default port of the `tls` will be used if
+    /// it's not set
+    #[serde(default)]
+    pub port:     Option<u16>,
+    /// The SMTP connection security
+    #[serde(default)]
+    pub tls:      SmtpTls,
+    /// The SMTP username
+    #[serde(default)]
+    pub username: Option<String>,
+    /// The SMTP password
+    #[serde(default)]
+    pub password: Option<String>,
+    /// The sender of the emails
+    pub from:     Mailbox,
+    /// The recipients of the emails
+    pub to:       Vec<Mailbox>,
+    /// The emails language
+    pub lang:     Lang,
+    /// The public URL of the guardian HTTP server, used in the emails links
+    #[serde(deserialize_with = "deserializers::url")]
+    pub base_url: Url,
+    /// The secret, used to sign the emails links
+    pub secret:   String,
+    /// The emails links lifetime in hours
+    #[serde(default = "defaults::email::link_ttl")]
+    pub link_ttl: u64,
+}
+
+/// The users profiles snapshots, taken before purging them
+#[derive(Deserialize)]
+pub struct Snapshots {
+    /// Whether the snapshots are enabled
+    #[serde(default = "defaults::bool_true")]
+    pub enabled:   bool,
+    /// The directory to store the snapshots in, they are stored in the
+    /// database if it's not set
+    #[serde(default)]
+    pub directory: Option<PathBuf>,
+    /// How long to keep the snapshots
+    #[serde(
+        default = "defaults::snapshots::retention",
+        deserialize_with = "deserializers::suffix_interval"
+    )]
+    pub retention: u32,
+    /// Include the user repositories and activities (issues and comments)
+    /// metadata in the snapshots
+    #[serde(default)]
+    pub content:   bool,
+}
+
+/// The HTTP server configuration
+#[derive(Deserialize)]
+pub struct Server {
+    /// Whether the HTTP server is enabled
+    #[serde(default)]
+    pub enabled:   bool,
+    /// The address to listen on
+    #[serde(default = "defaults::server::listen")]
+    pub listen:    SocketAddr,
+    /// Forgejo webhooks receiver, to check the users instantly
+    #[serde(default)]
+    pub webhook:   Option<Webhook>,
+    /// The admin dashboard
+    #[serde(default)]
+    pub dashboard: Option<Dashboard>,
+    /// The REST API
+    #[serde(default)]
+    pub api:       Option<Api>,
+    /// Export the Prometheus metrics on `/metrics`
+    #[serde(default)]
+    pub metrics:   bool,
+}
+
+/// The configuration hot reload
+#[derive(Deserialize)]
+pub struct Reload {
+    /// Watch the config file and its rules files, to reload it when any of them
+    /// is modified
+    #[serde(default)]
+    pub watch:    bool,
+    /// Interval between each config file modification check
+    #[serde(
+        default = "defaults::reload::interval",
+        deserialize_with = "deserializers::suffix_interval"
+    )]
+    pub interval: u32,
+}
+
 /// forgejo-guard configuration
 #[derive(Deserialize)]
 pub struct Config {
@@ -333,6 +676,49 @@ pub struct Config {
     /// The expressions, which are used to determine the actions
     #[serde(default)]
     pub expressions:     Exprs,
+    /// The HTTP server configuration
+    #[serde(default)]
+    pub server:          Server,
+    /// The outgoing webhook notifier configuration
+    #[serde(default)]
+    pub webhook:         Option<OutgoingWebhook>,
+    /// The email notifier configuration
+    #[serde(default)]
+    pub email:           Option<Email>,
+    /// The users profiles snapshots configuration
+    #[serde(default)]
+    pub snapshots:       Option<Snapshots>,
+    /// The configuration hot reload
+    #[serde(default)]
+    pub reload:          Reload,
+}
+
+impl Config {
+    /// Returns `true` if there is an enabled notifier, to alert the moderation
+    /// team
+    pub fn has_notifiers(&self) -> bool {
+        self.telegram.is_enabled()
+            || self.matrix.is_enabled()
+            || self.webhook().is_some()
+            || self.email().is_some()
+    }
+
+    /// Returns the email notifier configuration if it's enabled
+    pub fn email(&self) -> Option<&Email> {
+        self.email.as_ref().filter(|email| email.enabled)
+    }
+
+    /// Returns the outgoing webhook configuration if it's enabled
+    pub fn webhook(&self) -> Option<&OutgoingWebhook> {
+        self.webhook.as_ref().filter(|webhook| webhook.enabled)
+    }
+
+    /// Returns the snapshots configuration if it's enabled
+    pub fn snapshots(&self) -> Option<&Snapshots> {
+        self.snapshots
+            .as_ref()
+            .filter(|snapshots| snapshots.enabled)
+    }
 }
 
 impl BanAction {
@@ -340,6 +726,162 @@ impl BanAction {
     pub fn is_purge(&self) -> bool {
         matches!(self, Self::Purge)
     }
+
+    /// Returns `true` if the action is `Suspend`
+    pub fn is_suspend(&self) -> bool {
+        matches!(self, Self::Suspend)
+    }
+
+    /// Returns the severity of the action, the more destructive and the less
+    /// reversible action is the more severe
+    pub fn severity(&self) -> u8 {
+        match self {
+            Self::DisableRepoCreation => 0,
+            Self::PrivateRepos => 1,
+            Self::Restrict => 2,
+            Self::Suspend => 3,
+            Self::DeleteContent => 4,
+            Self::DeleteRepos => 5,
+            Self::Purge => 6,
+        }
+    }
+
+    /// Returns the action of the ban reasons, the most severe action of the
+    /// matched expressions, regardless of their order. The expressions without
+    /// an action use the given default action
+    pub fn of_reasons(reasons: &[RegexReason], default: BanAction) -> BanAction {
+        reasons
+            .iter()
+            .map(|reason| reason.action.unwrap_or(default))
+            .max_by_key(BanAction::severity)
+            .unwrap_or(default)
+    }
+}
+
+impl Expr {
+    /// Merge the regexes of the other expression into this expression
+    pub fn extend(&mut self, other: Expr) {
+        self.usernames.extend(other.usernames);
+        self.full_names.extend(other.full_names);
+        self.biographies.extend(other.biographies);
+        self.emails.extend(other.emails);
+        self.websites.extend(other.websites);
+        self.locations.extend(other.locations);
+        self.repo_names.extend(other.repo_names);
+        self.repo_descriptions.extend(other.repo_descriptions);
+        self.readmes.extend(other.readmes);
+        self.issue_titles.extend(other.issue_titles);
+        self.comment_bodies.extend(other.comment_bodies);
+    }
+
+    /// Returns the number of the expression regexes
+    pub fn regexes_count(&self) -> usize {
+        self.regexes().count()
+    }
+
+    /// Returns an iterator over the expression regexes
+    pub fn regexes(&self) -> impl Iterator<Item = &RegexReason> {
+        [
+            &self.usernames,
+            &self.full_names,
+            &self.biographies,
+            &self.emails,
+            &self.websites,
+            &self.locations,
+            &self.repo_names,
+            &self.repo_descriptions,
+            &self.readmes,
+            &self.issue_titles,
+            &self.comment_bodies,
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+impl Exprs {
+    /// Returns `true` if the score reached the `ban_score`
+    pub fn is_ban_score(&self, score: f64) -> bool {
+        self.score.enabled && self.ban_score.is_some_and(|min| score >= min)
+    }
+
+    /// Returns `true` if the score reached the `sus_score`
+    pub fn is_sus_score(&self, score: f64) -> bool {
+        self.score.enabled && self.sus_score.is_some_and(|min| score >= min)
+    }
+}
+
+impl Exprs {
+    /// Returns `true` if the email domains blocklist is enabled
+    pub fn is_email_domains_enabled(&self) -> bool {
+        self.email_domains.as_ref().is_some_and(|e| e.enabled)
+    }
+
+    /// Returns `true` if one of the enabled expressions, or the feeds
+    /// expressions merged into them, satisfies the predicate
+    fn any_expr(&self, predicate: impl Fn(&Expr) -> bool) -> bool {
+        [&self.ban, &self.sus, &self.score, &self.allow]
+            .into_iter()
+            .filter(|expr| expr.enabled)
+            .any(&predicate)
+            || self.feeds_rules().iter().any(|rules| {
+                (self.ban.enabled && predicate(&rules.ban))
+                    || (self.sus.enabled && predicate(&rules.sus))
+            })
+    }
+
+    /// Returns `true` if the user repositories are needed by one of the
+    /// enabled expressions
+    pub fn needs_repos(&self) -> bool {
+        self.any_expr(|expr| {
+            !expr.repo_names.is_empty()
+                || !expr.repo_descriptions.is_empty()
+                || !expr.readmes.is_empty()
+        })
+    }
+
+    /// Returns `true` if the user repositories READMEs are needed by one of
+    /// the enabled expressions
+    pub fn needs_readmes(&self) -> bool {
+        self.any_expr(|expr| !expr.readmes.is_empty())
+    }
+
+    /// Returns `true` if the user activities are needed by one of the enabled
+    /// expressions
+    pub fn needs_activities(&self) -> bool {
+        self.any_expr(|expr| !expr.issue_titles.is_empty() || !expr.comment_bodies.is_empty())
+    }
+
+    /// Returns the maximum number of requests to fetch the user content
+    pub fn max_content_reqs(&self) -> u32 {
+        let mut reqs = 0;
+        if self.needs_repos() {
+            reqs += 1;
+        }
+        if self.needs_readmes() {
+            // Two requests for each README, the repository contents and the
+            // README content
+            reqs += self.repos_limit * 2;
+        }
+        if self.needs_activities() {
+            reqs += 1;
+        }
+        reqs
+    }
+}
+
+impl DomainAction {
+    /// Returns `true` if the action is `Ban`
+    pub fn is_ban(&self) -> bool {
+        matches!(self, Self::Ban)
+    }
+}
+
+impl AllowAction {
+    /// Returns `true` if the action is `Downgrade`
+    pub fn is_downgrade(&self) -> bool {
+        matches!(self, Self::Downgrade)
+    }
 }
 
 impl RegexReason {
@@ -348,15 +890,41 @@ impl RegexReason {
         Self {
             re_vec: re,
             reason,
+            weight: None,
             location: Locations::Unknown,
+            matched: Vec::new(),
+            action: None,
         }
     }
 
+    /// Set the ban action of the regex
+    pub fn action(mut self, action: Option<BanAction>) -> Self {
+        self.action = action;
+        self
+    }
+
+    /// Set the weight of the regex
+    pub fn weight(mut self, weight: Option<f64>) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    /// Returns the weight of the regex, the default weight if it's not set
+    pub fn score_weight(&self) -> f64 {
+        self.weight.unwrap_or(DEFAULT_WEIGHT)
+    }
+
     /// Set the location of the regex
     pub fn location(mut self, location: Locations) -> Self {
         self.location = location;
         self
     }
+
+    /// Set the matched substrings of the regex
+    pub fn matched(mut self, matched: Vec<String>) -> Self {
+        self.matched = matched;
+        self
+    }
 }
 
 impl Telegram {
@@ -405,11 +973,34 @@ impl Display for RegexReason {
     }
 }
 
+impl Display for DomainAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ban => write!(f, "ban"),
+            Self::Sus => write!(f, "sus"),
+        }
+    }
+}
+
+impl Display for AllowAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Skip => write!(f, "skip"),
+            Self::Downgrade => write!(f, "downgrade"),
+        }
+    }
+}
+
 impl Display for BanAction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Purge => write!(f, "purge"),
             Self::Suspend => write!(f, "suspend"),
+            Self::Restrict => write!(f, "restrict"),
+            Self::DisableRepoCreation => write!(f, "disable_repo_creation"),
+            Self::PrivateRepos => write!(f, "private_repos"),
+            Self::DeleteRepos => write!(f, "delete_repos"),
+            Self::DeleteContent => write!(f, "delete_content"),
         }
     }
 }
@@ -445,6 +1036,16 @@ impl Default for Exprs {
             ban_action: defaults::expressions::ban_action(),
             ban: Expr::default(),
             sus: Expr::default(),
+            score: Expr::default(),
+            ban_score: None,
+            sus_score: None,
+            allow: Expr::default(),
+            allow_action: defaults::expressions::allow_action(),
+            email_domains: None,
+            feeds: Vec::new(),
+            repos_limit: defaults::expressions::repos_limit(),
+            activities_limit: defaults::expressions::activities_limit(),
+            rules_files: Vec::new(),
         }
     }
 }
@@ -465,6 +1066,52 @@ impl Default for Matrix {
     }
 }
 
+impl Server {
+    /// Returns the webhook configuration if the server and the webhooks
+    /// receiver are enabled
+    pub fn webhook(&self) -> Option<&Webhook> {
+        self.webhook
+            .as_ref()
+            .filter(|webhook| self.enabled && webhook.enabled)
+    }
+
+    /// Returns the dashboard configuration if it's enabled, and the server is
+    /// enabled
+    pub fn dashboard(&self) -> Option<&Dashboard> {
+        self.dashboard
+            .as_ref()
+            .filter(|dashboard| self.enabled && dashboard.enabled)
+    }
+
+    /// Returns the API configuration if it's enabled, and the server is
+    /// enabled
+    pub fn api(&self) -> Option<&Api> {
+        self.api.as_ref().filter(|api| self.enabled && api.enabled)
+    }
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Self {
+            enabled:   false,
+            listen:    defaults::server::listen(),
+            webhook:   None,
+            dashboard: None,
+            api:       None,
+            metrics:   false,
+        }
+    }
+}
+
+impl Default for Reload {
+    fn default() -> Self {
+        Self {
+            watch:    false,
+            interval: defaults::reload::interval(),
+        }
+    }
+}
+
 impl Default for LazyPurge {
     fn default() -> Self {
         Self {
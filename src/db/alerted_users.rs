@@ -1,34 +1,95 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
 
-use redb::{Database, TableDefinition};
+use redb::{ReadableTable, TableDefinition};
 
-use crate::error::GuardResult;
+use crate::{
+    config::BanAction,
+    db::GuardDb,
+    error::{GuardError, GuardResult},
+};
 
 /// A table containing alerted users, with the username as the key and no value.
 pub(super) const ALERTED_USERS_TABLE: TableDefinition<&str, ()> =
     TableDefinition::new("alerted_users");
 
+/// A table containing the ban action of the alerted users, with the username as
+/// the key and the serialized ban action as the value. The action is stored
+/// when the user is alerted, so a reloaded global ban action doesn't change it.
+/// The users alerted by the ban commands are not in the table
+pub(super) const ALERT_ACTIONS_TABLE: TableDefinition<&str, &str> =
+    TableDefinition::new("alert_actions");
+
+/// A table containing the matched rule of the alerted users, with the username
+/// as the key and the rule as the value, the rule is audited with the
+/// moderators bans. The users alerted by the ban commands are not in the table
+pub(super) const ALERT_RULES_TABLE: TableDefinition<&str, &str> =
+    TableDefinition::new("alert_rules");
+
 #[easy_ext::ext(AlertedUsersTableTrait)]
-impl Database {
+impl GuardDb {
     /// Add a new alerted user to the database
     pub fn add_alerted_user(&self, username: &str) -> GuardResult<()> {
         tracing::info!("Adding alerted user: {username}");
         let write_txn = self.begin_write()?;
         {
-            let mut table = write_txn.open_table(ALERTED_USERS_TABLE)?;
+            let mut table = write_txn.open_table(self.table(ALERTED_USERS_TABLE))?;
             table.insert(username, ())?;
         }
         write_txn.commit()?;
         Ok(())
     }
 
-    /// Remove alerted user, if exist
+    /// Set the ban action of the alerted user, the action that the moderators
+    /// ban the user with, and the matched rule of the alert
+    pub fn set_alert_action(
+        &self,
+        username: &str,
+        ban_action: BanAction,
+        rule: &str,
+    ) -> GuardResult<()> {
+        let ban_action = serde_json::to_string(&ban_action).map_err(|err| {
+            GuardError::Other(format!("Failed to serialize the ban action: {err}"))
+        })?;
+        let write_txn = self.begin_write()?;
+        {
+            let mut table = write_txn.open_table(self.table(ALERT_ACTIONS_TABLE))?;
+            table.insert(username, ban_action.as_str())?;
+            let mut table = write_txn.open_table(self.table(ALERT_RULES_TABLE))?;
+            table.insert(username, rule)?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    /// Returns the ban action of the alerted user, `None` if the user has been
+    /// alerted without an action, e.g. by the ban commands
+    pub fn alert_action(&self, username: &str) -> GuardResult<Option<BanAction>> {
+        let read_txn = self.begin_read()?;
+        let table = read_txn.open_table(self.table(ALERT_ACTIONS_TABLE))?;
+        Ok(table
+            .get(username)?
+            .and_then(|action| serde_json::from_str(action.value()).ok()))
+    }
+
+    /// Returns the matched rule of the alerted user, `None` if the user has
+    /// been alerted without expressions, e.g. by the ban commands
+    pub fn alert_rule(&self, username: &str) -> GuardResult<Option<String>> {
+        let read_txn = self.begin_read()?;
+        let table = read_txn.open_table(self.table(ALERT_RULES_TABLE))?;
+        Ok(table.get(username)?.map(|rule| rule.value().to_owned()))
+    }
+
+    /// Remove alerted user and its ban action and rule, if exist
     pub fn remove_alerted_user(&self, username: &str) -> GuardResult<()> {
         tracing::info!("Removing alerted user: {username}");
         let write_txn = self.begin_write()?;
         {
-            let mut table = write_txn.open_table(ALERTED_USERS_TABLE)?;
+            let mut table = write_txn.open_table(self.table(ALERTED_USERS_TABLE))?;
+            table.remove(username)?;
+            let mut table = write_txn.open_table(self.table(ALERT_ACTIONS_TABLE))?;
+            table.remove(username)?;
+            let mut table = write_txn.open_table(self.table(ALERT_RULES_TABLE))?;
             table.remove(username)?;
         }
         write_txn.commit()?;
@@ -38,7 +99,17 @@ impl Database {
     /// Returns `true` if the user already alerted to the moderation team
     pub fn is_alerted(&self, username: &str) -> GuardResult<bool> {
         let read_txn = self.begin_read()?;
-        let table = read_txn.open_table(ALERTED_USERS_TABLE)?;
+        let table = read_txn.open_table(self.table(ALERTED_USERS_TABLE))?;
         Ok(table.get(username).map(|o| o.is_some())?)
     }
+
+    /// Returns the alerted users
+    pub fn alerted_users(&self) -> GuardResult<Vec<String>> {
+        let read_txn = self.begin_read()?;
+        let table = read_txn.open_table(self.table(ALERTED_USERS_TABLE))?;
+        Ok(table
+            .iter()?
+            .filter_map(|e| e.ok().map(|(u, _)| u.value().to_owned()))
+            .collect())
+    }
 }
@@ -0,0 +1,317 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
+
+use std::borrow::Cow;
+
+use chrono::{DateTime, Utc};
+use redb::{ReadableTable, TableDefinition};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bots::{Decision, DecisionKind},
+    config::{BanAction, Config},
+    db::GuardDb,
+    error::{GuardError, GuardResult},
+};
+
+/// The audit log table, an append-only log of the guardian and the moderators
+/// actions, the key is the entry sequence number and the value is the JSON
+/// entry.
+pub(super) const AUDIT_LOG_TABLE: TableDefinition<u64, &str> = TableDefinition::new("audit_log");
+
+/// Who took the action
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Actor {
+    /// The guardian itself, an automated action
+    Guardian,
+    /// A moderator decision
+    Moderator {
+        /// The moderator, e.g. the Telegram username or the Matrix user ID
+        name:     String,
+        /// The notifier where the decision has been taken, see
+        /// [`crate::bots::notifier::Notifier::NAME`]
+        notifier: String,
+    },
+}
+
+/// The audited action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    /// The user has been banned
+    Banned,
+    /// The user has been added to the purge queue
+    AddedToPurgeQueue,
+    /// The user has been ignored
+    Ignored,
+    /// The user purge has been undone
+    Undone,
+    /// The user has been removed from the ignored users
+    Unignored,
+    /// The suspended user has been unsuspended
+    Unsuspended,
+    /// The user in the purge queue has been purged
+    LazyPurged,
+    /// The inactive user has been purged
+    InactivePurged,
+}
+
+/// An audit log entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// When the action has been taken
+    pub time:       DateTime<Utc>,
+    /// Who took the action
+    pub actor:      Actor,
+    /// The target username
+    pub username:   String,
+    /// The target user ID, if it's known
+    pub user_id:    Option<usize>,
+    /// The action
+    pub action:     AuditAction,
+    /// The ban action of the bans and the purges
+    pub ban_action: Option<BanAction>,
+    /// The matched rule of the automated bans and the alerted users bans
+    pub rule:       Option<String>,
+    /// Whether the guardian is in dry run, the action has not been taken on
+    /// the instance
+    pub dry_run:    bool,
+}
+
+/// The audit log query filter, all the filters are optional
+#[derive(Debug, Default)]
+pub struct AuditFilter {
+    /// Only the entries of this username
+    pub username:  Option<String>,
+    /// Only the decisions of this moderator
+    pub moderator: Option<String>,
+    /// Only the entries since this time (inclusive)
+    pub since:     Option<DateTime<Utc>>,
+    /// Only the entries until this time (inclusive)
+    pub until:     Option<DateTime<Utc>>,
+    /// Maximum number of the entries to return
+    pub limit:     Option<usize>,
+}
+
+impl AuditAction {
+    /// Returns the translated action message
+    pub fn message(&self, lang: &str) -> Cow<'static, str> {
+        match self {
+            AuditAction::Banned => t!("messages.ban_success", locale = lang),
+            AuditAction::AddedToPurgeQueue => t!("messages.added_to_purge_queue", locale = lang),
+            AuditAction::Ignored => t!("messages.ban_denied", locale = lang),
+            AuditAction::Undone => t!("messages.undo_success", locale = lang),
+            AuditAction::Unignored => t!("messages.unignore_success", locale = lang),
+            AuditAction::Unsuspended => t!("messages.unsuspend_success", locale = lang),
+            AuditAction::LazyPurged => t!("messages.lazy_purged", locale = lang),
+            AuditAction::InactivePurged => t!("messages.inactive_purged", locale = lang),
+        }
+    }
+}
+
+impl From<DecisionKind> for AuditAction {
+    fn from(kind: DecisionKind) -> Self {
+        match kind {
+            DecisionKind::Banned => AuditAction::Banned,
+            DecisionKind::AddedToPurgeQueue => AuditAction::AddedToPurgeQueue,
+            DecisionKind::Ignored => AuditAction::Ignored,
+            DecisionKind::Undone => AuditAction::Undone,
+            DecisionKind::Unignored => AuditAction::Unignored,
+            DecisionKind::Unsuspended => AuditAction::Unsuspended,
+        }
+    }
+}
+
+impl AuditEntry {
+    /// Create an entry of an automated action
+    pub fn guardian(
+        config: &Config,
+        username: impl Into<String>,
+        user_id: Option<usize>,
+        action: AuditAction,
+        ban_action: Option<BanAction>,
+    ) -> Self {
+        Self {
+            time: Utc::now(),
+            actor: Actor::Guardian,
+            username: username.into(),
+            user_id,
+            action,
+            ban_action,
+            rule: None,
+            dry_run: config.dry_run,
+        }
+    }
+
+    /// Create an entry of a moderator decision
+    pub fn decision(config: &Config, decision: &Decision) -> Self {
+        let ban_action = match decision.kind {
+            DecisionKind::Banned => {
+                Some(decision.ban_action.unwrap_or(config.expressions.ban_action))
+            }
+            DecisionKind::AddedToPurgeQueue => Some(BanAction::Purge),
+            _ => None,
+        };
+        Self {
+            time: Utc::now(),
+            actor: Actor::Moderator {
+                name:     decision.moderator.clone(),
+                notifier: decision.notifier.to_owned(),
+            },
+            username: decision.username.clone(),
+            user_id: None,
+            action: decision.kind.into(),
+            ban_action,
+            rule: decision.rule.clone(),
+            dry_run: config.dry_run,
+        }
+    }
+
+    /// Set the matched rule
+    pub fn rule(mut self, rule: impl Into<String>) -> Self {
+        self.rule = Some(rule.into());
+        self
+    }
+
+    /// Returns `true` if the entry matches the filter, the `since` filter is
+    /// checked by the query
+    fn matches(&self, filter: &AuditFilter) -> bool {
+        filter.username.as_ref().is_none_or(|u| u == &self.username)
+            && filter
+                .moderator
+                .as_ref()
+                .is_none_or(|m| matches!(&self.actor, Actor::Moderator { name, .. } if name == m))
+            && filter.until.is_none_or(|until| self.time <= until)
+    }
+}
+
+#[easy_ext::ext(AuditLogTableTrait)]
+impl GuardDb {
+    /// Append a new entry to the audit log
+    pub fn add_audit_entry(&self, entry: &AuditEntry) -> GuardResult<()> {
+        let entry = serde_json::to_string(entry).map_err(|err| {
+            GuardError::Other(format!("Failed to serialize the audit entry: {err}"))
+        })?;
+        let write_txn = self.begin_write()?;
+        {
+            let mut table = write_txn.open_table(self.table(AUDIT_LOG_TABLE))?;
+            let next = table.last()?.map_or(0, |(key, _)| key.value() + 1);
+            table.insert(next, entry.as_str())?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    /// Append a new entry to the audit log, logging the error if any
+    pub fn audit(&self, entry: AuditEntry) {
+        if let Err(err) = self.add_audit_entry(&entry) {
+            tracing::error!(
+                "Failed to add the audit entry of @{}: {err}",
+                entry.username
+            );
+        }
+    }
+
+    /// Returns the audit log entries that match the filter, newest first. The
+    /// log is append-only, so the entries are read from the newest until the
+    /// limit is reached or an entry is older than `since`
+    pub fn audit_log(&self, filter: &AuditFilter) -> GuardResult<Vec<AuditEntry>> {
+        let read_txn = self.begin_read()?;
+        let table = read_txn.open_table(self.table(AUDIT_LOG_TABLE))?;
+        Ok(table
+            .iter()?
+            .rev()
+            .filter_map(|e| {
+                e.ok()
+                    .and_then(|(_, entry)| serde_json::from_str::<AuditEntry>(entry.value()).ok())
+            })
+            .take_while(|entry| filter.since.is_none_or(|since| entry.time >= since))
+            .filter(|entry| entry.matches(filter))
+            .take(filter.limit.unwrap_or(usize::MAX))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::TimeDelta;
+    use redb::{backends::InMemoryBackend, Database};
+
+    use super::*;
+
+    fn database() -> GuardDb {
+        let db = Database::builder()
+            .create_with_backend(InMemoryBackend::new())
+            .unwrap();
+        GuardDb::new(Arc::new(db), None).unwrap()
+    }
+
+    fn config() -> Config {
+        toml::from_str(
+            r#"
+            [forgejo]
+            instance_url = "https://example.org"
+            token = "token"
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn decision_rule() {
+        let decision = Decision::new("spammer", DecisionKind::Banned, "moderator", "telegram")
+            .ban_action(BanAction::Suspend)
+            .rule(Some("`^spam in their username`".to_owned()));
+        let entry = AuditEntry::decision(&config(), &decision);
+
+        assert_eq!(entry.ban_action, Some(BanAction::Suspend));
+        assert_eq!(entry.rule.as_deref(), Some("`^spam in their username`"));
+    }
+
+    #[test]
+    fn newest_entries() {
+        let (database, config) = (database(), config());
+        let now = Utc::now();
+        for (username, hours) in [("old", 3), ("middle", 2), ("new", 1)] {
+            let mut entry =
+                AuditEntry::guardian(&config, username, None, AuditAction::Banned, None);
+            entry.time = now - TimeDelta::hours(hours);
+            database.add_audit_entry(&entry).unwrap();
+        }
+        let usernames = |filter: AuditFilter| {
+            database
+                .audit_log(&filter)
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.username)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(usernames(AuditFilter::default()), ["new", "middle", "old"]);
+        assert_eq!(
+            usernames(AuditFilter {
+                limit: Some(2),
+                ..Default::default()
+            }),
+            ["new", "middle"]
+        );
+        assert_eq!(
+            usernames(AuditFilter {
+                since: Some(now - TimeDelta::minutes(150)),
+                ..Default::default()
+            }),
+            ["new", "middle"]
+        );
+        assert_eq!(
+            usernames(AuditFilter {
+                until: Some(now - TimeDelta::minutes(90)),
+                limit: Some(1),
+                ..Default::default()
+            }),
+            ["middle"]
+        );
+    }
+}
@@ -0,0 +1,59 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
+
+use redb::TableDefinition;
+
+use crate::{
+    config::BanAction,
+    db::GuardDb,
+    error::{GuardError, GuardResult},
+};
+
+/// A table containing the banned users that still have their accounts, with the
+/// username as the key and the serialized applied ban action as the value. The
+/// purged users are not in the table
+pub(super) const BANNED_USERS_TABLE: TableDefinition<&str, &str> =
+    TableDefinition::new("banned_users");
+
+#[easy_ext::ext(BannedUsersTableTrait)]
+impl GuardDb {
+    /// Add a new banned user with the applied ban action to the database, the
+    /// purged users are skipped, their accounts are deleted
+    pub fn add_banned_user(&self, username: &str, ban_action: BanAction) -> GuardResult<()> {
+        if ban_action.is_purge() {
+            return Ok(());
+        }
+        tracing::info!("Adding banned user: {username} ({ban_action})");
+        let ban_action = serde_json::to_string(&ban_action).map_err(|err| {
+            GuardError::Other(format!("Failed to serialize the ban action: {err}"))
+        })?;
+        let write_txn = self.begin_write()?;
+        {
+            let mut table = write_txn.open_table(self.table(BANNED_USERS_TABLE))?;
+            table.insert(username, ban_action.as_str())?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    /// Remove banned user, if exist
+    pub fn remove_banned_user(&self, username: &str) -> GuardResult<()> {
+        tracing::info!("Removing banned user: {username}");
+        let write_txn = self.begin_write()?;
+        {
+            let mut table = write_txn.open_table(self.table(BANNED_USERS_TABLE))?;
+            table.remove(username)?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    /// Returns the ban action applied to the user, if any
+    pub fn banned_action(&self, username: &str) -> GuardResult<Option<BanAction>> {
+        let read_txn = self.begin_read()?;
+        let table = read_txn.open_table(self.table(BANNED_USERS_TABLE))?;
+        Ok(table
+            .get(username)?
+            .and_then(|action| serde_json::from_str(action.value()).ok()))
+    }
+}
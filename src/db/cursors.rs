@@ -0,0 +1,107 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
+
+use redb::TableDefinition;
+
+use crate::{db::GuardDb, error::GuardResult, forgejo_api::Sort};
+
+/// A table containing the users fetchers cursors, with the fetcher sort as the
+/// key and the cursor as the value. The cursor of `newest` and `recentupdate`
+/// is the last seen users ids, and the cursor of `oldest` (the existing users
+/// scan) is the next page.
+pub(super) const CURSORS_TABLE: TableDefinition<&str, Vec<u64>> = TableDefinition::new("cursors");
+
+/// Returns the stored cursor of the fetcher, if any
+fn cursor(db: &GuardDb, sort: &Sort) -> GuardResult<Option<Vec<u64>>> {
+    let read_txn = db.begin_read()?;
+    let table = read_txn.open_table(db.table(CURSORS_TABLE))?;
+    Ok(table.get(sort.as_str())?.map(|c| c.value()))
+}
+
+/// Store the cursor of the fetcher
+fn set_cursor(db: &GuardDb, sort: &Sort, cursor: Vec<u64>) -> GuardResult<()> {
+    tracing::debug!("Storing {sort} cursor: {cursor:?}");
+    let write_txn = db.begin_write()?;
+    {
+        let mut table = write_txn.open_table(db.table(CURSORS_TABLE))?;
+        table.insert(sort.as_str(), cursor)?;
+    }
+    write_txn.commit()?;
+    Ok(())
+}
+
+#[easy_ext::ext(CursorsTableTrait)]
+impl GuardDb {
+    /// Returns the stored last seen users ids of the fetcher, if any
+    pub fn users_ids_cursor(&self, sort: &Sort) -> GuardResult<Option<Vec<usize>>> {
+        Ok(cursor(self, sort)?
+            .filter(|ids| !ids.is_empty())
+            .map(|ids| ids.into_iter().map(|id| id as usize).collect()))
+    }
+
+    /// Store the last seen users ids of the fetcher
+    pub fn set_users_ids_cursor(&self, sort: &Sort, ids: &[usize]) -> GuardResult<()> {
+        set_cursor(self, sort, ids.iter().map(|id| *id as u64).collect())
+    }
+
+    /// Returns the stored next page of the existing users scan, if any
+    pub fn old_users_page(&self) -> GuardResult<Option<u32>> {
+        Ok(cursor(self, &Sort::Oldest)?
+            .and_then(|page| page.first().copied())
+            .map(|page| page as u32))
+    }
+
+    /// Store the next page of the existing users scan
+    pub fn set_old_users_page(&self, page: u32) -> GuardResult<()> {
+        set_cursor(self, &Sort::Oldest, vec![page.into()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::db::init_db;
+
+    #[test]
+    fn persisted_cursors() {
+        let path = std::env::temp_dir().join(format!(
+            "forgejo-guardian-{}-cursors.redb",
+            std::process::id()
+        ));
+        let open = |namespace| GuardDb::new(Arc::new(init_db(&path).unwrap()), namespace).unwrap();
+
+        {
+            let db = open(None);
+            assert_eq!(db.users_ids_cursor(&Sort::Newest).unwrap(), None);
+            assert_eq!(db.old_users_page().unwrap(), None);
+
+            db.set_users_ids_cursor(&Sort::Newest, &[42, 41, 40])
+                .unwrap();
+            db.set_users_ids_cursor(&Sort::RecentUpdate, &[7]).unwrap();
+            db.set_old_users_page(3).unwrap();
+            db.set_old_users_page(4).unwrap();
+            // An empty cursor is not a cursor, the fetcher seeds it again
+            db.set_users_ids_cursor(&Sort::RecentUpdate, &[]).unwrap();
+        }
+
+        // The cursors survive a restart, each fetcher with its own cursor
+        let db = open(None);
+        assert_eq!(
+            db.users_ids_cursor(&Sort::Newest).unwrap(),
+            Some(vec![42, 41, 40])
+        );
+        assert_eq!(db.users_ids_cursor(&Sort::RecentUpdate).unwrap(), None);
+        assert_eq!(db.old_users_page().unwrap(), Some(4));
+        drop(db);
+
+        // The instances cursors are namespaced
+        let db = open(Some("codeberg"));
+        assert_eq!(db.users_ids_cursor(&Sort::Newest).unwrap(), None);
+        assert_eq!(db.old_users_page().unwrap(), None);
+        drop(db);
+
+        std::fs::remove_file(&path).ok();
+    }
+}
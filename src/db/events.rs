@@ -4,21 +4,21 @@
 use std::str::FromStr;
 
 use matrix_sdk::ruma::{EventId, OwnedEventId};
-use redb::{Database, ReadableTable, TableDefinition};
+use redb::{ReadableTable, TableDefinition};
 
-use crate::error::GuardResult;
+use crate::{db::GuardDb, error::GuardResult};
 
 /// Events table, stores the event id and the username, the key is the event id
 /// and the value is the username.
 pub(super) const EVENTS_TABLE: TableDefinition<&str, &str> = TableDefinition::new("events");
 
 #[easy_ext::ext(EventsTableTrait)]
-impl Database {
+impl GuardDb {
     // Add a new event to the database
     pub fn add_event(&self, event: &EventId, username: &str) -> GuardResult<()> {
         let write_txn = self.begin_write()?;
         {
-            let mut table = write_txn.open_table(EVENTS_TABLE)?;
+            let mut table = write_txn.open_table(self.table(EVENTS_TABLE))?;
             table.insert(event.to_string().as_str(), username)?;
         }
         write_txn.commit()?;
@@ -29,31 +29,33 @@ impl Database {
     pub fn remove_event(&self, event: &EventId) -> GuardResult<()> {
         let write_txn = self.begin_write()?;
         {
-            let mut table = write_txn.open_table(EVENTS_TABLE)?;
+            let mut table = write_txn.open_table(self.table(EVENTS_TABLE))?;
             table.remove(event.to_string().as_str())?;
         }
         write_txn.commit()?;
         Ok(())
     }
 
-    /// Remove user events
-    pub fn remove_user_events(&self, username: &str) -> GuardResult<()> {
-        let event_ids: Vec<_> = {
-            let read_txn = self.begin_read()?;
-            let table = read_txn.open_table(EVENTS_TABLE)?;
-            table
-                .iter()?
-                .filter_map(|e| {
-                    e.ok().and_then(|(eid, u)| {
-                        (u.value() == username).then_some(eid.value().to_owned())
+    /// Returns the user events
+    pub fn user_events(&self, username: &str) -> GuardResult<Vec<OwnedEventId>> {
+        let read_txn = self.begin_read()?;
+        let table = read_txn.open_table(self.table(EVENTS_TABLE))?;
+        Ok(table
+            .iter()?
+            .filter_map(|e| {
+                e.ok().and_then(|(eid, u)| {
+                    (u.value() == username).then(|| {
+                        OwnedEventId::from_str(eid.value()).expect("It's a valid event id")
                     })
                 })
-                .collect()
-        };
+            })
+            .collect())
+    }
 
-        for event_id in event_ids {
-            self.remove_event(&OwnedEventId::from_str(&event_id).expect("It's a valid event id"))
-                .ok();
+    /// Remove user events
+    pub fn remove_user_events(&self, username: &str) -> GuardResult<()> {
+        for event_id in self.user_events(username)? {
+            self.remove_event(&event_id).ok();
         }
         Ok(())
     }
@@ -61,7 +63,7 @@ impl Database {
     // Get the username of an event, if it exists
     pub fn get_username(&self, event: &EventId) -> GuardResult<Option<String>> {
         let read_txn = self.begin_read()?;
-        let table = read_txn.open_table(EVENTS_TABLE)?;
+        let table = read_txn.open_table(self.table(EVENTS_TABLE))?;
         Ok(table
             .get(event.to_string().as_str())
             .map(|o| o.map(|g| g.value().to_string()))?)
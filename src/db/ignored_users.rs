@@ -1,32 +1,54 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
 
-use redb::{Database, TableDefinition};
+use redb::{ReadableTable, TableDefinition};
 
-use crate::error::GuardResult;
+use crate::{db::GuardDb, error::GuardResult};
 
 /// A table containing ignored users, with the username as the key and no value.
 pub(super) const IGNORED_USERS_TABLE: TableDefinition<&str, ()> =
     TableDefinition::new("ignored_users");
 
 #[easy_ext::ext(IgnoredUsersTableTrait)]
-impl Database {
+impl GuardDb {
     /// Add a new ignored user to the database
     pub fn add_ignored_user(&self, username: &str) -> GuardResult<()> {
         tracing::info!("Adding ignored user: {username}");
         let write_txn = self.begin_write()?;
         {
-            let mut table = write_txn.open_table(IGNORED_USERS_TABLE)?;
+            let mut table = write_txn.open_table(self.table(IGNORED_USERS_TABLE))?;
             table.insert(username, ())?;
         }
         write_txn.commit()?;
         Ok(())
     }
 
+    /// Remove ignored user, if exist
+    pub fn remove_ignored_user(&self, username: &str) -> GuardResult<()> {
+        tracing::info!("Removing ignored user: {username}");
+        let write_txn = self.begin_write()?;
+        {
+            let mut table = write_txn.open_table(self.table(IGNORED_USERS_TABLE))?;
+            table.remove(username)?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
     /// Returns `true` if the user exists
     pub fn is_ignored(&self, username: &str) -> GuardResult<bool> {
         let read_txn = self.begin_read()?;
-        let table = read_txn.open_table(IGNORED_USERS_TABLE)?;
+        let table = read_txn.open_table(self.table(IGNORED_USERS_TABLE))?;
         Ok(table.get(username).map(|o| o.is_some())?)
     }
+
+    /// Returns the ignored users
+    pub fn ignored_users(&self) -> GuardResult<Vec<String>> {
+        let read_txn = self.begin_read()?;
+        let table = read_txn.open_table(self.table(IGNORED_USERS_TABLE))?;
+        Ok(table
+            .iter()?
+            .filter_map(|e| e.ok().map(|(u, _)| u.value().to_owned()))
+            .collect())
+    }
 }
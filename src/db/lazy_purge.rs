@@ -5,15 +5,24 @@
 
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use redb::{Database, ReadableTable, TableDefinition};
+use redb::{ReadableTable, TableDefinition};
 use reqwest::StatusCode;
 use tokio_util::sync::CancellationToken;
 
 use crate::{
     config::{BanAction, Config},
-    db::{AlertedUsersTableTrait, EventsTableTrait},
+    db::{
+        AlertedUsersTableTrait,
+        AuditAction,
+        AuditEntry,
+        AuditLogTableTrait,
+        EventsTableTrait,
+        GuardDb,
+    },
     error::{GuardError, GuardResult},
     forgejo_api,
+    metrics::{InstanceLabels, METRICS},
+    snapshots,
     utils,
 };
 
@@ -30,13 +39,13 @@ fn timestamp_now() -> u64 {
 }
 
 #[easy_ext::ext(PurgedUsersTableTrait)]
-impl Database {
+impl GuardDb {
     /// Add a new purged user to the database
     pub fn add_purged_user(&self, username: &str) -> GuardResult<()> {
         tracing::info!("Adding purged user: {username}");
         let write_txn = self.begin_write()?;
         {
-            let mut table = write_txn.open_table(PURGED_USERS_TABLE)?;
+            let mut table = write_txn.open_table(self.table(PURGED_USERS_TABLE))?;
             table.insert(username, timestamp_now())?;
         }
         write_txn.commit()?;
@@ -47,7 +56,7 @@ impl Database {
     pub fn remove_purged_user(&self, username: &str) -> GuardResult<()> {
         let write_txn = self.begin_write()?;
         {
-            let mut table = write_txn.open_table(PURGED_USERS_TABLE)?;
+            let mut table = write_txn.open_table(self.table(PURGED_USERS_TABLE))?;
             table.remove(username)?;
         }
         write_txn.commit()?;
@@ -57,10 +66,20 @@ impl Database {
     /// Returns `true` if the user is lazy purged
     pub fn is_lazy_purged(&self, username: &str) -> GuardResult<bool> {
         let read_txn = self.begin_read()?;
-        let table = read_txn.open_table(PURGED_USERS_TABLE)?;
+        let table = read_txn.open_table(self.table(PURGED_USERS_TABLE))?;
         Ok(table.get(username).map(|o| o.is_some())?)
     }
 
+    /// Returns the users in the purge queue, with the timestamp of adding them
+    pub fn purged_users(&self) -> GuardResult<Vec<(String, u64)>> {
+        let read_txn = self.begin_read()?;
+        let table = read_txn.open_table(self.table(PURGED_USERS_TABLE))?;
+        Ok(table
+            .iter()?
+            .filter_map(|e| e.ok().map(|(u, p)| (u.value().to_owned(), p.value())))
+            .collect())
+    }
+
     /// Purge existing users
     pub async fn purge_users(
         &self,
@@ -71,12 +90,12 @@ impl Database {
         let now = timestamp_now();
         let purge_after = u64::from(config.lazy_purge.purge_after);
 
-        let mut reqs = 0;
+        let mut reqs: u32 = 0;
         let mut total_purged = 0;
 
         let usernames: Vec<_> = {
             let read_txn = self.begin_read()?;
-            let table = read_txn.open_table(PURGED_USERS_TABLE)?;
+            let table = read_txn.open_table(self.table(PURGED_USERS_TABLE))?;
             table
                 .iter()?
                 .filter_map(|e| {
@@ -89,7 +108,7 @@ impl Database {
 
         tracing::info!("Starting lazy purge");
         for username in usernames {
-            if reqs > config.lazy_purge.req_limit || cancellation_token.is_cancelled() {
+            if reqs > u32::from(config.lazy_purge.req_limit) || cancellation_token.is_cancelled() {
                 if utils::wait_interval(config.lazy_purge.req_interval, &cancellation_token).await {
                     break;
                 }
@@ -98,26 +117,41 @@ impl Database {
 
             if config.dry_run {
                 tracing::info!("User @{username} has been lazy purged");
-            } else if let Err(err) = forgejo_api::ban_user(
-                client,
-                &config.forgejo.instance,
-                &config.forgejo.token,
-                &username,
-                &BanAction::Purge,
-            )
-            .await
-            {
-                if !matches!(err, GuardError::FailedToBan(StatusCode::NOT_FOUND)) {
-                    tracing::error!("Failed to lazy purge `@{username}`: {err}");
-                    continue;
+            } else {
+                reqs += snapshots::take_by_username(client, config, self, &username).await;
+                if let Err(err) = forgejo_api::ban_user(
+                    client,
+                    &config.forgejo.instance,
+                    &config.forgejo.token,
+                    &username,
+                    &BanAction::Purge,
+                    &mut reqs,
+                )
+                .await
+                {
+                    if !matches!(err, GuardError::FailedToBan(StatusCode::NOT_FOUND)) {
+                        tracing::error!("Failed to lazy purge `@{username}`: {err}");
+                        continue;
+                    }
                 }
             }
 
             self.remove_purged_user(&username).ok();
             self.remove_alerted_user(&username).ok();
             self.remove_user_events(&username).ok();
+            self.audit(AuditEntry::guardian(
+                config,
+                username,
+                None,
+                AuditAction::LazyPurged,
+                Some(BanAction::Purge),
+            ));
             total_purged += 1;
         }
+        METRICS
+            .users_lazy_purged
+            .get_or_create(&InstanceLabels::new(config))
+            .inc_by(total_purged);
         tracing::info!("Done lazy purge, purged {total_purged} users");
         Ok(())
     }
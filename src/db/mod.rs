@@ -1,21 +1,43 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
 
-use std::path::Path;
+use std::{collections::HashMap, ops::Deref, path::Path, sync::Arc};
 
 use redb::{Database, TableDefinition, TableHandle, WriteTransaction};
 
 use crate::error::GuardResult;
 
 mod alerted_users;
+mod audit_log;
+mod banned_users;
+mod cursors;
 mod events;
 mod ignored_users;
 mod lazy_purge;
+mod snapshots;
+mod telegram_messages;
 
 pub use alerted_users::*;
+pub use audit_log::*;
+pub use banned_users::*;
+pub use cursors::*;
 pub use events::*;
 pub use ignored_users::*;
 pub use lazy_purge::*;
+pub use snapshots::*;
+pub use telegram_messages::*;
+
+/// The guardian database of an instance. The tables of the instance are
+/// namespaced with the instance name, `<instance>:<table>`, so multiple
+/// instances can share the same database file. The tables of the unnamed
+/// instance (single instance) are not namespaced.
+pub struct GuardDb {
+    /// The database
+    db:     Arc<Database>,
+    /// The namespaced tables names, the key is the table name and the value is
+    /// the namespaced name
+    tables: HashMap<String, String>,
+}
 
 /// Open a table in a write transaction, creating it if it doesn't exist.
 fn open_table<K, V>(write_txn: &WriteTransaction, table: TableDefinition<K, V>) -> GuardResult<()>
@@ -29,22 +51,75 @@ where
 
 /// Initialize the database, creating it if it doesn't exist.
 pub fn init_db(db_path: &Path) -> GuardResult<Database> {
-    let db = Database::create(db_path).map_err(redb::Error::from)?;
-    let write_txn = db.begin_write()?;
-
-    open_table(&write_txn, ALERTED_USERS_TABLE)?;
-    open_table(&write_txn, EVENTS_TABLE)?;
-    open_table(&write_txn, IGNORED_USERS_TABLE)?;
-    open_table(&write_txn, PURGED_USERS_TABLE)?;
-
-    tracing::info!(
-        "Database tables: {:?}",
-        write_txn
-            .list_tables()?
-            .map(|t| t.name().to_owned())
-            .collect::<Vec<_>>()
-    );
-
-    write_txn.commit()?;
-    Ok(db)
+    Ok(Database::create(db_path).map_err(redb::Error::from)?)
+}
+
+impl GuardDb {
+    /// Create the instance database, creating its tables if they don't exist.
+    pub fn new(db: Arc<Database>, namespace: Option<&str>) -> GuardResult<Self> {
+        let tables = namespace
+            .map(|namespace| {
+                [
+                    ALERTED_USERS_TABLE.name(),
+                    ALERT_ACTIONS_TABLE.name(),
+                    ALERT_RULES_TABLE.name(),
+                    AUDIT_LOG_TABLE.name(),
+                    BANNED_USERS_TABLE.name(),
+                    CURSORS_TABLE.name(),
+                    EVENTS_TABLE.name(),
+                    IGNORED_USERS_TABLE.name(),
+                    PURGED_USERS_TABLE.name(),
+                    SNAPSHOTS_TABLE.name(),
+                    TELEGRAM_MESSAGES_TABLE.name(),
+                ]
+                .into_iter()
+                .map(|table| (table.to_owned(), format!("{namespace}:{table}")))
+                .collect()
+            })
+            .unwrap_or_default();
+        let guard_db = Self { db, tables };
+
+        let write_txn = guard_db.begin_write()?;
+        open_table(&write_txn, guard_db.table(ALERTED_USERS_TABLE))?;
+        open_table(&write_txn, guard_db.table(ALERT_ACTIONS_TABLE))?;
+        open_table(&write_txn, guard_db.table(ALERT_RULES_TABLE))?;
+        open_table(&write_txn, guard_db.table(AUDIT_LOG_TABLE))?;
+        open_table(&write_txn, guard_db.table(BANNED_USERS_TABLE))?;
+        open_table(&write_txn, guard_db.table(CURSORS_TABLE))?;
+        open_table(&write_txn, guard_db.table(EVENTS_TABLE))?;
+        open_table(&write_txn, guard_db.table(IGNORED_USERS_TABLE))?;
+        open_table(&write_txn, guard_db.table(PURGED_USERS_TABLE))?;
+        open_table(&write_txn, guard_db.table(SNAPSHOTS_TABLE))?;
+        open_table(&write_txn, guard_db.table(TELEGRAM_MESSAGES_TABLE))?;
+
+        tracing::info!(
+            "Database tables: {:?}",
+            write_txn
+                .list_tables()?
+                .map(|t| t.name().to_owned())
+                .collect::<Vec<_>>()
+        );
+
+        write_txn.commit()?;
+        Ok(guard_db)
+    }
+
+    /// Returns the table in the instance namespace
+    fn table<K, V>(&self, table: TableDefinition<'static, K, V>) -> TableDefinition<'_, K, V>
+    where
+        K: redb::Key + 'static,
+        V: redb::Value + 'static,
+    {
+        self.tables
+            .get(table.name())
+            .map_or(table, |name| TableDefinition::new(name))
+    }
+}
+
+impl Deref for GuardDb {
+    type Target = Database;
+
+    fn deref(&self) -> &Self::Target {
+        &self.db
+    }
 }
@@ -0,0 +1,47 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
+
+use redb::{ReadableTableMetadata, TableDefinition};
+
+use crate::{db::GuardDb, error::GuardResult};
+
+/// A table containing the users profiles snapshots, with the username as the
+/// key and the snapshot timestamp and JSON as the value. Only used if the
+/// snapshots directory is not set.
+pub(super) const SNAPSHOTS_TABLE: TableDefinition<&str, (u64, &str)> =
+    TableDefinition::new("snapshots");
+
+#[easy_ext::ext(SnapshotsTableTrait)]
+impl GuardDb {
+    /// Add the user snapshot, replacing the old one if any
+    pub fn add_snapshot(&self, username: &str, taken_at: u64, snapshot: &str) -> GuardResult<()> {
+        let write_txn = self.begin_write()?;
+        {
+            let mut table = write_txn.open_table(self.table(SNAPSHOTS_TABLE))?;
+            table.insert(username, (taken_at, snapshot))?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    /// Returns the user snapshot, if any
+    pub fn snapshot(&self, username: &str) -> GuardResult<Option<String>> {
+        let read_txn = self.begin_read()?;
+        let table = read_txn.open_table(self.table(SNAPSHOTS_TABLE))?;
+        Ok(table.get(username)?.map(|g| g.value().1.to_owned()))
+    }
+
+    /// Remove the snapshots taken before the timestamp, returns the number of
+    /// the removed snapshots
+    pub fn remove_snapshots_before(&self, timestamp: u64) -> GuardResult<usize> {
+        let write_txn = self.begin_write()?;
+        let removed = {
+            let mut table = write_txn.open_table(self.table(SNAPSHOTS_TABLE))?;
+            let before = table.len()?;
+            table.retain(|_, (taken_at, _)| taken_at >= timestamp)?;
+            before - table.len()?
+        };
+        write_txn.commit()?;
+        Ok(removed as usize)
+    }
+}
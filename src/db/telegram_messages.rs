@@ -0,0 +1,46 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
+
+use redb::{ReadableTable, TableDefinition};
+
+use crate::{db::GuardDb, error::GuardResult};
+
+/// Telegram messages table, stores the alerts messages of the users to reflect
+/// the Matrix decisions on them, the key is the username and the value is the
+/// messages ids.
+pub(super) const TELEGRAM_MESSAGES_TABLE: TableDefinition<&str, Vec<i32>> =
+    TableDefinition::new("telegram_messages");
+
+#[easy_ext::ext(TelegramMessagesTableTrait)]
+impl GuardDb {
+    /// Add a new user alert message to the database
+    pub fn add_telegram_message(&self, username: &str, message_id: i32) -> GuardResult<()> {
+        let write_txn = self.begin_write()?;
+        {
+            let mut table = write_txn.open_table(self.table(TELEGRAM_MESSAGES_TABLE))?;
+            let mut messages = table.get(username)?.map(|g| g.value()).unwrap_or_default();
+            messages.push(message_id);
+            table.insert(username, messages)?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    /// Returns the user alerts messages
+    pub fn telegram_messages(&self, username: &str) -> GuardResult<Vec<i32>> {
+        let read_txn = self.begin_read()?;
+        let table = read_txn.open_table(self.table(TELEGRAM_MESSAGES_TABLE))?;
+        Ok(table.get(username)?.map(|g| g.value()).unwrap_or_default())
+    }
+
+    /// Remove the user alerts messages
+    pub fn remove_telegram_messages(&self, username: &str) -> GuardResult<()> {
+        let write_txn = self.begin_write()?;
+        {
+            let mut table = write_txn.open_table(self.table(TELEGRAM_MESSAGES_TABLE))?;
+            table.remove(username)?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,481 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
+
+//! Email domains checker, checks the user email domain and its MX hosts against
+//! a blocklist of disposable or abusive domains.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    fs,
+    future::Future,
+    path::Path,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant, SystemTime},
+};
+
+use regex::Regex;
+use reqwest::Method;
+use serde::Deserialize;
+use tokio_util::sync::CancellationToken;
+use url::Url;
+
+use crate::{
+    config::{locations::Locations, ConfigReceiver, EmailDomains, RegexReason},
+    error::{GuardError, GuardResult},
+};
+
+/// DNS record type of the MX records
+const MX_RECORD_TYPE: u16 = 15;
+/// How long the resolved MX hosts are cached, the domains can change their MX
+/// records
+const MX_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+/// Maximum number of the cached domains, the oldest resolved domain is evicted
+/// when the cache is full
+const MX_CACHE_CAPACITY: usize = 10_000;
+
+/// Resolver of the domains MX hosts
+pub trait MxResolver {
+    /// Returns the MX hosts of the domain, without the trailing dot
+    fn mx_hosts(&self, domain: &str) -> impl Future<Output = GuardResult<Vec<String>>> + Send;
+}
+
+/// DNS over HTTPS resolver, uses the JSON API of the resolver.
+///
+/// ref: https://developers.cloudflare.com/1.1.1.1/encryption/dns-over-https/make-api-requests/dns-json/
+pub struct DohResolver<'a> {
+    client: &'a reqwest::Client,
+    url:    &'a Url,
+}
+
+/// The DNS over HTTPS JSON response
+#[derive(Deserialize)]
+struct DohResponse {
+    #[serde(rename = "Answer", default)]
+    answer: Vec<DohAnswer>,
+}
+
+/// A DNS over HTTPS JSON answer
+#[derive(Deserialize)]
+struct DohAnswer {
+    #[serde(rename = "type")]
+    record_type: u16,
+    data:        String,
+}
+
+/// The blocklist of the email domains, it will be reloaded if the file got
+/// modified
+#[derive(Default)]
+pub struct DomainList {
+    /// The blocked domains, in lowercase
+    domains:  RwLock<HashSet<String>>,
+    /// Last modification time of the blocklist file
+    modified: Mutex<Option<SystemTime>>,
+    /// The resolved MX hosts of the domains with their resolve time, cleared
+    /// when the blocklist is reloaded
+    mx_cache: Mutex<HashMap<String, (Instant, Vec<String>)>>,
+}
+
+impl<'a> DohResolver<'a> {
+    /// Create a new DNS over HTTPS resolver
+    pub fn new(client: &'a reqwest::Client, url: &'a Url) -> Self {
+        Self { client, url }
+    }
+}
+
+impl MxResolver for DohResolver<'_> {
+    async fn mx_hosts(&self, domain: &str) -> GuardResult<Vec<String>> {
+        let mut url = self.url.clone();
+        url.query_pairs_mut()
+            .append_pair("name", domain)
+            .append_pair("type", "MX");
+
+        let res = self
+            .client
+            .request(Method::GET, url.clone())
+            .header("accept", "application/dns-json")
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+            return Err(GuardError::Other(format!(
+                "Failed to resolve `{domain}` MX hosts, status code: {status}",
+                status = res.status()
+            )));
+        }
+
+        let response: DohResponse = serde_json::from_str(&res.text().await?)
+            .map_err(|err| GuardError::Other(format!("Invalid DNS response from {url}: {err}")))?;
+
+        Ok(response
+            .answer
+            .into_iter()
+            .filter(|answer| answer.record_type == MX_RECORD_TYPE)
+            .filter_map(|answer| {
+                // The data is the preference and the host, e.g. `10 mx.example.com.`
+                answer
+                    .data
+                    .split_whitespace()
+                    .last()
+                    .map(|host| host.trim_end_matches('.').to_lowercase())
+            })
+            .filter(|host| !host.is_empty())
+            .collect())
+    }
+}
+
+impl DomainList {
+    /// Load the blocklist file, the file contains a domain per line, the empty
+    /// lines and the lines that start with `#` are ignored
+    pub fn load(&self, path: &Path) -> GuardResult<()> {
+        let modified = fs::metadata(path)?.modified().ok();
+        let domains: HashSet<_> = fs::read_to_string(path)?
+            .lines()
+            .map(|line| line.trim().trim_start_matches("*.").to_lowercase())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .collect();
+
+        tracing::info!(
+            "Loaded {} email domains from `{}`",
+            domains.len(),
+            path.display()
+        );
+        *self.domains.write().expect("Not poisoned") = domains;
+        *self.modified.lock().expect("Not poisoned") = modified;
+        self.mx_cache.lock().expect("Not poisoned").clear();
+        Ok(())
+    }
+
+    /// Reload the blocklist file if it got modified since the last load
+    pub fn reload_if_modified(&self, path: &Path) -> GuardResult<()> {
+        let modified = fs::metadata(path)?.modified().ok();
+        if modified.is_none() || *self.modified.lock().expect("Not poisoned") != modified {
+            self.load(path)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the blocked domain of the given domain, the domain is blocked if
+    /// it or one of its parents is in the blocklist
+    pub fn blocked(&self, domain: &str) -> Option<String> {
+        let domains = self.domains.read().expect("Not poisoned");
+        let domain = domain.trim_end_matches('.').to_lowercase();
+        let mut parent = domain.as_str();
+
+        loop {
+            if domains.contains(parent) {
+                return Some(parent.to_owned());
+            }
+            parent = parent.split_once('.')?.1;
+        }
+    }
+
+    /// Returns the MX hosts of the domain, from the cache if it's resolved
+    /// within the cache TTL
+    async fn mx_hosts(&self, resolver: &impl MxResolver, domain: &str) -> GuardResult<Vec<String>> {
+        if let Some((resolved_at, hosts)) = self.mx_cache.lock().expect("Not poisoned").get(domain)
+        {
+            if resolved_at.elapsed() < MX_CACHE_TTL {
+                return Ok(hosts.clone());
+            }
+        }
+
+        let hosts = resolver.mx_hosts(domain).await?;
+        let mut cache = self.mx_cache.lock().expect("Not poisoned");
+        if cache.len() >= MX_CACHE_CAPACITY && !cache.contains_key(domain) {
+            cache.retain(|_, (resolved_at, _)| resolved_at.elapsed() < MX_CACHE_TTL);
+            if cache.len() >= MX_CACHE_CAPACITY {
+                let oldest = cache
+                    .iter()
+                    .min_by_key(|(_, (resolved_at, _))| *resolved_at)
+                    .map(|(domain, _)| domain.clone());
+                if let Some(oldest) = oldest {
+                    cache.remove(&oldest);
+                }
+            }
+        }
+        cache.insert(domain.to_owned(), (Instant::now(), hosts.clone()));
+        Ok(hosts)
+    }
+}
+
+impl fmt::Debug for DomainList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DomainList")
+            .field("domains", &self.domains.read().map(|d| d.len()).ok())
+            .finish()
+    }
+}
+
+/// Returns the matched blocked domain as a `RegexReason`
+fn domain_reason(
+    email_domains: &EmailDomains,
+    blocked: &str,
+    matched: String,
+    location: Locations,
+) -> RegexReason {
+    RegexReason::new(
+        vec![Regex::new(&regex::escape(blocked)).expect("Escaped regex is valid")],
+        email_domains.reason.clone(),
+    )
+    .location(location)
+    .matched(vec![matched])
+}
+
+/// Check the email domain and its MX hosts (if `check_mx` is enabled) against
+/// the blocklist, returns the matched domain
+pub async fn check_email(
+    email_domains: &EmailDomains,
+    resolver: &impl MxResolver,
+    email: &str,
+) -> Option<RegexReason> {
+    if !email_domains.enabled {
+        return None;
+    }
+    let (_, domain) = email.rsplit_once('@')?;
+    let domain = domain.to_lowercase();
+
+    if let Some(blocked) = email_domains.list.blocked(&domain) {
+        return Some(domain_reason(
+            email_domains,
+            &blocked,
+            domain,
+            Locations::EmailDomain,
+        ));
+    }
+
+    if !email_domains.check_mx {
+        return None;
+    }
+
+    match email_domains.list.mx_hosts(resolver, &domain).await {
+        Ok(hosts) => {
+            hosts.into_iter().find_map(|host| {
+                email_domains
+                    .list
+                    .blocked(&host)
+                    .map(|blocked| domain_reason(email_domains, &blocked, host, Locations::EmailMx))
+            })
+        }
+        Err(err) => {
+            tracing::error!("Failed to resolve the MX hosts of `{domain}`: {err}");
+            None
+        }
+    }
+}
+
+/// Reload the email domains blocklist every `reload_interval`, if it got
+/// modified
+pub async fn reloader(mut config_receiver: ConfigReceiver, cancellation_token: CancellationToken) {
+    tracing::info!("Starting email domains blocklist reloader");
+
+    loop {
+        // The blocklist of the latest reloaded config
+        let config = Arc::clone(&config_receiver.borrow_and_update());
+        let Some(email_domains) = config
+            .expressions
+            .email_domains
+            .as_ref()
+            .filter(|e| e.enabled)
+        else {
+            // The blocklist has been disabled by the reload, wait for the next reload
+            tokio::select! {
+                result = config_receiver.changed() => {
+                    if result.is_ok() {
+                        continue;
+                    }
+                }
+                _ = cancellation_token.cancelled() => {}
+            };
+            tracing::info!("Email domains blocklist reloader has been stopped successfully.");
+            break;
+        };
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(email_domains.reload_interval.into())) => {
+                if let Err(err) = email_domains.list.reload_if_modified(&email_domains.blocklist) {
+                    tracing::error!(
+                        "Failed to reload the email domains blocklist `{}`: {err}",
+                        email_domains.blocklist.display()
+                    );
+                }
+            }
+            _ = cancellation_token.cancelled() => {
+                tracing::info!("Email domains blocklist reloader has been stopped successfully.");
+                break
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// A stub MX resolver, resolves the domains from a static map and counts
+    /// the resolve calls
+    #[derive(Default)]
+    struct StubResolver {
+        hosts: HashMap<&'static str, Vec<&'static str>>,
+        calls: AtomicUsize,
+    }
+
+    impl StubResolver {
+        fn new(hosts: &[(&'static str, &[&'static str])]) -> Self {
+            Self {
+                hosts: hosts
+                    .iter()
+                    .map(|(domain, hosts)| (*domain, hosts.to_vec()))
+                    .collect(),
+                ..Default::default()
+            }
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+    }
+
+    impl MxResolver for StubResolver {
+        fn mx_hosts(&self, domain: &str) -> impl Future<Output = GuardResult<Vec<String>>> + Send {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let hosts = self
+                .hosts
+                .get(domain)
+                .map(|hosts| hosts.iter().map(|host| host.to_string()).collect())
+                .ok_or_else(|| GuardError::Other(format!("`{domain}` has no MX records")));
+            std::future::ready(hosts)
+        }
+    }
+
+    fn blocklist(check_mx: bool, blocked: &[&str]) -> EmailDomains {
+        let email_domains: EmailDomains = toml::from_str(&format!(
+            "blocklist = \"blocklist.txt\"\ncheck_mx = {check_mx}"
+        ))
+        .unwrap();
+        *email_domains.list.domains.write().unwrap() =
+            blocked.iter().map(|domain| domain.to_string()).collect();
+        email_domains
+    }
+
+    #[test]
+    fn blocked_parent_domain() {
+        let email_domains = blocklist(false, &["spam.example"]);
+        let list = &email_domains.list;
+
+        assert_eq!(
+            list.blocked("spam.example").as_deref(),
+            Some("spam.example")
+        );
+        assert_eq!(
+            list.blocked("Mail.Spam.Example.").as_deref(),
+            Some("spam.example")
+        );
+        assert_eq!(list.blocked("notspam.example"), None);
+        assert_eq!(list.blocked("example"), None);
+    }
+
+    #[tokio::test]
+    async fn blocked_email_domain() {
+        let email_domains = blocklist(true, &["spam.example"]);
+        let resolver = StubResolver::default();
+
+        let reason = check_email(&email_domains, &resolver, "user@Spam.Example")
+            .await
+            .unwrap();
+        assert!(matches!(reason.location, Locations::EmailDomain));
+        assert_eq!(reason.matched, ["spam.example"]);
+        // The domain itself is blocked, its MX hosts are not resolved
+        assert_eq!(resolver.calls(), 0);
+    }
+
+    #[tokio::test]
+    async fn blocked_mx_host() {
+        let email_domains = blocklist(true, &["spam-mx.example"]);
+        let resolver = StubResolver::new(&[
+            ("clean.example", &["mx1.clean.example"]),
+            ("spam.example", &["mx1.clean.example", "mx.spam-mx.example"]),
+        ]);
+
+        assert!(check_email(&email_domains, &resolver, "user@clean.example")
+            .await
+            .is_none());
+        let reason = check_email(&email_domains, &resolver, "user@spam.example")
+            .await
+            .unwrap();
+        assert!(matches!(reason.location, Locations::EmailMx));
+        assert_eq!(reason.matched, ["mx.spam-mx.example"]);
+    }
+
+    #[tokio::test]
+    async fn mx_disabled_or_unresolved() {
+        let resolver = StubResolver::new(&[("spam.example", &["mx.spam-mx.example"])]);
+
+        let email_domains = blocklist(false, &["spam-mx.example"]);
+        assert!(check_email(&email_domains, &resolver, "user@spam.example")
+            .await
+            .is_none());
+        assert_eq!(resolver.calls(), 0);
+
+        let email_domains = blocklist(true, &["spam-mx.example"]);
+        assert!(
+            check_email(&email_domains, &resolver, "user@unknown.example")
+                .await
+                .is_none()
+        );
+        assert!(check_email(&email_domains, &resolver, "invalid-email")
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn mx_hosts_cache() {
+        let email_domains = blocklist(true, &[]);
+        let list = &email_domains.list;
+        let resolver = StubResolver::new(&[("clean.example", &["mx.clean.example"])]);
+
+        for _ in 0..3 {
+            assert_eq!(
+                list.mx_hosts(&resolver, "clean.example").await.unwrap(),
+                ["mx.clean.example"]
+            );
+        }
+        assert_eq!(resolver.calls(), 1);
+
+        // The expired hosts are resolved again
+        let expired = Instant::now()
+            .checked_sub(MX_CACHE_TTL + Duration::from_secs(1))
+            .unwrap();
+        list.mx_cache
+            .lock()
+            .unwrap()
+            .get_mut("clean.example")
+            .unwrap()
+            .0 = expired;
+        list.mx_hosts(&resolver, "clean.example").await.unwrap();
+        assert_eq!(resolver.calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn mx_hosts_cache_capacity() {
+        let email_domains = blocklist(true, &[]);
+        let list = &email_domains.list;
+        let resolver = StubResolver::new(&[("clean.example", &["mx.clean.example"])]);
+
+        let now = Instant::now();
+        *list.mx_cache.lock().unwrap() = (0..MX_CACHE_CAPACITY)
+            .map(|idx| {
+                let resolved_at = now.checked_sub(Duration::from_millis(idx as u64)).unwrap();
+                (format!("{idx}.example"), (resolved_at, Vec::new()))
+            })
+            .collect();
+
+        list.mx_hosts(&resolver, "clean.example").await.unwrap();
+        let cache = list.mx_cache.lock().unwrap();
+        assert_eq!(cache.len(), MX_CACHE_CAPACITY);
+        assert!(cache.contains_key("clean.example"));
+        // The oldest resolved domain is evicted
+        assert!(!cache.contains_key(&format!("{}.example", MX_CACHE_CAPACITY - 1)));
+    }
+}
@@ -36,8 +36,14 @@ pub enum GuardError {
     /// Failed to ban the user
     #[error("Failed to ban the user, status code: {0}")]
     FailedToBan(StatusCode),
+    /// Failed to unsuspend the user
+    #[error("Failed to unsuspend the user, status code: {0}")]
+    FailedToUnsuspend(StatusCode),
     #[error("Matrix Error: {0}")]
     Matrix(String),
+    /// SMTP transport error
+    #[error("SMTP error: {0}")]
+    Smtp(#[from] lettre::transport::smtp::Error),
     /// Other errors, for custom errors
     #[error("{0}")]
     Other(String),
@@ -0,0 +1,438 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
+
+//! Remote rule feeds, signed rules files with `ban` and `sus` tables, in the
+//! same format of the `expressions` tables.
+//!
+//! Each instance fetches its own feeds every interval and merges them into its
+//! local expressions.
+//! A feed that can't be fetched, verified or parsed is ignored and its last
+//! good rules are kept, the local rules are never replaced by a feed.
+
+use std::{
+    fmt,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use reqwest::{
+    header::{ETAG, IF_NONE_MATCH},
+    StatusCode,
+};
+use serde::Deserialize;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    config::{ConfigReceiver, Expr, Exprs, Feed, RegexReason},
+    error::{GuardError, GuardResult},
+    forgejo_api::ForgejoUser,
+    traits::ExprChecker,
+    utils,
+};
+
+/// Maximum size of the feed rules file, in bytes
+const MAX_FEED_SIZE: usize = 1024 * 1024;
+
+/// The rules of a feed
+#[derive(Deserialize, Debug)]
+pub struct Rules {
+    /// The ban expressions of the feed, merged into the `ban` expressions
+    #[serde(default)]
+    pub ban: Expr,
+    /// The sus expressions of the feed, merged into the `sus` expressions
+    #[serde(default)]
+    pub sus: Expr,
+}
+
+/// The last fetched rules of a feed, with its ETag to skip the unchanged
+/// feeds. It's kept across the config reloads if the feed didn't change
+#[derive(Default)]
+pub struct FeedRules {
+    /// The last good rules of the feed
+    rules:      RwLock<Option<Arc<Rules>>>,
+    /// The ETag of the last good rules
+    etag:       Mutex<Option<String>>,
+    /// The time of the last fetch attempt
+    fetched_at: Mutex<Option<Instant>>,
+}
+
+impl FeedRules {
+    /// Returns the last good rules of the feed, if any
+    pub fn get(&self) -> Option<Arc<Rules>> {
+        self.rules.read().expect("Not poisoned").clone()
+    }
+
+    /// Returns the remaining time to fetch the feed again
+    fn remaining(&self, interval: u32) -> Duration {
+        self.fetched_at
+            .lock()
+            .expect("Not poisoned")
+            .map(|fetched_at| {
+                Duration::from_secs(interval.into()).saturating_sub(fetched_at.elapsed())
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl fmt::Debug for FeedRules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FeedRules")
+            .field("loaded", &self.get().is_some())
+            .field("etag", &self.etag.lock().map(|e| e.clone()).ok())
+            .finish()
+    }
+}
+
+impl fmt::Debug for Feed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Feed")
+            .field("url", &self.url.as_str())
+            .field("interval", &self.interval)
+            .field("rules", &self.rules)
+            .finish()
+    }
+}
+
+impl Exprs {
+    /// Returns the last good rules of the feeds
+    pub fn feeds_rules(&self) -> Vec<Arc<Rules>> {
+        self.feeds
+            .iter()
+            .filter_map(|feed| feed.rules.get())
+            .collect()
+    }
+
+    /// Returns the matched `ban` expressions, the local expressions then the
+    /// feeds expressions. The feeds are ignored if the `ban` expressions are
+    /// disabled
+    pub fn ban_matches(&self, user: &ForgejoUser) -> Vec<RegexReason> {
+        let mut matches = self.ban.matches(user);
+        if self.ban.enabled {
+            for rules in self.feeds_rules() {
+                matches.extend(rules.ban.matches(user));
+            }
+        }
+        matches
+    }
+
+    /// Returns the matched `sus` expressions, the same as [`Self::ban_matches`]
+    pub fn sus_matches(&self, user: &ForgejoUser) -> Vec<RegexReason> {
+        let mut matches = self.sus.matches(user);
+        if self.sus.enabled {
+            for rules in self.feeds_rules() {
+                matches.extend(rules.sus.matches(user));
+            }
+        }
+        matches
+    }
+}
+
+/// The keys that can't be used in the feed rules. A remote feed can't read the
+/// local files, by `include` or `file`, and can't choose the ban `action`, its
+/// matched users are banned with the local `ban_action`
+const FORBIDDEN_KEYS: [&str; 3] = ["include", "file", "action"];
+
+/// Returns the first forbidden key used in the rules, if any
+fn forbidden_key(value: &toml::Value) -> Option<&'static str> {
+    match value {
+        toml::Value::Table(table) => {
+            FORBIDDEN_KEYS
+                .into_iter()
+                .find(|key| table.contains_key(*key))
+                .or_else(|| table.values().find_map(forbidden_key))
+        }
+        toml::Value::Array(array) => array.iter().find_map(forbidden_key),
+        _ => None,
+    }
+}
+
+/// Parse the feed rules file
+fn parse_rules(body: &[u8]) -> GuardResult<Rules> {
+    let content = std::str::from_utf8(body)
+        .map_err(|err| GuardError::Other(format!("The rules file isn't a valid UTF-8: {err}")))?;
+    let value: toml::Value = toml::from_str(content)?;
+    if let Some(key) = forbidden_key(&value) {
+        return Err(GuardError::Other(format!(
+            "The rules file can't use the `{key}` key"
+        )));
+    }
+
+    let rules: Rules = value
+        .try_into()
+        .map_err(|err| GuardError::Other(format!("Invalid rules file: {err}")))?;
+    if rules
+        .ban
+        .regexes()
+        .chain(rules.sus.regexes())
+        .any(|re| re.weight.is_some())
+    {
+        return Err(GuardError::Other(
+            "The rules file can't use the `weight` key, the feeds have no score expressions"
+                .to_owned(),
+        ));
+    }
+    Ok(rules)
+}
+
+/// Append the chunk to the rules file body, fails if the body exceeds the
+/// maximum feed size
+fn push_chunk(body: &mut Vec<u8>, chunk: &[u8]) -> GuardResult<()> {
+    if body.len() + chunk.len() > MAX_FEED_SIZE {
+        return Err(GuardError::Other(format!(
+            "The rules file is larger than {MAX_FEED_SIZE} bytes"
+        )));
+    }
+    body.extend_from_slice(chunk);
+    Ok(())
+}
+
+/// Fetch the feed rules file, returns `None` if it isn't modified since the
+/// last fetch
+async fn fetch_body(
+    client: &reqwest::Client,
+    feed: &Feed,
+) -> GuardResult<Option<(Vec<u8>, Option<String>)>> {
+    let mut req = client.get(feed.url.clone());
+    if let Some(etag) = feed.rules.etag.lock().expect("Not poisoned").clone() {
+        req = req.header(IF_NONE_MATCH, etag);
+    }
+
+    let mut res = req.send().await?;
+    if res.status() == StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+    if !res.status().is_success() {
+        return Err(GuardError::Other(format!(
+            "Failed to fetch the rules file, status code: {}",
+            res.status()
+        )));
+    }
+
+    let etag = res
+        .headers()
+        .get(ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .map(String::from);
+    let mut body = Vec::new();
+    while let Some(chunk) = res.chunk().await? {
+        push_chunk(&mut body, &chunk)?;
+    }
+    Ok(Some((body, etag)))
+}
+
+/// Fetch the feed signature, the hex encoded HMAC-SHA256 of the rules file
+async fn fetch_signature(client: &reqwest::Client, feed: &Feed) -> GuardResult<String> {
+    let url = match feed.signature_url {
+        Some(ref url) => url.clone(),
+        None => {
+            let mut url = feed.url.clone();
+            url.set_path(&format!("{}.sig", feed.url.path()));
+            url
+        }
+    };
+
+    let res = client.get(url).send().await?;
+    if !res.status().is_success() {
+        return Err(GuardError::Other(format!(
+            "Failed to fetch the signature, status code: {}",
+            res.status()
+        )));
+    }
+    Ok(res.text().await?)
+}
+
+/// Fetch the feed signature then its rules file, returns `None` if the rules
+/// file isn't modified since the last fetch. They are fetched again once if
+/// they don't match, the feed may be updated between the two requests
+async fn fetch_signed_body(
+    client: &reqwest::Client,
+    feed: &Feed,
+) -> GuardResult<Option<(Vec<u8>, Option<String>)>> {
+    for retry in [false, true] {
+        let signature = fetch_signature(client, feed).await?;
+        let Some((body, etag)) = fetch_body(client, feed).await? else {
+            return Ok(None);
+        };
+        if utils::is_valid_signature(&feed.secret, &signature, &body) {
+            return Ok(Some((body, etag)));
+        }
+        if !retry {
+            tracing::warn!(
+                "The feed `{}` signature doesn't match, fetching it again",
+                feed.url
+            );
+        }
+    }
+    Err(GuardError::Other(
+        "The rules file signature is invalid".to_owned(),
+    ))
+}
+
+/// Fetch the feed and swap its rules, if it's modified and valid
+async fn fetch_feed(client: &reqwest::Client, feed: &Feed) -> GuardResult<()> {
+    *feed.rules.fetched_at.lock().expect("Not poisoned") = Some(Instant::now());
+
+    let Some((body, etag)) = fetch_signed_body(client, feed).await? else {
+        tracing::debug!("The feed `{}` isn't modified", feed.url);
+        return Ok(());
+    };
+    let rules = parse_rules(&body)?;
+
+    tracing::info!(
+        "Loaded {} ban and {} sus regexes from the feed `{}`",
+        rules.ban.regexes_count(),
+        rules.sus.regexes_count(),
+        feed.url
+    );
+    *feed.rules.rules.write().expect("Not poisoned") = Some(Arc::new(rules));
+    *feed.rules.etag.lock().expect("Not poisoned") = etag;
+    Ok(())
+}
+
+/// Fetch all the feeds once, the failed feeds are logged and skipped
+pub async fn fetch_all(client: &reqwest::Client, feeds: &[Feed]) {
+    for feed in feeds {
+        if let Err(err) = fetch_feed(client, feed).await {
+            tracing::error!("Failed to fetch the feed `{}`: {err}", feed.url);
+        }
+    }
+}
+
+/// The feeds fetcher, fetches every feed when its interval passes
+pub async fn fetcher(mut config_receiver: ConfigReceiver, cancellation_token: CancellationToken) {
+    tracing::info!("Starting rule feeds fetcher");
+    let client = reqwest::Client::new();
+
+    loop {
+        // The feeds of the latest reloaded config
+        let config = Arc::clone(&config_receiver.borrow_and_update());
+        for feed in &config.expressions.feeds {
+            if feed.rules.remaining(feed.interval).is_zero() {
+                if let Err(err) = fetch_feed(&client, feed).await {
+                    tracing::error!(
+                        "Failed to fetch the feed `{}`, keeping its last rules: {err}",
+                        feed.url
+                    );
+                }
+            }
+        }
+
+        let Some(next_fetch) = config
+            .expressions
+            .feeds
+            .iter()
+            .map(|feed| feed.rules.remaining(feed.interval))
+            .min()
+        else {
+            // The feeds have been removed by the reload, wait for the next reload
+            tokio::select! {
+                result = config_receiver.changed() => {
+                    if result.is_ok() {
+                        continue;
+                    }
+                }
+                _ = cancellation_token.cancelled() => {}
+            };
+            tracing::info!("Rule feeds fetcher has been stopped successfully.");
+            break;
+        };
+        tokio::select! {
+            _ = tokio::time::sleep(next_fetch) => {}
+            result = config_receiver.changed() => {
+                if result.is_err() {
+                    tracing::info!("Rule feeds fetcher has been stopped successfully.");
+                    break
+                }
+            }
+            _ = cancellation_token.cancelled() => {
+                tracing::info!("Rule feeds fetcher has been stopped successfully.");
+                break
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+
+    use super::*;
+
+    fn sign(secret: &str, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(body);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    fn rejected_key(rules: &str) -> String {
+        parse_rules(rules.as_bytes()).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn valid_rules() {
+        let rules = parse_rules(
+            br#"
+            [ban]
+            usernames = ["^spam", { re = "^bot", reason = "Bot username" }]
+
+            [sus]
+            biographies = ["followers"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(rules.ban.regexes_count(), 2);
+        assert_eq!(rules.sus.regexes_count(), 1);
+    }
+
+    #[test]
+    fn rejected_keys() {
+        assert!(rejected_key("[ban]\ninclude = [\"rules/*.toml\"]").contains("`include`"));
+        assert!(rejected_key("[sus]\nusernames = [{ file = \"/etc/passwd\" }]").contains("`file`"));
+        assert!(
+            rejected_key("[ban]\nusernames = [{ re = \"^spam\", action = \"purge\" }]")
+                .contains("`action`")
+        );
+    }
+
+    #[test]
+    fn invalid_rules() {
+        assert!(parse_rules(b"[ban\n").is_err());
+        assert!(parse_rules(b"[ban]\nusernames = [\"(\"]").is_err());
+        assert!(parse_rules(&[0xff, 0xfe]).is_err());
+    }
+
+    #[test]
+    fn rules_signature() {
+        let body = b"[ban]\nusernames = [\"^spam\"]\n";
+        let signature = sign("s3cret", body);
+
+        assert!(utils::is_valid_signature("s3cret", &signature, body));
+        // The signature file may end with a newline
+        assert!(utils::is_valid_signature(
+            "s3cret",
+            &format!("{signature}\n"),
+            body
+        ));
+        assert!(!utils::is_valid_signature("other", &signature, body));
+        assert!(!utils::is_valid_signature(
+            "s3cret",
+            &signature,
+            b"[ban]\nusernames = [\".\"]\n"
+        ));
+        assert!(!utils::is_valid_signature("s3cret", "not hex", body));
+    }
+
+    #[test]
+    fn oversize_body() {
+        let mut body = Vec::new();
+        push_chunk(&mut body, &vec![b'#'; MAX_FEED_SIZE - 1]).unwrap();
+        push_chunk(&mut body, b"\n").unwrap();
+        assert_eq!(body.len(), MAX_FEED_SIZE);
+
+        assert!(push_chunk(&mut body, b"\n").is_err());
+        // The rejected chunk isn't appended
+        assert_eq!(body.len(), MAX_FEED_SIZE);
+    }
+}
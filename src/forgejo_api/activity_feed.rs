@@ -2,6 +2,7 @@
 // Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
 
 use reqwest::{Client, Method};
+use serde::{Deserialize, Serialize};
 use url::Url;
 
 use crate::{
@@ -9,6 +10,75 @@ use crate::{
     forgejo_api,
 };
 
+/// The comment of an activity
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ActivityComment {
+    /// The comment body
+    pub body: String,
+}
+
+/// The repository of an activity
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ActivityRepo {
+    /// The repository full name, `owner/name`
+    pub full_name: String,
+}
+
+/// User activity
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Activity {
+    /// The operation type, e.g. `create_issue`, `comment_issue`
+    pub op_type:    String,
+    /// The activity content, for issues and comments it's the issue index and
+    /// the title or the comment, separated by `|`
+    #[serde(default)]
+    pub content:    String,
+    /// The activity comment, if it's a comment activity
+    #[serde(default)]
+    pub comment:    Option<ActivityComment>,
+    /// The comment ID, if it's a comment activity
+    #[serde(default)]
+    pub comment_id: u64,
+    /// The activity repository
+    #[serde(default)]
+    pub repo:       Option<ActivityRepo>,
+}
+
+impl Activity {
+    /// Returns the issue or pull request title, if the activity is creating
+    /// one
+    pub fn issue_title(&self) -> Option<&str> {
+        matches!(
+            self.op_type.as_str(),
+            "create_issue" | "create_pull_request"
+        )
+        .then(|| self.content_text())
+    }
+
+    /// Returns the comment body, if the activity is a comment
+    pub fn comment_body(&self) -> Option<&str> {
+        matches!(self.op_type.as_str(), "comment_issue" | "comment_pull").then(|| {
+            self.comment
+                .as_ref()
+                .map_or_else(|| self.content_text(), |c| c.body.as_str())
+        })
+    }
+
+    /// Returns the issue or pull request index, if the activity is creating
+    /// one
+    pub fn issue_index(&self) -> Option<u64> {
+        self.issue_title()?;
+        self.content.split_once('|')?.0.parse().ok()
+    }
+
+    /// Returns the content without the issue index
+    fn content_text(&self) -> &str {
+        self.content
+            .split_once('|')
+            .map_or(self.content.as_str(), |(_, text)| text)
+    }
+}
+
 /// Returns whether the feeds is empty.
 pub async fn is_empty_feeds(
     client: &Client,
@@ -23,7 +93,8 @@ pub async fn is_empty_feeds(
         &format!("/api/v1/users/{username}/activities/feeds"),
     );
     let url = req.url().clone();
-    let res = client.execute(req).await?;
+    let res =
+        forgejo_api::execute(client, "GET /api/v1/users/{username}/activities/feeds", req).await?;
 
     if !res.status().is_success() {
         return Err(GuardError::InvalidForgejoResponse(
@@ -36,3 +107,39 @@ pub async fn is_empty_feeds(
 
     Ok(res.text().await.unwrap_or_default().trim() == "[]")
 }
+
+/// Returns a page of the activities performed by the user, latest first and
+/// `limit` activities per page
+pub async fn get_user_activities(
+    client: &Client,
+    instance: &Url,
+    token: &str,
+    username: &str,
+    limit: u32,
+    page: u32,
+) -> GuardResult<Vec<Activity>> {
+    let req = forgejo_api::build_request(
+        Method::GET,
+        instance,
+        token,
+        &format!(
+            "/api/v1/users/{username}/activities/feeds?only-performed-by=true&limit={limit}&\
+             page={page}"
+        ),
+    );
+    let url = req.url().clone();
+    let res =
+        forgejo_api::execute(client, "GET /api/v1/users/{username}/activities/feeds", req).await?;
+
+    if !res.status().is_success() {
+        return Err(GuardError::InvalidForgejoResponse(
+            format!("Status code: {status}", status = res.status()),
+            url,
+        ));
+    }
+
+    tracing::debug!("Get user activities response: {res:?}");
+
+    serde_json::from_str(&res.text().await?)
+        .map_err(|err| GuardError::InvalidForgejoResponse(err.to_string(), url))
+}
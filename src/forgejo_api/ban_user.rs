@@ -1,13 +1,17 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
 
-use reqwest::{Body, Method, Request};
+use reqwest::{Body, Method, Request, StatusCode};
 
 use crate::{
     config::BanAction,
     error::{GuardError, GuardResult},
 };
 
+/// The number of the repositories and activities to fetch per page, when
+/// taking an action on all of them
+const PAGE_LIMIT: u32 = 50;
+
 fn purge_req(instance: &url::Url, token: &str, username: &str) -> Request {
     super::build_request(
         Method::DELETE,
@@ -17,40 +21,286 @@ fn purge_req(instance: &url::Url, token: &str, username: &str) -> Request {
     )
 }
 
-fn suspend_req(instance: &url::Url, token: &str, username: &str) -> Request {
-    let mut req = super::build_request(
-        Method::PATCH,
-        instance,
-        token,
-        &format!("/api/v1/admin/users/{username}"),
-    );
+/// Returns a request with a JSON body
+fn json_req(
+    method: Method,
+    instance: &url::Url,
+    token: &str,
+    endpoint: &str,
+    body: String,
+) -> Request {
+    let mut req = super::build_request(method, instance, token, endpoint);
     req.headers_mut().insert(
         "Content-Type",
         "application/json".try_into().expect("Is valid"),
     );
-    *req.body_mut() = Some(Body::from(r#"{"prohibit_login": true}"#));
+    *req.body_mut() = Some(Body::from(body));
     req
 }
 
-/// Ban a user from the instance, purging their data.
+/// Returns a request to edit the user with the given JSON body
+fn edit_user_req(instance: &url::Url, token: &str, username: &str, body: String) -> Request {
+    json_req(
+        Method::PATCH,
+        instance,
+        token,
+        &format!("/api/v1/admin/users/{username}"),
+        body,
+    )
+}
+
+/// Send the request and count it in `sent_reqs`, returns an error if the
+/// response is not successful
+async fn send(
+    client: &reqwest::Client,
+    endpoint: &'static str,
+    req: Request,
+    sent_reqs: &mut u32,
+) -> GuardResult<()> {
+    *sent_reqs += 1;
+    let res = super::execute(client, endpoint, req).await?;
+    tracing::debug!("Ban action response of `{endpoint}`: {:?}", &res);
+
+    if !res.status().is_success() {
+        return Err(GuardError::FailedToBan(res.status()));
+    }
+    Ok(())
+}
+
+/// Send the requests, continuing after the failed ones. The not found
+/// resources are ignored, returns the last error if any
+async fn send_all(
+    client: &reqwest::Client,
+    endpoint: &'static str,
+    reqs: Vec<Request>,
+    sent_reqs: &mut u32,
+) -> GuardResult<()> {
+    let mut result = Ok(());
+    for req in reqs {
+        let url = req.url().clone();
+        match send(client, endpoint, req, sent_reqs).await {
+            Ok(()) | Err(GuardError::FailedToBan(StatusCode::NOT_FOUND)) => {}
+            Err(err) => {
+                tracing::error!("Failed to send `{endpoint}` request to `{url}`: {err}");
+                result = Err(err);
+            }
+        }
+    }
+    result
+}
+
+/// Returns the names of all the user repositories
+async fn user_repos(
+    client: &reqwest::Client,
+    instance: &url::Url,
+    token: &str,
+    username: &str,
+    sent_reqs: &mut u32,
+) -> GuardResult<Vec<String>> {
+    let mut repos = Vec::new();
+    for page in 1.. {
+        *sent_reqs += 1;
+        let page_repos =
+            super::get_user_repos(client, instance, token, username, PAGE_LIMIT, page).await?;
+        let is_last = page_repos.len() < PAGE_LIMIT as usize;
+        repos.extend(page_repos.into_iter().map(|repo| repo.name));
+        if is_last {
+            break;
+        }
+    }
+    Ok(repos)
+}
+
+/// Returns all the activities performed by the user
+async fn user_activities(
+    client: &reqwest::Client,
+    instance: &url::Url,
+    token: &str,
+    username: &str,
+    sent_reqs: &mut u32,
+) -> GuardResult<Vec<super::Activity>> {
+    let mut activities = Vec::new();
+    for page in 1.. {
+        *sent_reqs += 1;
+        let page_activities =
+            super::get_user_activities(client, instance, token, username, PAGE_LIMIT, page).await?;
+        let is_last = page_activities.len() < PAGE_LIMIT as usize;
+        activities.extend(page_activities);
+        if is_last {
+            break;
+        }
+    }
+    Ok(activities)
+}
+
+/// Make all the user repositories private, or delete them
+async fn repos_action(
+    client: &reqwest::Client,
+    instance: &url::Url,
+    token: &str,
+    username: &str,
+    delete: bool,
+    sent_reqs: &mut u32,
+) -> GuardResult<()> {
+    let repos = user_repos(client, instance, token, username, sent_reqs).await?;
+    if delete {
+        send_all(
+            client,
+            "DELETE /api/v1/repos/{owner}/{repo}",
+            repos
+                .iter()
+                .map(|repo| {
+                    super::build_request(
+                        Method::DELETE,
+                        instance,
+                        token,
+                        &format!("/api/v1/repos/{username}/{repo}"),
+                    )
+                })
+                .collect(),
+            sent_reqs,
+        )
+        .await
+    } else {
+        send_all(
+            client,
+            "PATCH /api/v1/repos/{owner}/{repo}",
+            repos
+                .iter()
+                .map(|repo| {
+                    json_req(
+                        Method::PATCH,
+                        instance,
+                        token,
+                        &format!("/api/v1/repos/{username}/{repo}"),
+                        r#"{"private": true}"#.to_owned(),
+                    )
+                })
+                .collect(),
+            sent_reqs,
+        )
+        .await
+    }
+}
+
+/// Delete the user issues, pull requests and comments
+async fn delete_content(
+    client: &reqwest::Client,
+    instance: &url::Url,
+    token: &str,
+    username: &str,
+    sent_reqs: &mut u32,
+) -> GuardResult<()> {
+    let activities = user_activities(client, instance, token, username, sent_reqs).await?;
+
+    let comments = activities
+        .iter()
+        .filter_map(|activity| {
+            let repo = activity.repo.as_ref()?;
+            (activity.comment_body().is_some() && activity.comment_id != 0).then(|| {
+                super::build_request(
+                    Method::DELETE,
+                    instance,
+                    token,
+                    &format!(
+                        "/api/v1/repos/{}/issues/comments/{}",
+                        repo.full_name, activity.comment_id
+                    ),
+                )
+            })
+        })
+        .collect();
+    let comments_result = send_all(
+        client,
+        "DELETE /api/v1/repos/{owner}/{repo}/issues/comments/{id}",
+        comments,
+        sent_reqs,
+    )
+    .await;
+
+    let issues = activities
+        .iter()
+        .filter_map(|activity| {
+            let repo = activity.repo.as_ref()?;
+            activity.issue_index().map(|index| {
+                super::build_request(
+                    Method::DELETE,
+                    instance,
+                    token,
+                    &format!("/api/v1/repos/{}/issues/{index}", repo.full_name),
+                )
+            })
+        })
+        .collect();
+    send_all(
+        client,
+        "DELETE /api/v1/repos/{owner}/{repo}/issues/{index}",
+        issues,
+        sent_reqs,
+    )
+    .await
+    .and(comments_result)
+}
+
+/// Ban a user from the instance, with the given ban action. The number of the
+/// sent requests is added to `sent_reqs`, the repositories and content actions
+/// send a request for each repository, issue or comment
 pub async fn ban_user(
     client: &reqwest::Client,
     instance: &url::Url,
     token: &str,
     username: &str,
     ban_action: &BanAction,
+    sent_reqs: &mut u32,
 ) -> GuardResult<()> {
-    let req = if ban_action.is_purge() {
-        purge_req(instance, token, username)
-    } else {
-        suspend_req(instance, token, username)
+    const EDIT_USER: &str = "PATCH /api/v1/admin/users/{username}";
+    let edit_user = |body: &str| edit_user_req(instance, token, username, body.to_owned());
+
+    tracing::debug!("Banning @{username} with the `{ban_action}` action");
+    let edit_body = match ban_action {
+        BanAction::Purge => {
+            return send(
+                client,
+                "DELETE /api/v1/admin/users/{username}",
+                purge_req(instance, token, username),
+                sent_reqs,
+            )
+            .await;
+        }
+        BanAction::PrivateRepos => {
+            return repos_action(client, instance, token, username, false, sent_reqs).await;
+        }
+        BanAction::DeleteRepos => {
+            return repos_action(client, instance, token, username, true, sent_reqs).await;
+        }
+        BanAction::DeleteContent => {
+            return delete_content(client, instance, token, username, sent_reqs).await;
+        }
+        BanAction::Suspend => r#"{"prohibit_login": true}"#,
+        BanAction::Restrict => r#"{"restricted": true}"#,
+        BanAction::DisableRepoCreation => r#"{"max_repo_creation": 0}"#,
     };
+    send(client, EDIT_USER, edit_user(edit_body), sent_reqs).await
+}
 
-    let res = client.execute(req).await?;
-    tracing::debug!("Ban user response of {ban_action:?}: {:?}", &res);
+/// Unsuspend a suspended user, allowing them to login again.
+pub async fn unsuspend_user(
+    client: &reqwest::Client,
+    instance: &url::Url,
+    token: &str,
+    username: &str,
+) -> GuardResult<()> {
+    let req = edit_user_req(
+        instance,
+        token,
+        username,
+        r#"{"prohibit_login": false}"#.to_owned(),
+    );
+    let res = super::execute(client, "PATCH /api/v1/admin/users/{username}", req).await?;
+    tracing::debug!("Unsuspend user response: {:?}", &res);
 
     if !res.status().is_success() {
-        return Err(GuardError::FailedToBan(res.status()));
+        return Err(GuardError::FailedToUnsuspend(res.status()));
     }
 
     Ok(())
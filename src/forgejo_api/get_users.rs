@@ -56,7 +56,7 @@ pub async fn get_user(username: &str, forgejo: &Forgejo) -> GuardResult<ForgejoU
     );
     let url = req.url().clone();
 
-    let res = reqwest::Client::new().execute(req).await?;
+    let res = super::execute(&reqwest::Client::new(), "GET /api/v1/users/{username}", req).await?;
 
     if !res.status().is_success() {
         return Err(GuardError::InvalidForgejoResponse(
@@ -88,7 +88,7 @@ pub async fn get_users(
     );
     let url = req.url().clone();
 
-    let res = client.execute(req).await?;
+    let res = super::execute(client, "GET /api/v1/admin/users", req).await?;
 
     if !res.status().is_success() {
         return Err(GuardError::InvalidForgejoResponse(
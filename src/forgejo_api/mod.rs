@@ -1,21 +1,31 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
 
-//! Simple SDK for Forgejo API, only for banning users and getting users.
+//! Simple SDK for Forgejo API, for banning users and getting users and their
+//! content.
 
 mod activity_feed;
 mod ban_user;
 mod get_users;
+mod repos;
 mod tokens;
 mod user;
 
+use std::time::Instant;
+
 pub use activity_feed::*;
 pub use ban_user::*;
 pub use get_users::*;
-use reqwest::{Method, Request};
+pub use repos::*;
+use reqwest::{Client, Method, Request, Response};
 pub use tokens::*;
 pub use user::*;
 
+use crate::{
+    health::HEALTH,
+    metrics::{EndpointErrorLabels, EndpointLabels, METRICS},
+};
+
 /// Build a request with the given method, instance, token and endpoint.
 pub fn build_request(method: Method, instance: &url::Url, token: &str, endpoint: &str) -> Request {
     let url = instance.join(endpoint).unwrap();
@@ -30,3 +40,35 @@ pub fn build_request(method: Method, instance: &url::Url, token: &str, endpoint:
 
     req
 }
+
+/// Execute the request, recording its latency and failure in the metrics and
+/// its success in the health. The endpoint is the request method and path
+/// template, e.g. `GET /api/v1/users/{username}`
+pub async fn execute(
+    client: &Client,
+    endpoint: &'static str,
+    req: Request,
+) -> reqwest::Result<Response> {
+    let start = Instant::now();
+    let res = client.execute(req).await;
+    METRICS
+        .forgejo_requests
+        .get_or_create(&EndpointLabels { endpoint })
+        .observe(start.elapsed().as_secs_f64());
+
+    let status = match &res {
+        Ok(res) if res.status().is_success() => {
+            HEALTH.forgejo_succeeded(res.url());
+            None
+        }
+        Ok(res) => Some(res.status().as_u16().to_string()),
+        Err(_) => Some("error".to_owned()),
+    };
+    if let Some(status) = status {
+        METRICS
+            .forgejo_errors
+            .get_or_create(&EndpointErrorLabels { endpoint, status })
+            .inc();
+    }
+    res
+}
@@ -0,0 +1,137 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
+
+use reqwest::{Client, Method};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{
+    error::{GuardError, GuardResult},
+    forgejo_api,
+};
+
+/// Maximum README size to check, bigger READMEs will be truncated
+const MAX_README_SIZE: usize = 64 * 1024;
+
+/// Forgejo repository
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ForgejoRepo {
+    /// Repository name
+    pub name:        String,
+    /// Repository description
+    pub description: String,
+    /// Whether the repository is empty
+    pub empty:       bool,
+}
+
+/// A file or a directory in the repository contents
+#[derive(Deserialize)]
+struct ContentsEntry {
+    /// The entry name
+    name:       String,
+    /// The entry type, `file`, `dir`, `symlink` or `submodule`
+    #[serde(rename = "type")]
+    entry_type: String,
+}
+
+/// Returns a page of the user repositories, `limit` repositories per page
+pub async fn get_user_repos(
+    client: &Client,
+    instance: &Url,
+    token: &str,
+    username: &str,
+    limit: u32,
+    page: u32,
+) -> GuardResult<Vec<ForgejoRepo>> {
+    let req = forgejo_api::build_request(
+        Method::GET,
+        instance,
+        token,
+        &format!("/api/v1/users/{username}/repos?limit={limit}&page={page}"),
+    );
+    let url = req.url().clone();
+    let res = forgejo_api::execute(client, "GET /api/v1/users/{username}/repos", req).await?;
+
+    if !res.status().is_success() {
+        return Err(GuardError::InvalidForgejoResponse(
+            format!("Status code: {status}", status = res.status()),
+            url,
+        ));
+    }
+
+    tracing::debug!("Get user repos response: {res:?}");
+
+    serde_json::from_str(&res.text().await?)
+        .map_err(|err| GuardError::InvalidForgejoResponse(err.to_string(), url))
+}
+
+/// Returns the README of the repository, if any. This will send two requests,
+/// one to find the README file in the repository root and one to get its
+/// content
+pub async fn get_readme(
+    client: &Client,
+    instance: &Url,
+    token: &str,
+    owner: &str,
+    repo: &str,
+) -> GuardResult<Option<String>> {
+    let req = forgejo_api::build_request(
+        Method::GET,
+        instance,
+        token,
+        &format!("/api/v1/repos/{owner}/{repo}/contents"),
+    );
+    let url = req.url().clone();
+    let res =
+        forgejo_api::execute(client, "GET /api/v1/repos/{owner}/{repo}/contents", req).await?;
+
+    if !res.status().is_success() {
+        return Err(GuardError::InvalidForgejoResponse(
+            format!("Status code: {status}", status = res.status()),
+            url,
+        ));
+    }
+
+    tracing::debug!("Get repo contents response: {res:?}");
+
+    let contents: Vec<ContentsEntry> = serde_json::from_str(&res.text().await?)
+        .map_err(|err| GuardError::InvalidForgejoResponse(err.to_string(), url))?;
+    let Some(readme) = contents.into_iter().find(|entry| {
+        entry.entry_type == "file" && entry.name.to_lowercase().starts_with("readme")
+    }) else {
+        return Ok(None);
+    };
+
+    let req = forgejo_api::build_request(
+        Method::GET,
+        instance,
+        token,
+        &format!("/api/v1/repos/{owner}/{repo}/raw/{}", readme.name),
+    );
+    let url = req.url().clone();
+    let res = forgejo_api::execute(
+        client,
+        "GET /api/v1/repos/{owner}/{repo}/raw/{filepath}",
+        req,
+    )
+    .await?;
+
+    if !res.status().is_success() {
+        return Err(GuardError::InvalidForgejoResponse(
+            format!("Status code: {status}", status = res.status()),
+            url,
+        ));
+    }
+
+    tracing::debug!("Get repo README response: {res:?}");
+
+    let mut readme = res.text().await?;
+    if readme.len() > MAX_README_SIZE {
+        let mut end = MAX_README_SIZE;
+        while !readme.is_char_boundary(end) {
+            end -= 1;
+        }
+        readme.truncate(end);
+    }
+    Ok(Some(readme))
+}
@@ -23,7 +23,7 @@ pub async fn is_empty_tokens(
         &format!("/api/v1/users/{username}/tokens"),
     );
     let url = req.url().clone();
-    let res = client.execute(req).await?;
+    let res = forgejo_api::execute(client, "GET /api/v1/users/{username}/tokens", req).await?;
 
     if !res.status().is_success() {
         return Err(GuardError::InvalidForgejoResponse(
@@ -51,7 +51,7 @@ pub async fn is_empty_apps(
         &format!("/api/v1/user/applications/oauth2?sudo={username}"),
     );
     let url = req.url().clone();
-    let res = client.execute(req).await?;
+    let res = forgejo_api::execute(client, "GET /api/v1/user/applications/oauth2", req).await?;
 
     if !res.status().is_success() {
         return Err(GuardError::InvalidForgejoResponse(
@@ -4,37 +4,59 @@
 use std::time::Duration;
 
 use chrono::{DateTime, Utc};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 /// Forgejo user
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct ForgejoUser {
     /// User id, incremental integer
-    pub id:         usize,
+    pub id:             usize,
     /// Avatar URL
-    pub avatar_url: url::Url,
+    pub avatar_url:     url::Url,
     /// HTML URL
-    pub html_url:   url::Url,
+    pub html_url:       url::Url,
     /// Is admin
-    pub is_admin:   bool,
+    pub is_admin:       bool,
     /// The login source id
-    pub source_id:  u32,
+    pub source_id:      u32,
     /// Username
     #[serde(rename = "login")]
-    pub username:   String,
+    pub username:       String,
     /// Full name
-    pub full_name:  String,
+    pub full_name:      String,
     /// Biography (AKA bio, profile description)
     #[serde(rename = "description")]
-    pub biography:  String,
+    pub biography:      String,
     /// Email
-    pub email:      String,
+    pub email:          String,
     /// Website
-    pub website:    String,
+    pub website:        String,
     /// Location
-    pub location:   String,
+    pub location:       String,
     /// Created date of the user
-    pub created:    DateTime<Utc>,
+    pub created:        DateTime<Utc>,
+    /// Whether the user is prohibited from login, AKA suspended
+    #[serde(default)]
+    pub prohibit_login: bool,
+    /// The user content, repositories, issues and comments. It's fetched only
+    /// if there are expressions for it
+    #[serde(skip)]
+    pub content:        UserContent,
+}
+
+/// The user content, repositories, issues and comments
+#[derive(Debug, Default, Clone)]
+pub struct UserContent {
+    /// Names of the user repositories
+    pub repo_names:        Vec<String>,
+    /// Descriptions of the user repositories
+    pub repo_descriptions: Vec<String>,
+    /// READMEs of the user repositories
+    pub readmes:           Vec<String>,
+    /// Titles of the user issues and pull requests
+    pub issue_titles:      Vec<String>,
+    /// Bodies of the user comments
+    pub comment_bodies:    Vec<String>,
 }
 
 impl ForgejoUser {
@@ -0,0 +1,165 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
+
+//! The guardian tasks health, reported on the server `/healthz` and `/readyz`
+//! endpoints.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{LazyLock, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+use url::Url;
+
+/// The guardian tasks health
+pub static HEALTH: LazyLock<Health> = LazyLock::new(Health::default);
+
+/// The task status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskStatus {
+    /// The task is running
+    Running,
+    /// The task has done its work, e.g. the old users fetcher checked all the
+    /// instance users
+    Finished,
+    /// The task has been stopped by the shutdown
+    Stopped,
+    /// The task has been stopped unexpectedly, or panicked
+    Dead,
+}
+
+/// A spawned task state
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskState {
+    /// The task name
+    pub name:       &'static str,
+    /// The instance(s) of the task, `None` for the unnamed instance (single
+    /// instance)
+    pub instance:   Option<String>,
+    /// Whether a dead task makes the guardian unhealthy
+    pub critical:   bool,
+    /// The task status
+    pub status:     TaskStatus,
+    /// When the task has been started
+    pub started_at: DateTime<Utc>,
+    /// When the task has been stopped
+    pub stopped_at: Option<DateTime<Utc>>,
+}
+
+/// The output of a spawned task
+pub trait TaskOutput {
+    /// Returns `true` if the task has done its work, otherwise the task must
+    /// run until the shutdown
+    fn is_finished(&self) -> bool;
+}
+
+impl TaskOutput for () {
+    fn is_finished(&self) -> bool {
+        false
+    }
+}
+
+impl TaskOutput for bool {
+    fn is_finished(&self) -> bool {
+        *self
+    }
+}
+
+/// The guardian tasks health
+#[derive(Default)]
+pub struct Health {
+    /// The spawned tasks
+    tasks:   Mutex<Vec<TaskState>>,
+    /// The last successful Forgejo request of each Forgejo instance, the key is
+    /// the instance origin
+    forgejo: Mutex<HashMap<String, DateTime<Utc>>>,
+}
+
+impl Health {
+    /// Register a new running task, returns its index
+    fn register(&self, name: &'static str, instance: Option<String>, critical: bool) -> usize {
+        let mut tasks = self.tasks.lock().expect("Poisoned lock");
+        tasks.push(TaskState {
+            name,
+            instance,
+            critical,
+            status: TaskStatus::Running,
+            started_at: Utc::now(),
+            stopped_at: None,
+        });
+        tasks.len() - 1
+    }
+
+    /// Set the stopped task status
+    fn stopped(&self, index: usize, status: TaskStatus) {
+        let mut tasks = self.tasks.lock().expect("Poisoned lock");
+        tasks[index].status = status;
+        tasks[index].stopped_at = Some(Utc::now());
+    }
+
+    /// Returns the spawned tasks
+    pub fn tasks(&self) -> Vec<TaskState> {
+        self.tasks.lock().expect("Poisoned lock").clone()
+    }
+
+    /// Returns `true` if there is no dead critical task
+    pub fn is_healthy(&self) -> bool {
+        !self
+            .tasks
+            .lock()
+            .expect("Poisoned lock")
+            .iter()
+            .any(|task| task.critical && task.status == TaskStatus::Dead)
+    }
+
+    /// Record a successful Forgejo request
+    pub fn forgejo_succeeded(&self, url: &Url) {
+        self.forgejo
+            .lock()
+            .expect("Poisoned lock")
+            .insert(url.origin().ascii_serialization(), Utc::now());
+    }
+
+    /// Returns the last successful request of the Forgejo instance
+    pub fn forgejo_last_success(&self, instance: &Url) -> Option<DateTime<Utc>> {
+        self.forgejo
+            .lock()
+            .expect("Poisoned lock")
+            .get(&instance.origin().ascii_serialization())
+            .copied()
+    }
+}
+
+/// Spawn a task and monitor it, the task is dead if it stopped before the
+/// shutdown without finishing its work, or panicked
+pub fn spawn<F>(
+    name: &'static str,
+    instance: Option<String>,
+    critical: bool,
+    cancellation_token: &CancellationToken,
+    future: F,
+) where
+    F: Future + Send + 'static,
+    F::Output: TaskOutput + Send + 'static,
+{
+    let index = HEALTH.register(name, instance, critical);
+    let cancellation_token = cancellation_token.clone();
+    let handle = tokio::spawn(future);
+
+    tokio::spawn(async move {
+        let status = match handle.await {
+            _ if cancellation_token.is_cancelled() => TaskStatus::Stopped,
+            Ok(output) if output.is_finished() => TaskStatus::Finished,
+            _ => TaskStatus::Dead,
+        };
+        if status == TaskStatus::Dead {
+            tracing::error!("The `{name}` task has been stopped unexpectedly");
+        }
+        HEALTH.stopped(index, status);
+    });
+}
@@ -11,9 +11,12 @@ use tokio_util::sync::CancellationToken;
 use url::Url;
 
 use crate::{
-    config::{BanAction, Config},
+    config::{BanAction, Config, ConfigReceiver},
+    db::{AuditAction, AuditEntry, AuditLogTableTrait, GuardDb},
     error::GuardResult,
     forgejo_api::{self, ForgejoUser, Sort},
+    metrics::{FetcherLabels, InstanceLabels, METRICS},
+    snapshots,
 };
 
 const LIMIT: u32 = 30;
@@ -101,7 +104,12 @@ pub async fn is_inactive(
 }
 
 /// Check if the user is inactive.
-async fn check_user(req_client: &Client, config: &Config, user: ForgejoUser) -> usize {
+async fn check_user(
+    req_client: &Client,
+    config: &Config,
+    database: &GuardDb,
+    user: ForgejoUser,
+) -> usize {
     if user.is_admin
         || config.inactive.exclude.contains(&user.username)
         || config.inactive.source_id_exclude.contains(&user.source_id)
@@ -129,20 +137,40 @@ async fn check_user(req_client: &Client, config: &Config, user: ForgejoUser) ->
     {
         Ok(true) => {
             tracing::info!("User `@{}` is inactive.", user.username);
-            if !config.dry_run {
+            let audit_entry = AuditEntry::guardian(
+                config,
+                &user.username,
+                Some(user.id),
+                AuditAction::InactivePurged,
+                Some(BanAction::Purge),
+            );
+            if config.dry_run {
+                database.audit(audit_entry);
+            } else {
+                let mut reqs = snapshots::take(req_client, config, database, &user).await;
                 if let Err(err) = forgejo_api::ban_user(
                     req_client,
                     &config.forgejo.instance,
                     &config.forgejo.token,
                     &user.username,
                     &BanAction::Purge,
+                    &mut reqs,
                 )
                 .await
                 {
                     tracing::error!("Error while ban inactive user `@{}`: {err}", user.username);
+                } else {
+                    database.audit(audit_entry);
+                    METRICS
+                        .inactive_users_purged
+                        .get_or_create(&InstanceLabels::new(config))
+                        .inc();
                 }
-                // activity feed, purge request and tokens (if sended)
-                return 2 + usize::from(config.check_tokens) + usize::from(config.check_oauth2);
+                // activity feed, tokens (if sended), snapshot and purge requests
+                return 1
+                    + usize::from(config.check_tokens)
+                    + usize::from(config.check_oauth2)
+                    + reqs as usize;
             }
         }
         Err(err) => {
@@ -159,6 +187,7 @@ async fn inactive_checker(
     cancellation_token: CancellationToken,
     req_client: &Client,
     config: &Config,
+    database: &GuardDb,
 ) {
     let wait_interval = || {
         async {
@@ -173,6 +202,12 @@ async fn inactive_checker(
         }
     };
 
+    let labels = FetcherLabels::new(config, "inactive");
+    METRICS
+        .fetcher_req_limit
+        .get_or_create(&labels)
+        .set(config.inactive.req_limit.into());
+
     let mut reqs: usize = 0;
     let mut page = 1;
     let now = SystemTime::now();
@@ -225,29 +260,57 @@ async fn inactive_checker(
                 }
                 reqs = 0
             }
-            reqs += check_user(req_client, config, user).await;
+            reqs += check_user(req_client, config, database, user).await;
+            METRICS
+                .fetcher_requests
+                .get_or_create(&labels)
+                .set(reqs as i64);
         }
         page += 1;
+        METRICS
+            .fetcher_last_success
+            .get_or_create(&labels)
+            .set(chrono::Utc::now().timestamp() as f64);
     }
 }
 
 /// The handler for the inactive users checker.
-pub async fn handler(config: Arc<Config>, cancellation_token: CancellationToken) {
+pub async fn handler(
+    config_receiver: ConfigReceiver,
+    database: Arc<GuardDb>,
+    cancellation_token: CancellationToken,
+) {
     tracing::info!("Starting inactive users checker");
     let request_client = Arc::new(reqwest::Client::new());
 
     // Run the first check, then wait for the interval.
     // Because the first check is not dependent on the interval.
-    inactive_checker(cancellation_token.clone(), &request_client, &config).await;
+    let config = Arc::clone(&config_receiver.borrow());
+    inactive_checker(
+        cancellation_token.clone(),
+        &request_client,
+        &config,
+        &database,
+    )
+    .await;
     loop {
+        let interval = config_receiver.borrow().inactive.interval;
         tokio::select! {
-            _ = tokio::time::sleep(Duration::from_secs(config.inactive.interval.into())) => {
-                inactive_checker(cancellation_token.clone(), &request_client, &config).await;
-            }
-            _ = cancellation_token.cancelled() => {
-                tracing::info!("Inactive users checker has been stopped successfully.");
-                break
-            }
-        };
+                _ = tokio::time::sleep(Duration::from_secs(interval.into())) => {
+                    // Each check uses the latest reloaded config
+                    let config = Arc::clone(&config_receiver.borrow());
+                    inactive_checker(
+            cancellation_token.clone(),
+            &request_client,
+            &config,
+            &database,
+        )
+        .await;
+                }
+                _ = cancellation_token.cancelled() => {
+                    tracing::info!("Inactive users checker has been stopped successfully.");
+                    break
+                }
+            };
     }
 }
@@ -0,0 +1,110 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
+
+//! The guarded Forgejo instances, the guardian can guard multiple instances
+//! from one process.
+
+use std::sync::Arc;
+
+use tokio::sync::{broadcast::Sender, watch};
+
+use crate::{
+    bots::{notifier::AlertSender, Decision, Report},
+    config::Config,
+    db::{AuditEntry, AuditLogTableTrait, GuardDb},
+    metrics,
+};
+
+/// The separator between the instance name and the username, the usernames
+/// can't contain it
+const USER_SEPARATOR: char = '/';
+
+/// A guarded Forgejo instance
+#[derive(Clone)]
+pub struct Instance {
+    /// The instance name, it can't be changed by the config reload
+    name:              Option<String>,
+    /// Sender of the instance configuration, the reloaded configuration is
+    /// sent in it
+    pub config_sender: watch::Sender<Arc<Config>>,
+    /// The instance database
+    pub database:      Arc<GuardDb>,
+    /// Sender of the instance suspicious users alerts
+    pub sus_sender:    AlertSender,
+    /// Sender of the instance banned users alerts and ban requests
+    pub ban_sender:    AlertSender,
+    /// Sender of the moderators decisions, each bot reflects the decisions of
+    /// the other bots on its alerts
+    pub decisions:     Sender<Decision>,
+    /// Sender of the reports to the moderation team, e.g. a rejected config
+    /// reload
+    pub reports:       Sender<Report>,
+}
+
+impl Instance {
+    /// Create a new instance with its configuration and database
+    pub fn new(config: Config, database: GuardDb) -> Self {
+        Self {
+            name:          config.forgejo.name.clone(),
+            config_sender: watch::channel(Arc::new(config)).0,
+            database:      Arc::new(database),
+            // Suspicious users are sent in this channel, users who meet the `alert`
+            // expressions
+            sus_sender:    AlertSender::default(),
+            // Banned users (already banned if `ban_alert`is set to true) and ban
+            // request are sent in this channel, this to alert the admins on
+            // the notifiers
+            ban_sender:    AlertSender::default(),
+            decisions:     Sender::new(100),
+            reports:       Sender::new(10),
+        }
+    }
+
+    /// Returns the instance name, `None` if it's the only instance
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Returns the current instance configuration
+    pub fn config(&self) -> Arc<Config> {
+        Arc::clone(&self.config_sender.borrow())
+    }
+
+    /// Returns the username prefixed with the instance name, to identify the
+    /// user in the bots. e.g. `codeberg/username`
+    pub fn user_id(&self, username: &str) -> String {
+        match self.name() {
+            Some(name) => format!("{name}{USER_SEPARATOR}{username}"),
+            None => username.to_owned(),
+        }
+    }
+
+    /// Publish a moderator decision to the other notifiers
+    pub fn decide(&self, decision: Decision) {
+        let config = self.config();
+        metrics::decision_taken(&config, &decision);
+        self.database
+            .audit(AuditEntry::decision(&config, &decision));
+        // An error means that there is no other notifier
+        self.decisions.send(decision).ok();
+    }
+}
+
+/// Split the user id into the instance name and the username, see
+/// [`Instance::user_id`]
+pub fn split_user_id(user_id: &str) -> (Option<&str>, &str) {
+    match user_id.split_once(USER_SEPARATOR) {
+        Some((name, username)) => (Some(name), username),
+        None => (None, user_id),
+    }
+}
+
+/// Find the instance by its name. If the name is `None`, the instance will be
+/// returned only if it's the only one
+pub fn find<'a>(instances: &'a [Instance], name: Option<&str>) -> Option<&'a Instance> {
+    match name {
+        Some(name) => instances.iter().find(|i| i.name() == Some(name)),
+        None if instances.len() == 1 => instances.first(),
+        None => None,
+    }
+}
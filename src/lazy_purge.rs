@@ -3,14 +3,16 @@
 
 use std::{sync::Arc, time::Duration};
 
-use redb::Database;
 use tokio_util::sync::CancellationToken;
 
-use crate::{config::Config, db::PurgedUsersTableTrait};
+use crate::{
+    config::{Config, ConfigReceiver},
+    db::{GuardDb, PurgedUsersTableTrait},
+};
 
 /// Purge purged users
 pub async fn purge_purged_users(
-    database: &Database,
+    database: &GuardDb,
     config: &Config,
     request_client: &reqwest::Client,
     cancellation_token: CancellationToken,
@@ -25,14 +27,16 @@ pub async fn purge_purged_users(
 
 /// The lazy purge worker
 pub async fn worker(
-    database: Arc<Database>,
-    config: Arc<Config>,
+    database: Arc<GuardDb>,
+    config_receiver: ConfigReceiver,
     cancellation_token: CancellationToken,
 ) {
     tracing::info!("Starting lazy purge worker");
     let request_client = Arc::new(reqwest::Client::new());
 
     loop {
+        // Each purge uses the latest reloaded config
+        let config = Arc::clone(&config_receiver.borrow());
         tokio::select! {
             _ = tokio::time::sleep(Duration::from_secs(config.lazy_purge.interval.into())) => {
                 purge_purged_users(&database, &config, &request_client, cancellation_token.clone()).await;
@@ -4,36 +4,67 @@
 #[macro_use]
 extern crate rust_i18n;
 
-use std::{process::ExitCode, sync::Arc, time::Duration};
+use std::{future::Future, process::ExitCode, sync::Arc, time::Duration};
 
 use forgejo_api::Sort;
-use tokio::{signal::ctrl_c, sync};
+use instance::Instance;
+use tokio::signal::ctrl_c;
 use tokio_util::sync::CancellationToken;
+use tracing::{Instrument, Span};
 
 pub mod bots;
+pub mod check;
 pub mod config;
 pub mod db;
+pub mod email_domains;
 pub mod error;
+pub mod feeds;
 pub mod forgejo_api;
+pub mod health;
 pub mod inactive_users;
+pub mod instance;
 pub mod lazy_purge;
+pub mod metrics;
+pub mod reload;
+pub mod server;
+pub mod simulate;
+pub mod snapshots;
 pub mod traits;
 pub mod users_fetcher;
 pub mod utils;
 
 i18n!("locales", fallback = "en-us");
 
-async fn try_main() -> error::GuardResult<()> {
-    let config = Arc::new(utils::get_config()?);
-    let cancellation_token = CancellationToken::new();
-    // Suspicious users are sent and received in this channel, users who meet the
-    // `alert` expressions
-    let (sus_sender, sus_receiver) = sync::mpsc::channel::<bots::UserAlert>(100);
-    // Banned users (already banned if `ban_alert`is set to true) and ban request
-    // are sent and received in this channel, this to alert the admins on
-    // Telegram and Matrix
-    let (ban_sender, ban_receiver) = sync::mpsc::channel::<bots::UserAlert>(100);
-    let database = Arc::new(db::init_db(&config.database)?);
+/// Spawn the instance critical task in the instance span, see
+/// [`health::spawn`]
+fn spawn<F>(
+    span: &Span,
+    name: &'static str,
+    instance: &Instance,
+    cancellation_token: &CancellationToken,
+    future: F,
+) where
+    F: Future + Send + 'static,
+    F::Output: health::TaskOutput + Send + 'static,
+{
+    health::spawn(
+        name,
+        instance.name().map(String::from),
+        true,
+        cancellation_token,
+        future.instrument(span.clone()),
+    );
+}
+
+/// Run the instance tasks, the users fetchers, inactive users checker and lazy
+/// purge
+fn run_instance(instance: &Instance, cancellation_token: &CancellationToken) {
+    let config = &instance.config();
+    let span = match instance.name() {
+        Some(name) => tracing::info_span!("instance", name),
+        None => Span::none(),
+    };
+    let _entered = span.enter();
 
     tracing::info!("Forgejo instance: {}", config.forgejo.instance);
     tracing::info!("Dry run: {}", config.dry_run);
@@ -51,13 +82,57 @@ async fn try_main() -> error::GuardResult<()> {
         "Sus expressions enabled: {}",
         config.expressions.sus.enabled
     );
+    tracing::info!(
+        "Score expressions enabled: {}",
+        config.expressions.score.enabled
+    );
+    tracing::info!(
+        "Allow expressions enabled: {}",
+        config.expressions.allow.enabled
+    );
+    tracing::info!(
+        "Email domains blocklist enabled: {}",
+        config.expressions.is_email_domains_enabled()
+    );
     tracing::info!(
         config = "lazy_purge",
         "Lazy purge enabled: {}",
         config.lazy_purge.enabled
     );
+    tracing::info!(
+        config = "snapshots",
+        "Snapshots enabled: {}",
+        config.snapshots().is_some()
+    );
     tracing::debug!("The config exprs: {:#?}", config.expressions);
 
+    if let Some(snapshots) = config.snapshots() {
+        tracing::info!(
+            config = "snapshots",
+            "Storage: {}",
+            snapshots
+                .directory
+                .as_ref()
+                .map_or_else(|| "database".to_owned(), |d| d.display().to_string())
+        );
+        tracing::info!(
+            config = "snapshots",
+            "Retention: {} seconds",
+            snapshots.retention
+        );
+        spawn(
+            &span,
+            "snapshots_cleaner",
+            instance,
+            cancellation_token,
+            snapshots::cleaner(
+                instance.config_sender.subscribe(),
+                Arc::clone(&instance.database),
+                cancellation_token.clone(),
+            ),
+        );
+    }
+
     if config.inactive.enabled {
         tracing::info!(
             config = "inactive",
@@ -81,23 +156,107 @@ async fn try_main() -> error::GuardResult<()> {
             config.inactive.interval,
         );
 
-        tokio::spawn(inactive_users::handler(
-            Arc::clone(&config),
-            cancellation_token.clone(),
-        ));
+        spawn(
+            &span,
+            "inactive_checker",
+            instance,
+            cancellation_token,
+            inactive_users::handler(
+                instance.config_sender.subscribe(),
+                Arc::clone(&instance.database),
+                cancellation_token.clone(),
+            ),
+        );
     }
 
-    if config.expressions.ban.enabled || config.expressions.sus.enabled {
+    if config.expressions.ban.enabled
+        || config.expressions.sus.enabled
+        || config.expressions.score.enabled
+        || config.expressions.is_email_domains_enabled()
+    {
         tracing::info!(
             config = "expressions",
             "Ban action: {}",
             config.expressions.ban_action
         );
+        if config.expressions.allow.enabled {
+            tracing::info!(
+                config = "expressions",
+                "Allow action: {}",
+                config.expressions.allow_action
+            );
+        }
+        if let Some(email_domains) = config
+            .expressions
+            .email_domains
+            .as_ref()
+            .filter(|e| e.enabled)
+        {
+            tracing::info!(
+                config = "email_domains",
+                "Blocklist: {}",
+                email_domains.blocklist.display()
+            );
+            tracing::info!(config = "email_domains", "Action: {}", email_domains.action);
+            tracing::info!(
+                config = "email_domains",
+                "Check MX hosts: {}",
+                email_domains.check_mx
+            );
+            spawn(
+                &span,
+                "email_domains_reloader",
+                instance,
+                cancellation_token,
+                email_domains::reloader(
+                    instance.config_sender.subscribe(),
+                    cancellation_token.clone(),
+                ),
+            );
+        }
+        if !config.expressions.feeds.is_empty() {
+            for feed in &config.expressions.feeds {
+                tracing::info!(
+                    config = "feeds",
+                    "Feed: {} (every {}s)",
+                    feed.url,
+                    feed.interval
+                );
+            }
+            spawn(
+                &span,
+                "feeds_fetcher",
+                instance,
+                cancellation_token,
+                feeds::fetcher(
+                    instance.config_sender.subscribe(),
+                    cancellation_token.clone(),
+                ),
+            );
+        }
         tracing::info!(
             config = "expressions",
             "Safe mode: {}",
             config.expressions.safe_mode
         );
+        if config.expressions.score.enabled {
+            tracing::info!(
+                config = "expressions",
+                "Ban score: {}",
+                config
+                    .expressions
+                    .ban_score
+                    .map_or_else(|| "disabled".to_owned(), |s| s.to_string())
+            );
+            tracing::info!(
+                config = "expressions",
+                "Sus score: {}",
+                config
+                    .expressions
+                    .sus_score
+                    .map_or_else(|| "disabled".to_owned(), |s| s.to_string())
+            );
+        }
         tracing::info!(
             config = "expressions",
             "check existing users: {}",
@@ -129,34 +288,52 @@ async fn try_main() -> error::GuardResult<()> {
             config.expressions.req_interval
         );
 
-        tokio::spawn(users_fetcher::users_fetcher(
-            Sort::Newest,
-            Arc::clone(&config),
-            Arc::clone(&database),
-            cancellation_token.clone(),
-            sus_sender.clone(),
-            ban_sender.clone(),
-        ));
+        spawn(
+            &span,
+            "newest_fetcher",
+            instance,
+            cancellation_token,
+            users_fetcher::users_fetcher(
+                Sort::Newest,
+                instance.config_sender.subscribe(),
+                Arc::clone(&instance.database),
+                cancellation_token.clone(),
+                instance.sus_sender.clone(),
+                instance.ban_sender.clone(),
+            ),
+        );
 
         if config.expressions.check_updated_users {
-            tokio::spawn(users_fetcher::users_fetcher(
-                Sort::RecentUpdate,
-                Arc::clone(&config),
-                Arc::clone(&database),
-                cancellation_token.clone(),
-                sus_sender.clone(),
-                ban_sender.clone(),
-            ));
+            spawn(
+                &span,
+                "recentupdate_fetcher",
+                instance,
+                cancellation_token,
+                users_fetcher::users_fetcher(
+                    Sort::RecentUpdate,
+                    instance.config_sender.subscribe(),
+                    Arc::clone(&instance.database),
+                    cancellation_token.clone(),
+                    instance.sus_sender.clone(),
+                    instance.ban_sender.clone(),
+                ),
+            );
         }
 
         if config.expressions.check_existing_users {
-            tokio::spawn(users_fetcher::old_users(
-                Arc::clone(&config),
-                Arc::clone(&database),
-                ban_sender,
-                sus_sender,
-                cancellation_token.clone(),
-            ));
+            spawn(
+                &span,
+                "oldest_fetcher",
+                instance,
+                cancellation_token,
+                users_fetcher::old_users(
+                    instance.config_sender.subscribe(),
+                    Arc::clone(&instance.database),
+                    instance.ban_sender.clone(),
+                    instance.sus_sender.clone(),
+                    cancellation_token.clone(),
+                ),
+            );
         }
 
         if config.lazy_purge.enabled {
@@ -180,21 +357,76 @@ async fn try_main() -> error::GuardResult<()> {
                 "Purge after: {} seconds",
                 config.lazy_purge.purge_after
             );
-            tokio::spawn(lazy_purge::worker(
-                Arc::clone(&database),
-                Arc::clone(&config),
-                cancellation_token.clone(),
-            ));
+            spawn(
+                &span,
+                "lazy_purge",
+                instance,
+                cancellation_token,
+                lazy_purge::worker(
+                    Arc::clone(&instance.database),
+                    instance.config_sender.subscribe(),
+                    cancellation_token.clone(),
+                ),
+            );
         }
     }
+}
+
+async fn try_main() -> error::GuardResult<()> {
+    let configs = utils::get_configs()?;
+    let cancellation_token = CancellationToken::new();
+    let database = Arc::new(db::init_db(&configs[0].database)?);
+    let mut instances = Vec::with_capacity(configs.len());
+
+    for config in configs {
+        let instance_db = db::GuardDb::new(Arc::clone(&database), config.forgejo.name.as_deref())?;
+        instances.push(Instance::new(config, instance_db));
+    }
+
+    // The notifiers must subscribe to the alerts before running the instances
+    bots::run_bots(&instances, cancellation_token.clone());
+    for instance in &instances {
+        run_instance(instance, &cancellation_token);
+    }
 
-    bots::run_bots(
-        Arc::clone(&database),
-        Arc::clone(&config),
-        cancellation_token.clone(),
-        sus_receiver,
-        ban_receiver,
+    let config = instances[0].config();
+    tracing::info!(
+        config = "reload",
+        "Watch the config file: {}",
+        config.reload.watch
     );
+    if config.reload.watch {
+        tracing::info!(
+            config = "reload",
+            "Interval between each config file check: {} seconds",
+            config.reload.interval
+        );
+    }
+    health::spawn(
+        "config_reloader",
+        None,
+        false,
+        &cancellation_token,
+        reload::reloader(instances.clone(), cancellation_token.clone()),
+    );
+
+    let server_config = &config.server;
+    if server_config.enabled {
+        tracing::info!(
+            config = "server",
+            "Listen address: {}",
+            server_config.listen
+        );
+
+        let token = cancellation_token.clone();
+        let server = server::run(instances, cancellation_token.clone());
+        tokio::spawn(async move {
+            if let Err(err) = server.await {
+                tracing::error!("The server has been stopped: {err}");
+                token.cancel();
+            }
+        });
+    }
 
     tokio::select! {
         _ = ctrl_c() => {
@@ -211,11 +443,24 @@ async fn try_main() -> error::GuardResult<()> {
 
 #[tokio::main]
 async fn main() -> ExitCode {
-    tracing_subscriber::fmt()
-        .with_max_level(utils::get_log_level())
-        .init();
+    let mut args = std::env::args().skip(1);
+    let subcommand = args
+        .next()
+        .filter(|arg| ["check", "simulate"].contains(&arg.as_str()));
+    let subscriber = tracing_subscriber::fmt().with_max_level(utils::get_log_level());
+    if subcommand.is_some() {
+        // Keep the stdout for the subcommand result
+        subscriber.with_writer(std::io::stderr).init();
+    } else {
+        subscriber.init();
+    }
 
-    if let Err(err) = try_main().await {
+    let result = match subcommand.as_deref() {
+        Some("check") => check::run(args).await,
+        Some(_) => simulate::run(args).await,
+        None => try_main().await,
+    };
+    if let Err(err) = result {
         eprintln!("{err}");
         return ExitCode::FAILURE;
     }
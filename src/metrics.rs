@@ -0,0 +1,266 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
+
+//! The guardian Prometheus metrics, exported on the server `/metrics` endpoint.
+
+use std::sync::{atomic::AtomicU64, LazyLock};
+
+use prometheus_client::{
+    encoding::{text, EncodeLabelSet},
+    metrics::{
+        counter::Counter,
+        family::Family,
+        gauge::Gauge,
+        histogram::{exponential_buckets, Histogram},
+    },
+    registry::Registry,
+};
+
+use crate::{bots::Decision, config::Config};
+
+/// The guardian metrics
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// The labels of the instance metrics
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct InstanceLabels {
+    /// The instance name, empty for the unnamed instance (single instance)
+    pub instance: String,
+}
+
+/// The labels of the users fetchers metrics
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct FetcherLabels {
+    /// The instance name, empty for the unnamed instance (single instance)
+    pub instance: String,
+    /// The fetcher, `newest`, `recentupdate`, `oldest`, `webhook` or
+    /// `inactive`
+    pub fetcher:  String,
+}
+
+/// The labels of the moderators decisions metrics
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct DecisionLabels {
+    /// The instance name, empty for the unnamed instance (single instance)
+    pub instance: String,
+    /// The decision kind
+    pub decision: &'static str,
+    /// The notifier where the decision has been taken
+    pub notifier: &'static str,
+}
+
+/// The labels of the Forgejo API metrics
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct EndpointLabels {
+    /// The Forgejo API endpoint
+    pub endpoint: &'static str,
+}
+
+/// The labels of the Forgejo API errors metrics
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct EndpointErrorLabels {
+    /// The Forgejo API endpoint
+    pub endpoint: &'static str,
+    /// The response status code, or `error` if the request failed
+    pub status:   String,
+}
+
+/// The labels of the notifiers metrics
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct NotifierLabels {
+    /// The notifier name
+    pub notifier: &'static str,
+}
+
+/// A histogram family, with a buckets constructor
+type HistogramFamily<L> = Family<L, Histogram, fn() -> Histogram>;
+
+/// The guardian metrics
+pub struct Metrics {
+    /// The metrics registry
+    registry:                  Registry,
+    /// The checked users against the expressions
+    pub users_checked:         Family<FetcherLabels, Counter>,
+    /// The banned users by the expressions
+    pub users_banned:          Family<FetcherLabels, Counter>,
+    /// The suspected users by the expressions
+    pub users_suspected:       Family<FetcherLabels, Counter>,
+    /// The sent ban requests of the active users
+    pub ban_requests:          Family<FetcherLabels, Counter>,
+    /// The moderators decisions
+    pub decisions:             Family<DecisionLabels, Counter>,
+    /// The lazy purged users
+    pub users_lazy_purged:     Family<InstanceLabels, Counter>,
+    /// The purged inactive users
+    pub inactive_users_purged: Family<InstanceLabels, Counter>,
+    /// The users in the purge queue, updated on each scrape
+    pub purge_queue_size:      Family<InstanceLabels, Gauge>,
+    /// The fetcher requests since its last wait, to compare with the
+    /// `req_limit`
+    pub fetcher_requests:      Family<FetcherLabels, Gauge>,
+    /// The fetcher request limit before waiting `req_interval`
+    pub fetcher_req_limit:     Family<FetcherLabels, Gauge>,
+    /// The timestamp of the last completed fetch
+    pub fetcher_last_success:  Family<FetcherLabels, Gauge<f64, AtomicU64>>,
+    /// The delay between the user registration and checking them
+    pub fetcher_lag:           HistogramFamily<FetcherLabels>,
+    /// The Forgejo API requests latency
+    pub forgejo_requests:      HistogramFamily<EndpointLabels>,
+    /// The failed Forgejo API requests
+    pub forgejo_errors:        Family<EndpointErrorLabels, Counter>,
+    /// The notifiers failures to send the alerts
+    pub notifier_failures:     Family<NotifierLabels, Counter>,
+}
+
+impl Metrics {
+    /// Create and register the metrics
+    fn new() -> Self {
+        let mut metrics = Self {
+            registry:              Registry::with_prefix("forgejo_guardian"),
+            users_checked:         Family::default(),
+            users_banned:          Family::default(),
+            users_suspected:       Family::default(),
+            ban_requests:          Family::default(),
+            decisions:             Family::default(),
+            users_lazy_purged:     Family::default(),
+            inactive_users_purged: Family::default(),
+            purge_queue_size:      Family::default(),
+            fetcher_requests:      Family::default(),
+            fetcher_req_limit:     Family::default(),
+            fetcher_last_success:  Family::default(),
+            fetcher_lag:           Family::new_with_constructor(|| {
+                Histogram::new(exponential_buckets(1.0, 4.0, 10))
+            }),
+            forgejo_requests:      Family::new_with_constructor(|| {
+                Histogram::new(exponential_buckets(0.01, 2.0, 12))
+            }),
+            forgejo_errors:        Family::default(),
+            notifier_failures:     Family::default(),
+        };
+        let registry = &mut metrics.registry;
+
+        registry.register(
+            "users_checked",
+            "The checked users against the expressions",
+            metrics.users_checked.clone(),
+        );
+        registry.register(
+            "users_banned",
+            "The banned users by the expressions",
+            metrics.users_banned.clone(),
+        );
+        registry.register(
+            "users_suspected",
+            "The suspected users by the expressions",
+            metrics.users_suspected.clone(),
+        );
+        registry.register(
+            "ban_requests",
+            "The sent ban requests of the active users",
+            metrics.ban_requests.clone(),
+        );
+        registry.register(
+            "decisions",
+            "The moderators decisions",
+            metrics.decisions.clone(),
+        );
+        registry.register(
+            "users_lazy_purged",
+            "The lazy purged users",
+            metrics.users_lazy_purged.clone(),
+        );
+        registry.register(
+            "inactive_users_purged",
+            "The purged inactive users",
+            metrics.inactive_users_purged.clone(),
+        );
+        registry.register(
+            "purge_queue_size",
+            "The users in the purge queue",
+            metrics.purge_queue_size.clone(),
+        );
+        registry.register(
+            "fetcher_requests",
+            "The fetcher requests since its last wait, to compare with the request limit",
+            metrics.fetcher_requests.clone(),
+        );
+        registry.register(
+            "fetcher_request_limit",
+            "The fetcher request limit before waiting the request interval",
+            metrics.fetcher_req_limit.clone(),
+        );
+        registry.register(
+            "fetcher_last_success_timestamp_seconds",
+            "The timestamp of the fetcher last completed fetch",
+            metrics.fetcher_last_success.clone(),
+        );
+        registry.register(
+            "fetcher_lag_seconds",
+            "The delay between the user registration and checking them",
+            metrics.fetcher_lag.clone(),
+        );
+        registry.register(
+            "forgejo_request_duration_seconds",
+            "The Forgejo API requests latency",
+            metrics.forgejo_requests.clone(),
+        );
+        registry.register(
+            "forgejo_request_errors",
+            "The failed Forgejo API requests",
+            metrics.forgejo_errors.clone(),
+        );
+        registry.register(
+            "notifier_failures",
+            "The notifiers failures to send the alerts",
+            metrics.notifier_failures.clone(),
+        );
+
+        metrics
+    }
+
+    /// Encode the metrics in the OpenMetrics text format
+    pub fn encode(&self) -> String {
+        let mut buffer = String::new();
+        text::encode(&mut buffer, &self.registry).expect("Writing to a string can't fail");
+        buffer
+    }
+}
+
+impl InstanceLabels {
+    /// Returns the labels of the config instance
+    pub fn new(config: &Config) -> Self {
+        Self {
+            instance: config.forgejo.name.clone().unwrap_or_default(),
+        }
+    }
+}
+
+impl FetcherLabels {
+    /// Returns the labels of the config instance fetcher
+    pub fn new(config: &Config, fetcher: &str) -> Self {
+        Self {
+            instance: config.forgejo.name.clone().unwrap_or_default(),
+            fetcher:  fetcher.to_owned(),
+        }
+    }
+}
+
+/// Record a moderator decision taken on the config instance
+pub fn decision_taken(config: &Config, decision: &Decision) {
+    METRICS
+        .decisions
+        .get_or_create(&DecisionLabels {
+            instance: config.forgejo.name.clone().unwrap_or_default(),
+            decision: decision.kind.as_str(),
+            notifier: decision.notifier,
+        })
+        .inc();
+}
+
+/// Record a notifier failure to send an alert
+pub fn notifier_failed(notifier: &'static str) {
+    METRICS
+        .notifier_failures
+        .get_or_create(&NotifierLabels { notifier })
+        .inc();
+}
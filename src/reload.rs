@@ -0,0 +1,374 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
+
+//! The configuration hot reload. The config file is reloaded on `SIGHUP`, or
+//! when it gets modified if the `reload.watch` is enabled.
+//!
+//! The new configuration is swapped in only if it's valid, the running tasks
+//! use it from their next iteration. An invalid configuration is rejected and
+//! reported to the moderation team.
+
+use std::{
+    path::Path,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use tokio::signal::unix::{signal, SignalKind};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    bots::Report,
+    config::Config,
+    error::{GuardError, GuardResult},
+    instance::{self, Instance},
+    utils,
+};
+
+/// Returns the file modification time, `None` if it can't be read
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Returns the modification times of the config file and the loaded rules
+/// files of the instances
+fn modified_times(instances: &[Instance]) -> Vec<Option<SystemTime>> {
+    let mut times = vec![utils::config_path()
+        .ok()
+        .and_then(|path| modified_time(&path))];
+    for instance in instances {
+        times.extend(
+            instance
+                .config()
+                .expressions
+                .rules_files
+                .iter()
+                .map(|path| modified_time(path)),
+        );
+    }
+    times
+}
+
+/// Keep the fetched rules of the unchanged feeds, to not wait for the next
+/// fetch
+fn keep_feeds_rules(old_config: &Config, new_config: &mut Config) {
+    for feed in &mut new_config.expressions.feeds {
+        if let Some(old_feed) = old_config.expressions.feeds.iter().find(|old_feed| {
+            old_feed.url == feed.url
+                && old_feed.signature_url == feed.signature_url
+                && old_feed.secret == feed.secret
+        }) {
+            feed.rules = Arc::clone(&old_feed.rules);
+        }
+    }
+}
+
+/// Returns the settings that are used only on the startup, with their names.
+/// The tasks, the notifiers and the server are started with them, so they
+/// can't be changed by the reload
+fn startup_settings(config: &Config) -> [(&'static str, String); 14] {
+    let exprs = &config.expressions;
+    let server = &config.server;
+    [
+        ("database", format!("{:?}", config.database)),
+        (
+            "expressions",
+            format!(
+                "{:?}",
+                exprs.ban.enabled
+                    || exprs.sus.enabled
+                    || exprs.score.enabled
+                    || exprs.is_email_domains_enabled()
+            ),
+        ),
+        (
+            "expressions.check_updated_users",
+            format!("{:?}", exprs.check_updated_users),
+        ),
+        (
+            "expressions.check_existing_users",
+            format!("{:?}", exprs.check_existing_users),
+        ),
+        (
+            "expressions.email_domains",
+            format!("{:?}", exprs.is_email_domains_enabled()),
+        ),
+        (
+            "expressions.feeds",
+            format!("{:?}", !exprs.feeds.is_empty()),
+        ),
+        ("inactive", format!("{:?}", config.inactive.enabled)),
+        ("lazy_purge", format!("{:?}", config.lazy_purge.enabled)),
+        ("snapshots", format!("{:?}", config.snapshots().is_some())),
+        (
+            "telegram",
+            format!(
+                "{:?}",
+                config
+                    .telegram
+                    .data()
+                    .map(|t| (&t.token, t.chat.to_string(), t.lang.as_str()))
+            ),
+        ),
+        (
+            "matrix",
+            format!(
+                "{:?}",
+                config.matrix.data().map(|m| {
+                    (
+                        m.homeserver.as_str(),
+                        &m.username,
+                        &m.password,
+                        m.room.to_string(),
+                        m.lang.as_str(),
+                    )
+                })
+            ),
+        ),
+        (
+            "email",
+            format!(
+                "{:?}",
+                config
+                    .email()
+                    .map(|e| (&e.host, e.port, e.tls as u8, &e.username, &e.password))
+            ),
+        ),
+        ("webhook", format!("{:?}", config.webhook().is_some())),
+        (
+            "server",
+            format!(
+                "{:?}",
+                (
+                    server.enabled,
+                    server.listen,
+                    server.dashboard().is_some(),
+                    server.api().is_some(),
+                    server.metrics,
+                )
+            ),
+        ),
+    ]
+}
+
+/// Returns the name of the first changed setting that requires a restart, see
+/// [`startup_settings`]
+fn restart_required(old_config: &Config, new_config: &Config) -> Option<&'static str> {
+    startup_settings(old_config)
+        .into_iter()
+        .zip(startup_settings(new_config))
+        .find(|((_, old), (_, new))| old != new)
+        .map(|((name, _), _)| name)
+}
+
+/// Swap the instances configs with the reloaded configs. Nothing is swapped if
+/// any of the reloaded configs requires a restart
+fn swap_configs(instances: &[Instance], configs: Vec<Config>) -> GuardResult<()> {
+    if configs.len() != instances.len()
+        || configs
+            .iter()
+            .any(|config| instance::find(instances, config.forgejo.name.as_deref()).is_none())
+    {
+        return Err(GuardError::Other(
+            "The instances can't be added, removed or renamed without a restart".to_owned(),
+        ));
+    }
+    for config in &configs {
+        let instance =
+            instance::find(instances, config.forgejo.name.as_deref()).expect("The instance exists");
+        if let Some(setting) = restart_required(&instance.config(), config) {
+            return Err(GuardError::Other(format!(
+                "The `{setting}` settings can't be changed without a restart"
+            )));
+        }
+    }
+
+    for mut config in configs {
+        let instance =
+            instance::find(instances, config.forgejo.name.as_deref()).expect("The instance exists");
+        keep_feeds_rules(&instance.config(), &mut config);
+        instance.config_sender.send_replace(Arc::new(config));
+    }
+    Ok(())
+}
+
+/// Reload the config file and swap the instances configs. Nothing is swapped
+/// if any of the instances configs is invalid
+fn reload(instances: &[Instance]) -> GuardResult<()> {
+    swap_configs(instances, utils::get_configs()?)
+}
+
+/// Reload the config, reporting the rejected config to the moderation team
+fn reload_and_report(instances: &[Instance]) {
+    tracing::info!("Reloading the config");
+    match reload(instances) {
+        Ok(()) => tracing::info!("The config has been reloaded successfully"),
+        Err(err) => {
+            tracing::error!("The reloaded config has been rejected: {err}");
+            for instance in instances {
+                // An error means that there is no notifier
+                instance
+                    .reports
+                    .send(Report::ConfigRejected(err.to_string()))
+                    .ok();
+            }
+        }
+    }
+}
+
+/// The config reloader, reloads the config on `SIGHUP` or when the config file
+/// gets modified
+pub async fn reloader(instances: Vec<Instance>, cancellation_token: CancellationToken) {
+    let mut sighup = match signal(SignalKind::hangup()) {
+        Ok(sighup) => sighup,
+        Err(err) => {
+            tracing::error!("Failed to listen to the SIGHUP signal: {err}");
+            return;
+        }
+    };
+    tracing::info!("Starting the config reloader");
+    let mut last_modified = modified_times(&instances);
+
+    loop {
+        // The reload settings of the latest reloaded config
+        let config = instances[0].config();
+        let watch_interval = Duration::from_secs(config.reload.interval.into());
+        let watch = config.reload.watch;
+
+        tokio::select! {
+            _ = sighup.recv() => {
+                tracing::info!("Received SIGHUP");
+                reload_and_report(&instances);
+                last_modified = modified_times(&instances);
+            }
+            _ = tokio::time::sleep(watch_interval), if watch => {
+                if modified_times(&instances) != last_modified {
+                    tracing::info!("The config file or its rules files have been modified");
+                    reload_and_report(&instances);
+                    // The reloaded config may load other rules files
+                    last_modified = modified_times(&instances);
+                }
+            }
+            _ = cancellation_token.cancelled() => {
+                tracing::info!("Config reloader has been stopped successfully.");
+                break
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use redb::{backends::InMemoryBackend, Database};
+
+    use super::*;
+    use crate::db::GuardDb;
+
+    fn config(extra: &str) -> Config {
+        toml::from_str(&format!(
+            r#"
+            [forgejo]
+            instance_url = "https://forgejo.example"
+            token = "token"
+
+            {extra}
+            "#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn reloadable_changes() {
+        let old_config = config(
+            r#"
+            [expressions]
+            interval = 60
+            [expressions.ban]
+            usernames = ["^spam"]
+            "#,
+        );
+        let new_config = config(
+            r#"
+            [expressions]
+            interval = 30
+            ban_action = "suspend"
+            [expressions.ban]
+            usernames = ["^spam", "^bot"]
+            "#,
+        );
+
+        assert_eq!(restart_required(&old_config, &new_config), None);
+    }
+
+    #[test]
+    fn restart_required_changes() {
+        let old_config = config("[expressions.ban]\nusernames = [\"^spam\"]");
+
+        assert_eq!(
+            restart_required(&old_config, &config("")),
+            Some("expressions")
+        );
+        assert_eq!(
+            restart_required(&config(""), &old_config),
+            Some("expressions")
+        );
+        assert_eq!(
+            restart_required(&config(""), &config("[inactive]\nenabled = true")),
+            Some("inactive")
+        );
+        assert_eq!(
+            restart_required(&config(""), &config("[lazy_purge]\nenabled = true")),
+            Some("lazy_purge")
+        );
+        assert_eq!(
+            restart_required(
+                &config("[server]\nenabled = true"),
+                &config("[server]\nenabled = true\nlisten = \"0.0.0.0:9000\"")
+            ),
+            Some("server")
+        );
+    }
+
+    #[test]
+    fn rejected_reload() {
+        let database = Database::builder()
+            .create_with_backend(InMemoryBackend::new())
+            .unwrap();
+        let instance = Instance::new(
+            config(
+                "[expressions]
+interval = 60
+[expressions.ban]
+usernames = [\"^spam\"]",
+            ),
+            GuardDb::new(Arc::new(database), None).unwrap(),
+        );
+        let instances = [instance];
+
+        let err = swap_configs(
+            &instances,
+            vec![config(
+                "[expressions]\ninterval = 30\n[expressions.ban]\nusernames = \
+                 [\"^spam\"]\n[inactive]\nenabled = true",
+            )],
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The `inactive` settings can't be changed without a restart"
+        );
+        // The rejected config isn't swapped in
+        assert_eq!(instances[0].config().expressions.interval, 60);
+
+        swap_configs(
+            &instances,
+            vec![config(
+                "[expressions]\ninterval = 30\n[expressions.ban]\nusernames = [\"^spam\"]",
+            )],
+        )
+        .unwrap();
+        assert_eq!(instances[0].config().expressions.interval, 30);
+    }
+}
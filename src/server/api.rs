@@ -0,0 +1,556 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
+
+//! The REST API, to query the guardian state and take the moderators actions
+//! programmatically. The API is protected by a bearer token.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{FromRequestParts, Path, Query, State},
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use super::{audit_log, ServerState};
+use crate::{
+    bots::{actions, webhook::ReasonDocument, Lang, UserAlert},
+    config::RegexReason,
+    db::{
+        AlertedUsersTableTrait,
+        AuditEntry,
+        AuditFilter,
+        IgnoredUsersTableTrait,
+        PurgedUsersTableTrait,
+    },
+    forgejo_api,
+    instance::{self, Instance},
+    users_fetcher::{self, Verdict},
+};
+
+/// The API name, as a notifier and a moderator of the decisions
+const NAME: &str = "api";
+/// The default maximum number of the listed actions
+const DEFAULT_ACTIONS_LIMIT: usize = 100;
+
+/// The API error, returned as `{"error": "<message>"}`
+pub struct ApiError(StatusCode, String);
+
+/// The API result
+type ApiResult<T> = Result<T, ApiError>;
+
+impl ApiError {
+    /// Create a new API error
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self(status, message.into())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        #[derive(Serialize)]
+        struct ErrorDocument {
+            error: String,
+        }
+
+        (self.0, Json(ErrorDocument { error: self.1 })).into_response()
+    }
+}
+
+/// An authorized API request, the request must have a valid bearer token
+pub struct Authorized;
+
+impl FromRequestParts<Arc<ServerState>> for Authorized {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<ServerState>,
+    ) -> Result<Self, Self::Rejection> {
+        let config = state.instances[0].config();
+        let api = config.server.api().expect("The API is enabled");
+        let token = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .unwrap_or_default();
+
+        // Compare the MACs, to compare the tokens in a constant time
+        let token_mac = |token: &str| {
+            let mut mac = Hmac::<Sha256>::new_from_slice(token.as_bytes())
+                .expect("HMAC can take key of any size");
+            mac.update(b"api-token");
+            mac
+        };
+        if token_mac(&api.token)
+            .verify_slice(&token_mac(token).finalize().into_bytes())
+            .is_err()
+        {
+            return Err(ApiError::new(
+                StatusCode::UNAUTHORIZED,
+                "Missing or invalid API token",
+            ));
+        }
+        Ok(Self)
+    }
+}
+
+/// The instance query, required if there are multiple instances
+#[derive(Deserialize)]
+pub struct InstanceQuery {
+    /// The instance name
+    instance: Option<String>,
+}
+
+/// The audit log query, all the filters are optional
+#[derive(Deserialize)]
+pub struct AuditQuery {
+    /// The instance name
+    instance:  Option<String>,
+    /// Only the actions on this username
+    username:  Option<String>,
+    /// Only the decisions of this moderator
+    moderator: Option<String>,
+    /// Only the actions since this time, RFC 3339
+    since:     Option<DateTime<Utc>>,
+    /// Only the actions until this time, RFC 3339
+    until:     Option<DateTime<Utc>>,
+    /// Maximum number of the actions, default [`DEFAULT_ACTIONS_LIMIT`]
+    limit:     Option<usize>,
+}
+
+/// A user in the instance
+#[derive(Serialize)]
+struct UserDocument<'a> {
+    instance: Option<&'a str>,
+    username: String,
+}
+
+/// A user in the purge queue
+#[derive(Serialize)]
+struct PurgeDocument<'a> {
+    instance: Option<&'a str>,
+    username: String,
+    /// When the user has been added to the purge queue
+    added_at: DateTime<Utc>,
+    /// When the user will be purged, on the first lazy purge after it
+    purge_at: DateTime<Utc>,
+}
+
+/// An audit log entry, with its instance
+#[derive(Serialize)]
+struct ActionDocument<'a> {
+    instance: Option<&'a str>,
+    #[serde(flatten)]
+    entry:    AuditEntry,
+}
+
+/// The verdict of checking a user against the expressions
+#[derive(Serialize)]
+struct CheckDocument<'a> {
+    instance:    Option<&'a str>,
+    username:    &'a str,
+    /// `ban`, `downgrade`, `sus`, `allow` or `clean`
+    verdict:     &'static str,
+    /// The matched expressions of the `allow` verdict, `ban` or `sus`
+    expressions: Option<&'static str>,
+    reasons:     Vec<ReasonDocument<'a>>,
+    allowed_by:  Vec<ReasonDocument<'a>>,
+    score:       Option<f64>,
+}
+
+/// Returns the queried instance
+fn queried_instance<'a>(state: &'a ServerState, query: &InstanceQuery) -> ApiResult<&'a Instance> {
+    instance::find(&state.instances, query.instance.as_deref()).ok_or_else(|| {
+        ApiError::new(
+            StatusCode::NOT_FOUND,
+            if query.instance.is_some() {
+                "Unknown instance"
+            } else {
+                "There are multiple instances, the `instance` query is required"
+            },
+        )
+    })
+}
+
+/// Returns the instances to list, the queried instance or all of them
+fn listed_instances<'a>(
+    state: &'a ServerState,
+    query: &InstanceQuery,
+) -> ApiResult<Vec<&'a Instance>> {
+    if query.instance.is_none() {
+        return Ok(state.instances.iter().collect());
+    }
+    queried_instance(state, query).map(|instance| vec![instance])
+}
+
+/// Returns the internal server error of the database error
+fn db_error(err: impl std::fmt::Display) -> ApiError {
+    tracing::error!("Failed to read the database: {err}");
+    ApiError::new(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Failed to read the database",
+    )
+}
+
+/// Returns the documents of the matched expressions
+fn reason_documents(reasons: &[RegexReason]) -> Vec<ReasonDocument<'_>> {
+    reasons.iter().map(ReasonDocument::from).collect()
+}
+
+/// Lists the users of the instances, returned by `users`
+fn list_users(
+    instances: Vec<&Instance>,
+    users: impl Fn(&Instance) -> ApiResult<Vec<String>>,
+) -> ApiResult<Vec<UserDocument<'_>>> {
+    let mut documents = Vec::new();
+    for instance in instances {
+        documents.extend(users(instance)?.into_iter().map(|username| {
+            UserDocument {
+                instance: instance.name(),
+                username,
+            }
+        }));
+    }
+    Ok(documents)
+}
+
+/// Lists the alerted users, who are waiting for a moderator decision
+pub async fn alerts_handler(
+    _: Authorized,
+    State(state): State<Arc<ServerState>>,
+    Query(query): Query<InstanceQuery>,
+) -> Response {
+    let instances = match listed_instances(&state, &query) {
+        Ok(instances) => instances,
+        Err(err) => return err.into_response(),
+    };
+    list_users(instances, |instance| {
+        let mut alerted = instance.database.alerted_users().map_err(db_error)?;
+        alerted.retain(|username| actions::can_ban(instance, username));
+        Ok(alerted)
+    })
+    .map(Json)
+    .into_response()
+}
+
+/// Lists the ignored users
+pub async fn ignored_handler(
+    _: Authorized,
+    State(state): State<Arc<ServerState>>,
+    Query(query): Query<InstanceQuery>,
+) -> Response {
+    let instances = match listed_instances(&state, &query) {
+        Ok(instances) => instances,
+        Err(err) => return err.into_response(),
+    };
+    list_users(instances, |instance| {
+        instance.database.ignored_users().map_err(db_error)
+    })
+    .map(Json)
+    .into_response()
+}
+
+/// Ignore the user, the user will not be alerted again
+pub async fn ignore_handler(
+    _: Authorized,
+    State(state): State<Arc<ServerState>>,
+    Path(username): Path<String>,
+    Query(query): Query<InstanceQuery>,
+) -> ApiResult<StatusCode> {
+    let instance = queried_instance(&state, &query)?;
+    actions::ignore(instance, &username, NAME, NAME);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Unignore the user, the user will be alerted again if they still match the
+/// expressions
+pub async fn unignore_handler(
+    _: Authorized,
+    State(state): State<Arc<ServerState>>,
+    Path(username): Path<String>,
+    Query(query): Query<InstanceQuery>,
+) -> ApiResult<StatusCode> {
+    let instance = queried_instance(&state, &query)?;
+    if !actions::can_unignore(instance, &username) {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "The user isn't ignored",
+        ));
+    }
+    actions::unignore(instance, &username, NAME, NAME);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Lists the users in the purge queue
+pub async fn purges_handler(
+    _: Authorized,
+    State(state): State<Arc<ServerState>>,
+    Query(query): Query<InstanceQuery>,
+) -> Response {
+    let instances = match listed_instances(&state, &query) {
+        Ok(instances) => instances,
+        Err(err) => return err.into_response(),
+    };
+
+    let mut documents = Vec::new();
+    for instance in instances {
+        let purged = match instance.database.purged_users() {
+            Ok(purged) => purged,
+            Err(err) => return db_error(err).into_response(),
+        };
+        let purge_after = u64::from(instance.config().lazy_purge.purge_after);
+        documents.extend(purged.into_iter().map(|(username, added_at)| {
+            let timestamp =
+                |secs: u64| DateTime::from_timestamp(secs as i64, 0).expect("A valid timestamp");
+            PurgeDocument {
+                instance: instance.name(),
+                username,
+                added_at: timestamp(added_at),
+                purge_at: timestamp(added_at + purge_after),
+            }
+        }));
+    }
+    Json(documents).into_response()
+}
+
+/// Cancel the user lazy purge, remove them from the purge queue
+pub async fn undo_handler(
+    _: Authorized,
+    State(state): State<Arc<ServerState>>,
+    Path(username): Path<String>,
+    Query(query): Query<InstanceQuery>,
+) -> ApiResult<StatusCode> {
+    let instance = queried_instance(&state, &query)?;
+    if !actions::can_undo(instance, &username) {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "The user isn't in the purge queue",
+        ));
+    }
+    actions::undo(instance, &username, NAME, NAME);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Send a ban request of the user to the notifiers, the same as the bots ban
+/// command
+pub async fn ban_request_handler(
+    _: Authorized,
+    State(state): State<Arc<ServerState>>,
+    Path(username): Path<String>,
+    Query(query): Query<InstanceQuery>,
+) -> ApiResult<StatusCode> {
+    let instance = queried_instance(&state, &query)?;
+
+    tracing::info!("The API requesting a ban request for `@{username}`");
+    let user = match forgejo_api::get_user(&username, &instance.config().forgejo).await {
+        Ok(user) => user,
+        Err(err) => {
+            tracing::error!("Failed to get the user `@{username}`: {err}");
+            return Err(ApiError::new(
+                StatusCode::NOT_FOUND,
+                "There is no user with this username",
+            ));
+        }
+    };
+    if user.is_admin {
+        return Err(ApiError::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "The user is an admin, admins can't be banned",
+        ));
+    }
+    if !actions::can_ban(instance, &username) {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "The user is already in the purge queue",
+        ));
+    }
+
+    let alert = UserAlert::new(
+        user,
+        vec![RegexReason::new(
+            Vec::new(),
+            // The reason is shared between the notifiers, so it's in the default language
+            Some(t!("messages.ban_api_reason", locale = Lang::default().as_str()).into_owned()),
+        )],
+    )
+    .is_active(true);
+    if instance.ban_sender.send(alert).await.is_err() {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            "There is no notifier to send the ban request to",
+        ));
+    }
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Check the user against the expressions without taking any action, returns
+/// the verdict
+pub async fn check_handler(
+    _: Authorized,
+    State(state): State<Arc<ServerState>>,
+    Path(username): Path<String>,
+    Query(query): Query<InstanceQuery>,
+) -> Response {
+    let instance = match queried_instance(&state, &query) {
+        Ok(instance) => instance,
+        Err(err) => return err.into_response(),
+    };
+    let user = match forgejo_api::get_user(&username, &instance.config().forgejo).await {
+        Ok(user) => user,
+        Err(err) => {
+            tracing::error!("Failed to get the user `@{username}`: {err}");
+            return ApiError::new(StatusCode::NOT_FOUND, "There is no user with this username")
+                .into_response();
+        }
+    };
+
+    let (user, verdict) =
+        users_fetcher::dry_check_user(user, &Client::new(), &instance.config()).await;
+    let empty = Vec::new();
+    let (verdict, expressions, reasons, allowed_by, score) = match &verdict {
+        Verdict::Ban { reasons, score } => ("ban", None, reasons, &empty, *score),
+        Verdict::Downgrade {
+            reasons,
+            score,
+            allowed_by,
+        } => ("downgrade", None, reasons, allowed_by, *score),
+        Verdict::Sus { reasons, score } => ("sus", None, reasons, &empty, *score),
+        Verdict::Allow {
+            expressions,
+            reasons,
+            score,
+            allowed_by,
+        } => ("allow", Some(*expressions), reasons, allowed_by, *score),
+        Verdict::Clean => ("clean", None, &empty, &empty, None),
+    };
+
+    Json(CheckDocument {
+        instance: instance.name(),
+        username: &user.username,
+        verdict,
+        expressions,
+        reasons: reason_documents(reasons),
+        allowed_by: reason_documents(allowed_by),
+        score,
+    })
+    .into_response()
+}
+
+/// Lists the audit log, the guardian and the moderators actions, newest first
+pub async fn actions_handler(
+    _: Authorized,
+    State(state): State<Arc<ServerState>>,
+    Query(query): Query<AuditQuery>,
+) -> ApiResult<Response> {
+    let instances = listed_instances(
+        &state,
+        &InstanceQuery {
+            instance: query.instance,
+        },
+    )?;
+    let filter = AuditFilter {
+        username:  query.username,
+        moderator: query.moderator,
+        since:     query.since,
+        until:     query.until,
+        limit:     Some(query.limit.unwrap_or(DEFAULT_ACTIONS_LIMIT)),
+    };
+
+    let documents: Vec<_> = audit_log(&instances, &filter)
+        .map_err(db_error)?
+        .into_iter()
+        .map(|(instance, entry)| {
+            ActionDocument {
+                instance: instance.name(),
+                entry,
+            }
+        })
+        .collect();
+    Ok(Json(documents).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use axum::http::Request;
+    use redb::{backends::InMemoryBackend, Database};
+
+    use super::*;
+    use crate::{config::Config, db::GuardDb};
+
+    fn state() -> Arc<ServerState> {
+        let config: Config = toml::from_str(
+            r#"
+            [forgejo]
+            instance_url = "https://forgejo.example"
+            token = "token"
+
+            [server]
+            enabled = true
+
+            [server.api]
+            token = "s3cret-token"
+            "#,
+        )
+        .unwrap();
+        let db = Database::builder()
+            .create_with_backend(InMemoryBackend::new())
+            .unwrap();
+        Arc::new(ServerState {
+            instances: vec![Instance::new(
+                config,
+                GuardDb::new(Arc::new(db), None).unwrap(),
+            )],
+            webhooks:  HashMap::new(),
+        })
+    }
+
+    /// Returns the rejection status of the request with the authorization
+    /// header, `None` if it's authorized
+    async fn rejection(
+        state: &Arc<ServerState>,
+        authorization: Option<&str>,
+    ) -> Option<StatusCode> {
+        let mut request = Request::builder();
+        if let Some(authorization) = authorization {
+            request = request.header(header::AUTHORIZATION, authorization);
+        }
+        let (mut parts, ()) = request.body(()).unwrap().into_parts();
+        Authorized::from_request_parts(&mut parts, state)
+            .await
+            .err()
+            .map(|err| err.0)
+    }
+
+    #[tokio::test]
+    async fn bearer_token() {
+        let state = state();
+
+        assert_eq!(rejection(&state, Some("Bearer s3cret-token")).await, None);
+        for authorization in [
+            None,
+            Some(""),
+            Some("Bearer "),
+            Some("Bearer other"),
+            Some("Bearer s3cret-token2"),
+            Some("bearer s3cret-token"),
+            Some("Basic s3cret-token"),
+            Some("s3cret-token"),
+        ] {
+            assert_eq!(
+                rejection(&state, authorization).await,
+                Some(StatusCode::UNAUTHORIZED),
+                "{authorization:?} should be rejected"
+            );
+        }
+    }
+}
@@ -0,0 +1,447 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
+
+//! The admin dashboard, lists the pending alerts, the purge queue, the ignored
+//! users and the recent actions of the instances, with buttons to take an
+//! action on them. The dashboard is protected by the admin token, the login
+//! sets a session cookie signed with the token, it expires after
+//! [`SESSION_MAX_AGE`].
+
+use std::{collections::HashSet, sync::Arc};
+
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{AppendHeaders, IntoResponse, Redirect, Response},
+    Form,
+};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+
+use super::{audit_log, escape_html, page, ServerState};
+use crate::{
+    bots::{action_word, actions},
+    config::{Config, Dashboard},
+    db::{
+        Actor,
+        AlertedUsersTableTrait,
+        AuditFilter,
+        IgnoredUsersTableTrait,
+        PurgedUsersTableTrait,
+    },
+    instance::{self, Instance},
+};
+
+/// The dashboard name, as a notifier and a moderator of the decisions
+const NAME: &str = "dashboard";
+/// The session cookie name
+const SESSION_COOKIE: &str = "guardian_dashboard";
+/// The session lifetime in seconds
+const SESSION_MAX_AGE: i64 = 12 * 60 * 60;
+/// Maximum number of the recent actions to show
+const MAX_RECENT_ACTIONS: usize = 50;
+
+/// The login form
+#[derive(Deserialize)]
+pub struct LoginForm {
+    /// The admin token
+    token: String,
+}
+
+/// The action form
+#[derive(Deserialize)]
+pub struct ActionForm {
+    /// The user id, see [`Instance::user_id`]
+    user: String,
+}
+
+/// Returns the dashboard configuration of the config, the dashboard must be
+/// enabled
+fn dashboard_config(config: &Config) -> &Dashboard {
+    config.server.dashboard().expect("The dashboard is enabled")
+}
+
+/// Returns the session MAC of the token, with the session issue time
+fn session_mac(token: &str, issued_at: i64) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(token.as_bytes()).expect("HMAC can take key of any size");
+    mac.update(format!("dashboard-session\n{issued_at}").as_bytes());
+    mac
+}
+
+/// Returns `true` if the session is signed with the token and isn't expired.
+/// The session is the issue time and the hex encoded MAC, separated by a dot
+fn is_valid_session(token: &str, session: &str) -> bool {
+    let Some((issued_at, signature)) = session.split_once('.') else {
+        return false;
+    };
+    let (Ok(issued_at), Ok(signature)) = (issued_at.parse::<i64>(), hex::decode(signature)) else {
+        return false;
+    };
+    let age = Utc::now().timestamp() - issued_at;
+    (0..SESSION_MAX_AGE).contains(&age)
+        && session_mac(token, issued_at)
+            .verify_slice(&signature)
+            .is_ok()
+}
+
+/// Returns `true` if the request is served over TLS, by the reverse proxy
+/// `X-Forwarded-Proto` header
+fn is_https(headers: &HeaderMap) -> bool {
+    headers
+        .get("x-forwarded-proto")
+        .and_then(|proto| proto.to_str().ok())
+        .is_some_and(|proto| proto.eq_ignore_ascii_case("https"))
+}
+
+/// Returns the dashboard language
+fn dashboard_lang(state: &ServerState) -> &'static str {
+    dashboard_config(&state.instances[0].config()).lang.as_str()
+}
+
+/// Returns `true` if the request has a valid session cookie
+fn is_authorized(state: &ServerState, headers: &HeaderMap) -> bool {
+    let config = state.instances[0].config();
+    let dashboard = dashboard_config(&config);
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|cookies| cookies.split(';'))
+        .filter_map(|cookie| cookie.trim().strip_prefix(&format!("{SESSION_COOKIE}=")))
+        .any(|session| is_valid_session(&dashboard.token, session))
+}
+
+/// Returns the login page, with an optional error
+fn login_page(lang: &str, error: Option<&str>) -> Response {
+    let error = error
+        .map(|error| format!("<p>{}</p>", escape_html(error)))
+        .unwrap_or_default();
+    page(&format!(
+        "<h1>{}</h1>{error}<form method=\"post\" action=\"/dashboard/login\"><input \
+         type=\"password\" name=\"token\" placeholder=\"{}\" required> <button \
+         type=\"submit\">{}</button></form>",
+        escape_html(&t!("dashboard.title", locale = lang)),
+        escape_html(&t!("dashboard.token", locale = lang)),
+        escape_html(&t!("dashboard.login", locale = lang)),
+    ))
+    .into_response()
+}
+
+/// Returns an HTML form that posts the action on the user
+fn action_form(action: &str, button: &str, user_id: &str) -> String {
+    format!(
+        "<form method=\"post\" action=\"/dashboard/{action}\"><input type=\"hidden\" \
+         name=\"user\" value=\"{}\"><button type=\"submit\">{}</button></form>",
+        escape_html(user_id),
+        escape_html(button)
+    )
+}
+
+/// Returns an HTML table of the rows, or the empty message if there is no rows
+fn table(lang: &str, rows: Vec<String>) -> String {
+    if rows.is_empty() {
+        return format!(
+            "<p>{}</p>",
+            escape_html(&t!("dashboard.empty", locale = lang))
+        );
+    }
+    format!("<table>{}</table>", rows.concat())
+}
+
+/// Returns a human readable duration, e.g. `1d 2h 3m`
+fn human_duration(secs: u64) -> String {
+    let (days, hours, minutes) = (secs / 86400, secs % 86400 / 3600, secs % 3600 / 60);
+    match (days, hours) {
+        (0, 0) => format!("{minutes}m"),
+        (0, _) => format!("{hours}h {minutes}m"),
+        _ => format!("{days}d {hours}h {minutes}m"),
+    }
+}
+
+/// Returns a link to the user profile
+fn user_link(instance: &Instance, username: &str) -> String {
+    let profile = instance
+        .config()
+        .forgejo
+        .instance
+        .join(username)
+        .map(String::from)
+        .unwrap_or_default();
+    format!(
+        "<a href=\"{}\">@{}</a>",
+        escape_html(&profile),
+        escape_html(username)
+    )
+}
+
+/// Returns the instance section of the dashboard
+fn instance_section(lang: &str, instance: &Instance) -> String {
+    let database = &instance.database;
+    let now = Utc::now().timestamp().max(0) as u64;
+    let purge_after = u64::from(instance.config().lazy_purge.purge_after);
+
+    let mut purged = database.purged_users().unwrap_or_default();
+    purged.sort_unstable_by_key(|(_, purged_at)| *purged_at);
+    let purged_usernames: HashSet<_> = purged.iter().map(|(username, _)| username).collect();
+
+    let mut alerted = database.alerted_users().unwrap_or_default();
+    alerted.retain(|username| !purged_usernames.contains(username));
+    let alerted = alerted
+        .iter()
+        .map(|username| {
+            let user_id = instance.user_id(username);
+            let ban_action = action_word(lang, &actions::ban_action(instance, username));
+            format!(
+                "<tr><td>{}</td><td>{} {}</td></tr>",
+                user_link(instance, username),
+                action_form(
+                    "ban",
+                    &t!("buttons.ban", locale = lang, action = ban_action),
+                    &user_id
+                ),
+                action_form("ignore", &t!("buttons.ignore", locale = lang), &user_id),
+            )
+        })
+        .collect();
+
+    let purged = purged
+        .iter()
+        .map(|(username, purged_at)| {
+            let remaining = (purged_at + purge_after).saturating_sub(now);
+            let remaining = if remaining == 0 {
+                t!("dashboard.overdue", locale = lang)
+            } else {
+                t!(
+                    "dashboard.remaining",
+                    locale = lang,
+                    remaining = human_duration(remaining)
+                )
+            };
+            format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                user_link(instance, username),
+                escape_html(&remaining),
+                action_form(
+                    "undo",
+                    &t!("buttons.undo", locale = lang),
+                    &instance.user_id(username)
+                ),
+            )
+        })
+        .collect();
+
+    let ignored = database
+        .ignored_users()
+        .unwrap_or_default()
+        .iter()
+        .map(|username| {
+            format!(
+                "<tr><td>{}</td><td>{}</td></tr>",
+                user_link(instance, username),
+                action_form(
+                    "unignore",
+                    &t!("buttons.unignore", locale = lang),
+                    &instance.user_id(username)
+                ),
+            )
+        })
+        .collect();
+
+    let name = instance
+        .name()
+        .map(|name| format!("<h2>{}</h2>", escape_html(name)))
+        .unwrap_or_default();
+    format!(
+        "{name}<h3>{}</h3>{}<h3>{}</h3>{}<h3>{}</h3>{}",
+        escape_html(&t!("dashboard.pending_alerts", locale = lang)),
+        table(lang, alerted),
+        escape_html(&t!("dashboard.purge_queue", locale = lang)),
+        table(lang, purged),
+        escape_html(&t!("dashboard.ignored_users", locale = lang)),
+        table(lang, ignored),
+    )
+}
+
+/// Returns the recent actions section of the dashboard
+fn recent_section(lang: &str, state: &ServerState) -> String {
+    let has_names = state.instances[0].name().is_some();
+    let header = format!(
+        "<tr><th>{}</th>{}<th>{}</th><th>{}</th><th>{}</th></tr>",
+        escape_html(&t!("dashboard.time", locale = lang)),
+        if has_names {
+            format!(
+                "<th>{}</th>",
+                escape_html(&t!("dashboard.instance", locale = lang))
+            )
+        } else {
+            String::new()
+        },
+        escape_html(&t!("dashboard.username", locale = lang)),
+        escape_html(&t!("dashboard.decision", locale = lang)),
+        escape_html(&t!("dashboard.moderator", locale = lang)),
+    );
+
+    let filter = AuditFilter {
+        limit: Some(MAX_RECENT_ACTIONS),
+        ..Default::default()
+    };
+    let instances: Vec<_> = state.instances.iter().collect();
+    let rows: Vec<_> = audit_log(&instances, &filter)
+        .unwrap_or_default()
+        .iter()
+        .map(|(instance, entry)| {
+            let instance = instance
+                .name()
+                .map(|name| format!("<td>{}</td>", escape_html(name)))
+                .unwrap_or_default();
+            let actor = match &entry.actor {
+                Actor::Guardian => t!("words.guardian", locale = lang).into_owned(),
+                Actor::Moderator { name, notifier } => format!("{name} ({notifier})"),
+            };
+            format!(
+                "<tr><td>{}</td>{instance}<td>@{}</td><td>{}</td><td>{}</td></tr>",
+                entry.time.format("%Y-%m-%d %H:%M:%S UTC"),
+                escape_html(&entry.username),
+                escape_html(&entry.action.message(lang)),
+                escape_html(&actor),
+            )
+        })
+        .collect();
+
+    let table = if rows.is_empty() {
+        table(lang, rows)
+    } else {
+        table(lang, [vec![header], rows].concat())
+    };
+    format!(
+        "<h2>{}</h2>{table}",
+        escape_html(&t!("dashboard.recent_actions", locale = lang))
+    )
+}
+
+/// Shows the dashboard, or the login page if the request isn't authorized
+pub async fn handler(State(state): State<Arc<ServerState>>, headers: HeaderMap) -> Response {
+    let lang = dashboard_lang(&state);
+    if !is_authorized(&state, &headers) {
+        return login_page(lang, None);
+    }
+
+    let instances: String = state
+        .instances
+        .iter()
+        .map(|instance| instance_section(lang, instance))
+        .collect();
+    page(&format!(
+        "<h1>{}</h1><form method=\"post\" action=\"/dashboard/logout\"><button \
+         type=\"submit\">{}</button></form>{instances}{}",
+        escape_html(&t!("dashboard.title", locale = lang)),
+        escape_html(&t!("dashboard.logout", locale = lang)),
+        recent_section(lang, &state),
+    ))
+    .into_response()
+}
+
+/// Log in to the dashboard, sets the session cookie if the token is valid
+pub async fn login_handler(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Form(form): Form<LoginForm>,
+) -> Response {
+    let issued_at = Utc::now().timestamp();
+    let session = session_mac(&form.token, issued_at).finalize().into_bytes();
+    if session_mac(
+        &dashboard_config(&state.instances[0].config()).token,
+        issued_at,
+    )
+    .verify_slice(&session)
+    .is_err()
+    {
+        tracing::warn!("Received a dashboard login with an invalid token");
+        let lang = dashboard_lang(&state);
+        let mut response = login_page(lang, Some(&t!("dashboard.invalid_token", locale = lang)));
+        *response.status_mut() = StatusCode::UNAUTHORIZED;
+        return response;
+    }
+
+    (
+        AppendHeaders([(
+            header::SET_COOKIE,
+            format!(
+                "{SESSION_COOKIE}={issued_at}.{}; Path=/dashboard; HttpOnly; SameSite=Strict; \
+                 Max-Age={SESSION_MAX_AGE}{}",
+                hex::encode(session),
+                if is_https(&headers) { "; Secure" } else { "" }
+            ),
+        )]),
+        Redirect::to("/dashboard"),
+    )
+        .into_response()
+}
+
+/// Log out of the dashboard, removes the session cookie
+pub async fn logout_handler() -> Response {
+    (
+        AppendHeaders([(
+            header::SET_COOKIE,
+            format!("{SESSION_COOKIE}=; Path=/dashboard; HttpOnly; SameSite=Strict; Max-Age=0"),
+        )]),
+        Redirect::to("/dashboard"),
+    )
+        .into_response()
+}
+
+/// Takes the action on the user, then redirects to the dashboard. Shows the
+/// failure message if the action failed or isn't applicable
+pub async fn action_handler(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Path(action): Path<String>,
+    Form(form): Form<ActionForm>,
+) -> Response {
+    if !is_authorized(&state, &headers) {
+        return Redirect::to("/dashboard").into_response();
+    }
+    let (instance_name, username) = instance::split_user_id(&form.user);
+    let Some(instance) = instance::find(&state.instances, instance_name) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let lang = dashboard_lang(&state);
+
+    let failure = match action.as_str() {
+        "ban" if actions::can_ban(instance, username) => {
+            actions::ban(instance, username, NAME, NAME)
+                .await
+                .is_none()
+                .then(|| t!("messages.ban_failed", locale = lang))
+        }
+        "ignore" => {
+            actions::ignore(instance, username, NAME, NAME);
+            None
+        }
+        "undo" if actions::can_undo(instance, username) => {
+            actions::undo(instance, username, NAME, NAME);
+            None
+        }
+        "unignore" if actions::can_unignore(instance, username) => {
+            actions::unignore(instance, username, NAME, NAME);
+            None
+        }
+        "ban" | "undo" | "unignore" => Some(t!("email.not_applicable", locale = lang)),
+        _ => return StatusCode::NOT_FOUND.into_response(),
+    };
+
+    match failure {
+        Some(msg) => {
+            page(&format!(
+                "<p>{}</p><a href=\"/dashboard\">{}</a>",
+                escape_html(&msg),
+                escape_html(&t!("dashboard.title", locale = lang))
+            ))
+            .into_response()
+        }
+        None => Redirect::to("/dashboard").into_response(),
+    }
+}
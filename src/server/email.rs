@@ -0,0 +1,166 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
+
+//! The emails links endpoint, the link shows a confirmation page to not be
+//! triggered by the emails links scanners, then the confirmation takes the
+//! action on the user.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Html,
+};
+use serde::Deserialize;
+
+use super::{escape_html, page, ServerState};
+use crate::{
+    bots::{
+        action_word,
+        actions,
+        email::{self, EmailNotifier, LinkAction},
+        notifier::Notifier,
+        DecisionKind,
+    },
+    instance::{self, Instance},
+};
+
+/// The signed link query
+#[derive(Deserialize)]
+pub struct LinkQuery {
+    /// The user id, see [`Instance::user_id`]
+    user:      String,
+    /// The email recipient of the link, the moderator who takes the action
+    to:        String,
+    /// The link expiration timestamp
+    expires:   i64,
+    /// The link signature
+    signature: String,
+}
+
+/// Returns an HTML form that posts to the link
+fn link_form(button: &str, link: Option<&str>) -> String {
+    let action = link
+        .map(|link| format!(" action=\"{}\"", escape_html(link)))
+        .unwrap_or_default();
+    format!(
+        "<form method=\"post\"{action}><button type=\"submit\">{}</button></form>",
+        escape_html(button)
+    )
+}
+
+/// Returns the link instance if the link is valid, otherwise returns the error
+/// page
+fn verify<'a>(
+    state: &'a ServerState,
+    action: &str,
+    query: &LinkQuery,
+) -> Result<(&'a Instance, LinkAction), (StatusCode, Html<String>)> {
+    let Some(action) = LinkAction::from_path(action) else {
+        return Err((StatusCode::NOT_FOUND, page("")));
+    };
+    let (instance_name, _) = instance::split_user_id(&query.user);
+    let Some(instance) = instance::find(&state.instances, instance_name)
+        .filter(|instance| instance.config().email().is_some())
+    else {
+        return Err((StatusCode::NOT_FOUND, page("")));
+    };
+
+    let config = instance.config();
+    let email = email::email_config(&config);
+    if !email::is_valid_link(
+        email,
+        action,
+        &query.user,
+        &query.to,
+        query.expires,
+        &query.signature,
+    ) {
+        tracing::warn!("Received an invalid or expired email link");
+        return Err((
+            StatusCode::FORBIDDEN,
+            page(&format!(
+                "<p>{}</p>",
+                escape_html(&t!("email.invalid_link", locale = email.lang.as_str()))
+            )),
+        ));
+    }
+
+    Ok((instance, action))
+}
+
+/// Shows the confirmation page of the link action
+pub async fn confirm_handler(
+    State(state): State<Arc<ServerState>>,
+    Path(action): Path<String>,
+    Query(query): Query<LinkQuery>,
+) -> (StatusCode, Html<String>) {
+    let (instance, action) = match verify(&state, &action, &query) {
+        Ok(link) => link,
+        Err(err) => return err,
+    };
+    let (_, username) = instance::split_user_id(&query.user);
+    let lang = email::email_config(&instance.config()).lang.as_str();
+    let ban_action = action_word(lang, &actions::ban_action(instance, username));
+
+    (
+        StatusCode::OK,
+        page(&format!(
+            "<p>{}</p>{}",
+            escape_html(&t!("email.confirm", locale = lang, username = username)),
+            link_form(&action.button(lang, &ban_action), None)
+        )),
+    )
+}
+
+/// Takes the link action on the user, the same as the bots buttons
+pub async fn action_handler(
+    State(state): State<Arc<ServerState>>,
+    Path(action): Path<String>,
+    Query(query): Query<LinkQuery>,
+) -> (StatusCode, Html<String>) {
+    let (instance, action) = match verify(&state, &action, &query) {
+        Ok(link) => link,
+        Err(err) => return err,
+    };
+    let (_, username) = instance::split_user_id(&query.user);
+    let lang = email::email_config(&instance.config()).lang.as_str();
+    let moderator = query.to.as_str();
+
+    let (msg, undo_link) = match action {
+        LinkAction::Ban if actions::can_ban(instance, username) => {
+            match actions::ban(instance, username, moderator, <EmailNotifier>::NAME).await {
+                Some(kind) => {
+                    let undo_link = (kind == DecisionKind::AddedToPurgeQueue).then(|| {
+                        email::action_link(
+                            email::email_config(&instance.config()),
+                            LinkAction::Undo,
+                            &query.user,
+                            &query.to,
+                        )
+                    });
+                    (kind.message(lang), undo_link)
+                }
+                None => (t!("messages.ban_failed", locale = lang), None),
+            }
+        }
+        LinkAction::Ignore if actions::can_ignore(instance, username) => {
+            actions::ignore(instance, username, moderator, <EmailNotifier>::NAME);
+            (DecisionKind::Ignored.message(lang), None)
+        }
+        LinkAction::Undo if actions::can_undo(instance, username) => {
+            actions::undo(instance, username, moderator, <EmailNotifier>::NAME);
+            (DecisionKind::Undone.message(lang), None)
+        }
+        _ => (t!("email.not_applicable", locale = lang), None),
+    };
+
+    let undo_form = undo_link
+        .map(|link| link_form(&LinkAction::Undo.button(lang, ""), Some(link.as_str())))
+        .unwrap_or_default();
+    (
+        StatusCode::OK,
+        page(&format!("<p>{}</p>{undo_form}", escape_html(&msg))),
+    )
+}
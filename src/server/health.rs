@@ -0,0 +1,85 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
+
+//! The health and readiness endpoints, to let the orchestrator restart the
+//! guardian when one of its critical tasks is dead.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use url::Url;
+
+use super::ServerState;
+use crate::health::{TaskState, HEALTH};
+
+/// The Forgejo instance state
+#[derive(Serialize)]
+struct ForgejoDocument<'a> {
+    /// The instance name, `None` for the unnamed instance (single instance)
+    instance:     Option<&'a str>,
+    /// The instance URL
+    url:          Url,
+    /// The last successful request to the instance
+    last_success: Option<DateTime<Utc>>,
+}
+
+/// The guardian health report
+#[derive(Serialize)]
+struct HealthDocument<'a> {
+    /// There is no dead critical task
+    healthy: bool,
+    /// The guardian is healthy and each instance had a successful request
+    ready:   bool,
+    /// The spawned tasks
+    tasks:   Vec<TaskState>,
+    /// The guarded Forgejo instances
+    forgejo: Vec<ForgejoDocument<'a>>,
+}
+
+/// Returns the health report, with `OK` status if `is_ok` returns `true`,
+/// otherwise `SERVICE_UNAVAILABLE`
+fn report(state: &ServerState, is_ok: fn(&HealthDocument) -> bool) -> Response {
+    let forgejo: Vec<_> = state
+        .instances
+        .iter()
+        .map(|instance| {
+            ForgejoDocument {
+                instance:     instance.name(),
+                url:          instance.config().forgejo.instance.clone(),
+                last_success: HEALTH.forgejo_last_success(&instance.config().forgejo.instance),
+            }
+        })
+        .collect();
+    let healthy = HEALTH.is_healthy();
+    let document = HealthDocument {
+        healthy,
+        ready: healthy && forgejo.iter().all(|f| f.last_success.is_some()),
+        tasks: HEALTH.tasks(),
+        forgejo,
+    };
+
+    let status = if is_ok(&document) {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(document)).into_response()
+}
+
+/// The liveness endpoint, fails if a critical task is dead
+pub async fn healthz_handler(State(state): State<Arc<ServerState>>) -> Response {
+    report(&state, |document| document.healthy)
+}
+
+/// The readiness endpoint, fails if the guardian is not healthy or an instance
+/// has not been reached yet
+pub async fn readyz_handler(State(state): State<Arc<ServerState>>) -> Response {
+    report(&state, |document| document.ready)
+}
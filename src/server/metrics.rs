@@ -0,0 +1,31 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
+
+//! The Prometheus metrics endpoint, in the OpenMetrics text format.
+
+use std::sync::Arc;
+
+use axum::{extract::State, http::header, response::IntoResponse};
+
+use super::ServerState;
+use crate::{
+    db::PurgedUsersTableTrait,
+    metrics::{InstanceLabels, METRICS},
+};
+
+/// The OpenMetrics text format content type
+const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Exports the metrics, the purge queue size is updated on each scrape
+pub async fn handler(State(state): State<Arc<ServerState>>) -> impl IntoResponse {
+    for instance in &state.instances {
+        if let Ok(purged_users) = instance.database.purged_users() {
+            METRICS
+                .purge_queue_size
+                .get_or_create(&InstanceLabels::new(&instance.config()))
+                .set(purged_users.len() as i64);
+        }
+    }
+
+    ([(header::CONTENT_TYPE, CONTENT_TYPE)], METRICS.encode())
+}
@@ -0,0 +1,197 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
+
+//! The embedded HTTP server, it receives the Forgejo webhooks and serves the
+//! emails links, the admin dashboard, the REST API, the metrics and the health
+//! endpoints.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    response::Html,
+    routing::{delete, get, post, put},
+    Router,
+};
+use tokio::{net::TcpListener, sync::mpsc::Sender};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    db::{AuditEntry, AuditFilter, AuditLogTableTrait},
+    error::GuardResult,
+    instance::Instance,
+};
+
+mod api;
+mod dashboard;
+mod email;
+mod health;
+mod metrics;
+mod webhook;
+
+/// The webhooks queue of an instance
+struct WebhookQueue {
+    /// The instance of the webhooks
+    instance: Instance,
+    /// Sender of the usernames to be checked by the webhook worker
+    sender:   Sender<String>,
+    /// The usernames that are waiting to be checked, to not check the same
+    /// user twice for a burst of events
+    queued:   Arc<Mutex<HashSet<String>>>,
+}
+
+/// The shared state of the server handlers
+pub struct ServerState {
+    /// The guarded instances
+    instances: Vec<Instance>,
+    /// The webhooks queues, the key is the instance name, the unnamed instance
+    /// (single instance) has no name
+    webhooks:  HashMap<Option<String>, WebhookQueue>,
+}
+
+/// Escape the HTML special characters
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Returns an HTML page with the given body, the body must be escaped
+fn page(body: &str) -> Html<String> {
+    Html(format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><meta name=\"viewport\" \
+         content=\"width=device-width, initial-scale=1\"><title>Forgejo \
+         Guardian</title><style>{STYLE}</style></head><body>{body}</body></html>"
+    ))
+}
+
+/// The pages style
+const STYLE: &str = "body{font-family:sans-serif;margin:2em auto;max-width:60em;padding:0 \
+                     1em}table{border-collapse:collapse;width:100%}td,th{border-bottom:1px solid \
+                     #ccc;padding:.4em;text-align:start}form{display:inline}";
+
+/// Returns the audit log entries of the instances that match the filter, with
+/// their instance, newest first
+fn audit_log<'a>(
+    instances: &[&'a Instance],
+    filter: &AuditFilter,
+) -> GuardResult<Vec<(&'a Instance, AuditEntry)>> {
+    let mut entries = Vec::new();
+    for instance in instances {
+        entries.extend(
+            instance
+                .database
+                .audit_log(filter)?
+                .into_iter()
+                .map(|entry| (*instance, entry)),
+        );
+    }
+    entries.sort_by(|(_, a), (_, b)| b.time.cmp(&a.time));
+    entries.truncate(filter.limit.unwrap_or(usize::MAX));
+    Ok(entries)
+}
+
+/// Run the HTTP server, until the cancellation token is cancelled
+pub async fn run(
+    instances: Vec<Instance>,
+    cancellation_token: CancellationToken,
+) -> GuardResult<()> {
+    let listener = TcpListener::bind(instances[0].config().server.listen).await?;
+    let mut webhooks = HashMap::new();
+
+    for instance in instances.iter().cloned() {
+        if instance.config().server.webhook().is_none() {
+            continue;
+        }
+        let path = match instance.name() {
+            Some(name) => format!("/webhook/{name}"),
+            None => "/webhook".to_owned(),
+        };
+        tracing::info!("Webhooks receiver enabled on `{path}`");
+
+        let (sender, receiver) = tokio::sync::mpsc::channel(100);
+        let queued = Arc::new(Mutex::new(HashSet::new()));
+        crate::health::spawn(
+            "webhook_worker",
+            instance.name().map(String::from),
+            false,
+            &cancellation_token,
+            webhook::worker(
+                instance.clone(),
+                receiver,
+                Arc::clone(&queued),
+                cancellation_token.clone(),
+            ),
+        );
+        webhooks.insert(
+            instance.name().map(String::from),
+            WebhookQueue {
+                instance,
+                sender,
+                queued,
+            },
+        );
+    }
+
+    if instances.iter().any(|i| i.config().email().is_some()) {
+        tracing::info!("Emails links enabled on `/email/{{action}}`");
+    }
+
+    let mut router = Router::new()
+        .route("/healthz", get(health::healthz_handler))
+        .route("/readyz", get(health::readyz_handler))
+        .route("/webhook", post(webhook::handler))
+        .route("/webhook/{instance}", post(webhook::instance_handler))
+        .route(
+            "/email/{action}",
+            get(email::confirm_handler).post(email::action_handler),
+        );
+
+    let server_config = &instances[0].config().server;
+    if server_config.dashboard().is_some() {
+        tracing::info!("Dashboard enabled on `/dashboard`");
+        router = router
+            .route("/dashboard", get(dashboard::handler))
+            .route("/dashboard/login", post(dashboard::login_handler))
+            .route("/dashboard/logout", post(dashboard::logout_handler))
+            .route("/dashboard/{action}", post(dashboard::action_handler));
+    }
+    if server_config.api().is_some() {
+        tracing::info!("API enabled on `/api`");
+        router = router
+            .route("/api/alerts", get(api::alerts_handler))
+            .route("/api/ignored", get(api::ignored_handler))
+            .route(
+                "/api/ignored/{username}",
+                put(api::ignore_handler).delete(api::unignore_handler),
+            )
+            .route("/api/purges", get(api::purges_handler))
+            .route("/api/purges/{username}", delete(api::undo_handler))
+            .route(
+                "/api/ban-requests/{username}",
+                post(api::ban_request_handler),
+            )
+            .route("/api/check/{username}", get(api::check_handler))
+            .route("/api/actions", get(api::actions_handler));
+    }
+    if server_config.metrics {
+        tracing::info!("Metrics enabled on `/metrics`");
+        router = router.route("/metrics", get(metrics::handler));
+    }
+
+    let router = router.with_state(Arc::new(ServerState {
+        instances,
+        webhooks,
+    }));
+
+    tracing::info!("Listening on http://{}", listener.local_addr()?);
+    axum::serve(listener, router)
+        .with_graceful_shutdown(cancellation_token.cancelled_owned())
+        .await?;
+
+    tracing::info!("The server has been stopped successfully.");
+    Ok(())
+}
@@ -0,0 +1,318 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
+
+//! Forgejo webhooks receiver, checks the sender of the webhook events
+//! instantly instead of waiting for the users fetcher.
+
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+};
+use serde::Deserialize;
+use tokio::sync::mpsc::Receiver;
+use tokio_util::sync::CancellationToken;
+
+use super::ServerState;
+use crate::{forgejo_api, instance::Instance, users_fetcher, utils};
+
+/// The signature headers, Forgejo sends the Gitea header too
+const SIGNATURE_HEADERS: [&str; 2] = ["X-Forgejo-Signature", "X-Gitea-Signature"];
+/// The event headers, Forgejo sends the Gitea header too
+const EVENT_HEADERS: [&str; 2] = ["X-Forgejo-Event", "X-Gitea-Event"];
+
+/// The sender of the webhook event
+#[derive(Deserialize)]
+struct EventSender {
+    /// The sender username
+    login: String,
+}
+
+/// The webhook event payload, only the needed fields
+#[derive(Deserialize)]
+struct EventPayload {
+    /// The user who triggered the event
+    sender: Option<EventSender>,
+}
+
+/// Returns the first present header value of the given headers
+fn header<'a>(headers: &'a HeaderMap, names: &[&str]) -> Option<&'a str> {
+    names
+        .iter()
+        .find_map(|name| headers.get(*name))
+        .and_then(|value| value.to_str().ok())
+}
+
+/// The webhook handler of the unnamed instance (single instance), see
+/// [`handle`]
+pub async fn handler(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    handle(&state, None, &headers, &body)
+}
+
+/// The webhook handler of the named instances, see [`handle`]
+pub async fn instance_handler(
+    State(state): State<Arc<ServerState>>,
+    Path(instance): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    handle(&state, Some(instance), &headers, &body)
+}
+
+/// Verifies the webhook signature and sends the event sender to the instance
+/// worker to be checked
+fn handle(
+    state: &ServerState,
+    instance: Option<String>,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> StatusCode {
+    let Some(queue) = state.webhooks.get(&instance) else {
+        return StatusCode::NOT_FOUND;
+    };
+    let config = queue.instance.config();
+    let webhook = config
+        .server
+        .webhook()
+        .expect("The queue exists only if the webhook is enabled");
+
+    let Some(signature) = header(headers, &SIGNATURE_HEADERS) else {
+        tracing::warn!("Received a webhook without a signature");
+        return StatusCode::UNAUTHORIZED;
+    };
+    if !utils::is_valid_signature(&webhook.secret, signature, body) {
+        tracing::warn!("Received a webhook with an invalid signature");
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    let event = header(headers, &EVENT_HEADERS).unwrap_or("unknown");
+    let payload: EventPayload = match serde_json::from_slice(body) {
+        Ok(payload) => payload,
+        Err(err) => {
+            tracing::warn!("Received an invalid `{event}` webhook payload: {err}");
+            return StatusCode::BAD_REQUEST;
+        }
+    };
+    let Some(sender) = payload.sender else {
+        tracing::debug!("Received a `{event}` webhook without a sender, ignoring it");
+        return StatusCode::NO_CONTENT;
+    };
+
+    tracing::debug!("Received a `{event}` webhook from @{}", sender.login);
+    if !queue
+        .queued
+        .lock()
+        .expect("Not poisoned")
+        .insert(sender.login.clone())
+    {
+        // Already waiting to be checked
+        return StatusCode::ACCEPTED;
+    }
+    if queue.sender.try_send(sender.login.clone()).is_err() {
+        tracing::warn!(
+            "The webhooks queue is full, @{} will be checked by the users fetcher",
+            sender.login
+        );
+        queue
+            .queued
+            .lock()
+            .expect("Not poisoned")
+            .remove(&sender.login);
+        return StatusCode::SERVICE_UNAVAILABLE;
+    }
+
+    StatusCode::ACCEPTED
+}
+
+/// The webhook worker, checks the received users within the request limit of
+/// the expressions
+pub async fn worker(
+    instance: Instance,
+    mut receiver: Receiver<String>,
+    queued: Arc<Mutex<HashSet<String>>>,
+    cancellation_token: CancellationToken,
+) {
+    tracing::info!("Starting webhooks worker");
+
+    let request_client = reqwest::Client::new();
+    let mut window_start = Instant::now();
+    let mut reqs = 0;
+
+    loop {
+        let username = tokio::select! {
+            Some(username) = receiver.recv() => username,
+            _ = cancellation_token.cancelled() => {
+                tracing::info!("Webhooks worker has been stopped successfully.");
+                break
+            }
+        };
+        // Load the config of each user, to use the reloaded config
+        let config = instance.config();
+        let req_interval = Duration::from_secs(config.expressions.req_interval.into());
+        let bots_enabled = config.has_notifiers();
+
+        if window_start.elapsed() >= req_interval {
+            window_start = Instant::now();
+            reqs = 0;
+        }
+        // The user request, the check requests and the content requests
+        if (reqs + 5 + config.expressions.max_content_reqs()) > config.expressions.req_limit {
+            let remaining = req_interval.saturating_sub(window_start.elapsed());
+            if utils::wait_interval(remaining.as_secs() as u32, &cancellation_token).await {
+                break;
+            }
+            window_start = Instant::now();
+            reqs = 0;
+        }
+        queued.lock().expect("Not poisoned").remove(&username);
+
+        reqs += 1;
+        let user = match forgejo_api::get_user(&username, &config.forgejo).await {
+            Ok(user) => user,
+            Err(err) => {
+                tracing::error!("(webhook) Failed to get @{username}: {err}");
+                continue;
+            }
+        };
+        if user.is_admin {
+            continue;
+        }
+
+        reqs += users_fetcher::check_user(
+            "webhook",
+            user,
+            &instance.database,
+            &request_client,
+            &config,
+            false,
+            bots_enabled.then_some(&instance.sus_sender),
+            bots_enabled.then_some(&instance.ban_sender),
+        )
+        .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hmac::{Hmac, Mac};
+    use redb::{backends::InMemoryBackend, Database};
+    use sha2::Sha256;
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::{config::Config, db::GuardDb, server::WebhookQueue};
+
+    const BODY: &[u8] = br#"{"action":"created","sender":{"login":"spammer"}}"#;
+
+    fn state() -> (ServerState, Receiver<String>) {
+        let config: Config = toml::from_str(
+            r#"
+            [forgejo]
+            instance_url = "https://forgejo.example"
+            token = "token"
+
+            [server]
+            enabled = true
+
+            [server.webhook]
+            secret = "s3cret"
+            "#,
+        )
+        .unwrap();
+        let db = Database::builder()
+            .create_with_backend(InMemoryBackend::new())
+            .unwrap();
+        let instance = Instance::new(config, GuardDb::new(Arc::new(db), None).unwrap());
+
+        let (sender, receiver) = mpsc::channel(10);
+        let queue = WebhookQueue {
+            instance: instance.clone(),
+            sender,
+            queued: Arc::default(),
+        };
+        let state = ServerState {
+            instances: vec![instance],
+            webhooks:  [(None, queue)].into(),
+        };
+        (state, receiver)
+    }
+
+    fn sign(secret: &str, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(body);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    fn headers(signature: Option<(&'static str, String)>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Forgejo-Event", "repository".parse().unwrap());
+        if let Some((name, signature)) = signature {
+            headers.insert(name, signature.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn valid_signature() {
+        for name in SIGNATURE_HEADERS {
+            let (state, mut receiver) = state();
+            let headers = headers(Some((name, sign("s3cret", BODY))));
+
+            assert_eq!(handle(&state, None, &headers, BODY), StatusCode::ACCEPTED);
+            assert_eq!(receiver.try_recv().unwrap(), "spammer");
+            // A burst of events checks the user once
+            assert_eq!(handle(&state, None, &headers, BODY), StatusCode::ACCEPTED);
+            assert!(receiver.try_recv().is_err());
+        }
+    }
+
+    #[test]
+    fn invalid_signature() {
+        let (state, mut receiver) = state();
+        let signatures = [
+            sign("other", BODY),
+            sign("s3cret", br#"{"sender":{"login":"admin"}}"#),
+            "not hex".to_owned(),
+        ];
+
+        for signature in signatures {
+            let headers = headers(Some(("X-Gitea-Signature", signature)));
+            assert_eq!(
+                handle(&state, None, &headers, BODY),
+                StatusCode::UNAUTHORIZED
+            );
+        }
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn missing_signature() {
+        let (state, mut receiver) = state();
+
+        assert_eq!(
+            handle(&state, None, &headers(None), BODY),
+            StatusCode::UNAUTHORIZED
+        );
+        assert!(receiver.try_recv().is_err());
+        // The webhooks of an unknown instance are not found
+        assert_eq!(
+            handle(
+                &state,
+                Some("other".to_owned()),
+                &headers(Some(("X-Forgejo-Signature", sign("s3cret", BODY)))),
+                BODY
+            ),
+            StatusCode::NOT_FOUND
+        );
+    }
+}
@@ -0,0 +1,492 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
+
+//! The `simulate` subcommand, checks all the instance users against the
+//! current and a candidate config side by side, and reports the users whose
+//! verdict changes, without taking any action.
+//!
+//! The users are paged through like the old users fetcher, respecting the
+//! `req_limit` and `req_interval` of the current config.
+
+use std::{fmt, io::Write, path::Path};
+
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    check,
+    config::{Config, RegexReason},
+    error::{GuardError, GuardResult},
+    feeds,
+    forgejo_api::{self, ForgejoUser, Sort},
+    users_fetcher::{self, Verdict},
+    utils,
+};
+
+/// The usage of the `simulate` subcommand
+const USAGE: &str = "Usage: forgejo-guardian simulate [OPTIONS] --candidate <PATH>
+
+Check all the instance users against the current and the candidate config, and
+report the users whose verdict changes, without taking any action.
+
+Options:
+  --candidate <PATH>   The candidate config file
+  --instance <NAME>    The instance to simulate, required if there are many instances
+  --format <FORMAT>    The report format, `csv` or `json` [default: csv]
+  --output <PATH>      Write the report to the file instead of the stdout
+  -h, --help           Print this help";
+
+/// The report format
+#[derive(Clone, Copy)]
+enum Format {
+    Csv,
+    Json,
+}
+
+/// The `simulate` subcommand arguments
+struct SimulateArgs {
+    /// The candidate config file
+    candidate: String,
+    /// The instance name
+    instance:  Option<String>,
+    /// The report format
+    format:    Format,
+    /// The report file, the stdout if it's `None`
+    output:    Option<String>,
+}
+
+/// The simplified verdict of a user
+#[derive(Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Outcome {
+    /// The user will be banned
+    Ban,
+    /// The moderation team will be alerted about the user
+    Sus,
+    /// No action will be taken
+    None,
+}
+
+/// The change of the user verdict
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Change {
+    /// The candidate config bans the user, the current doesn't match them
+    NewlyBanned,
+    /// The candidate config suspects the user, the current doesn't match them
+    NewlySuspected,
+    /// The candidate config bans the user, the current suspects them
+    Upgraded,
+    /// The candidate config suspects the user, the current bans them
+    Downgraded,
+    /// The current config matches the user, the candidate doesn't
+    NoLongerMatched,
+}
+
+/// A user whose verdict changes
+#[derive(Serialize)]
+struct ReportEntry {
+    /// The user id
+    id:        usize,
+    /// The username
+    username:  String,
+    /// The verdict change
+    change:    Change,
+    /// The verdict of the current config
+    current:   Outcome,
+    /// The verdict of the candidate config
+    candidate: Outcome,
+    /// The matched expressions of the decisive config, the candidate config
+    /// unless the user is no longer matched
+    reasons:   Vec<String>,
+}
+
+/// The simulation report
+#[derive(Serialize)]
+struct Report {
+    /// The instance name, if there are many instances
+    instance:  Option<String>,
+    /// Number of the checked users
+    checked:   usize,
+    /// Whether all the instance users are checked, `false` if the simulation
+    /// got interrupted
+    completed: bool,
+    /// The users whose verdict changes
+    users:     Vec<ReportEntry>,
+}
+
+impl SimulateArgs {
+    /// Parse the subcommand arguments, returns `None` if the help is requested
+    fn parse(mut args: impl Iterator<Item = String>) -> GuardResult<Option<Self>> {
+        let mut candidate = None;
+        let mut instance = None;
+        let mut format = Format::Csv;
+        let mut output = None;
+
+        while let Some(arg) = args.next() {
+            if matches!(arg.as_str(), "-h" | "--help") {
+                return Ok(None);
+            }
+            if !matches!(
+                arg.as_str(),
+                "--candidate" | "--instance" | "--format" | "--output"
+            ) {
+                return Err(GuardError::Other(format!(
+                    "Unexpected argument `{arg}`\n\n{USAGE}"
+                )));
+            }
+            let value = args.next().ok_or_else(|| {
+                GuardError::Other(format!("The `{arg}` option requires a value\n\n{USAGE}"))
+            })?;
+            match arg.as_str() {
+                "--candidate" => candidate = Some(value),
+                "--instance" => instance = Some(value),
+                "--output" => output = Some(value),
+                _ => {
+                    format = match value.as_str() {
+                        "csv" => Format::Csv,
+                        "json" => Format::Json,
+                        _ => {
+                            return Err(GuardError::Other(format!(
+                                "Unknown report format `{value}`, expected `csv` or `json`"
+                            )));
+                        }
+                    }
+                }
+            }
+        }
+
+        let candidate = candidate.ok_or_else(|| {
+            GuardError::Other(format!("The `--candidate` option is required\n\n{USAGE}"))
+        })?;
+        Ok(Some(Self {
+            candidate,
+            instance,
+            format,
+            output,
+        }))
+    }
+}
+
+impl Outcome {
+    /// Returns the outcome of the verdict with its matched expressions
+    fn of(verdict: Verdict) -> (Self, Vec<RegexReason>) {
+        match verdict {
+            Verdict::Ban { reasons, .. } => (Self::Ban, reasons),
+            Verdict::Downgrade { reasons, .. } | Verdict::Sus { reasons, .. } => {
+                (Self::Sus, reasons)
+            }
+            Verdict::Allow { .. } | Verdict::Clean => (Self::None, Vec::new()),
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ban => write!(f, "ban"),
+            Self::Sus => write!(f, "sus"),
+            Self::None => write!(f, "none"),
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NewlyBanned => write!(f, "newly_banned"),
+            Self::NewlySuspected => write!(f, "newly_suspected"),
+            Self::Upgraded => write!(f, "upgraded"),
+            Self::Downgraded => write!(f, "downgraded"),
+            Self::NoLongerMatched => write!(f, "no_longer_matched"),
+        }
+    }
+}
+
+impl Change {
+    /// Returns the change between the current and the candidate outcomes, if
+    /// any
+    fn of(current: Outcome, candidate: Outcome) -> Option<Self> {
+        match (current, candidate) {
+            (current, candidate) if current == candidate => None,
+            (Outcome::Sus, Outcome::Ban) => Some(Self::Upgraded),
+            (Outcome::Ban, Outcome::Sus) => Some(Self::Downgraded),
+            (_, Outcome::Ban) => Some(Self::NewlyBanned),
+            (_, Outcome::Sus) => Some(Self::NewlySuspected),
+            (_, Outcome::None) => Some(Self::NoLongerMatched),
+        }
+    }
+}
+
+impl Report {
+    /// Write the report as CSV, a row for each user
+    fn write_csv(&self, mut writer: impl Write) -> GuardResult<()> {
+        writeln!(writer, "id,username,change,current,candidate,reasons")?;
+        for entry in &self.users {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{}",
+                entry.id,
+                csv_field(&entry.username),
+                entry.change,
+                entry.current,
+                entry.candidate,
+                csv_field(&entry.reasons.join("; "))
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Write the report in the given format
+    fn write(&self, format: Format, mut writer: impl Write) -> GuardResult<()> {
+        match format {
+            Format::Csv => self.write_csv(writer),
+            Format::Json => {
+                serde_json::to_writer_pretty(&mut writer, self)
+                    .map_err(|err| GuardError::Other(err.to_string()))?;
+                Ok(writeln!(writer)?)
+            }
+        }
+    }
+}
+
+/// Returns the CSV field, quoted if it contains a special character
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// Returns `true` if the two configs need the same user content, so it can be
+/// fetched once
+fn same_content(current: &Config, candidate: &Config) -> bool {
+    let (current, candidate) = (&current.expressions, &candidate.expressions);
+    current.needs_repos() == candidate.needs_repos()
+        && current.needs_readmes() == candidate.needs_readmes()
+        && current.needs_activities() == candidate.needs_activities()
+        && current.repos_limit == candidate.repos_limit
+        && current.activities_limit == candidate.activities_limit
+}
+
+/// Returns the verdicts of the user by the current and the candidate configs,
+/// with the number of sended requests
+async fn verdicts(
+    client: &reqwest::Client,
+    current: &Config,
+    candidate: &Config,
+    mut user: ForgejoUser,
+) -> (Verdict, Verdict, u32) {
+    let mut reqs = users_fetcher::fetch_user_content("simulate", client, current, &mut user).await;
+    let current_verdict = users_fetcher::user_verdict(&user, client, current).await;
+    if !same_content(current, candidate) {
+        user.content = Default::default();
+        reqs += users_fetcher::fetch_user_content("simulate", client, candidate, &mut user).await;
+    }
+    let candidate_verdict = users_fetcher::user_verdict(&user, client, candidate).await;
+    (current_verdict, candidate_verdict, reqs)
+}
+
+/// Page through all the instance users, and returns the report of the users
+/// whose verdict changes
+async fn simulate(
+    current: &Config,
+    candidate: &Config,
+    cancellation_token: &CancellationToken,
+) -> GuardResult<Report> {
+    let client = reqwest::Client::new();
+    let exprs = &current.expressions;
+    let max_user_reqs = exprs.max_content_reqs() + candidate.expressions.max_content_reqs();
+    let mut report = Report {
+        instance:  current.forgejo.name.clone(),
+        checked:   0,
+        completed: false,
+        users:     Vec::new(),
+    };
+    let mut reqs = 0;
+    let mut page = 1;
+
+    'main_loop: loop {
+        // Enter the block if we cancelled, so will break
+        if reqs >= exprs.req_limit || cancellation_token.is_cancelled() {
+            if utils::wait_interval(exprs.req_interval, cancellation_token).await {
+                break;
+            }
+            reqs = 0
+        }
+        reqs += 1;
+
+        let users = match forgejo_api::get_users(
+            &client,
+            &current.forgejo.instance,
+            &current.forgejo.token,
+            exprs.limit,
+            page,
+            &Sort::Newest,
+        )
+        .await
+        {
+            Ok(users) => users,
+            Err(err) => {
+                tracing::error!("Failed to fetch the users page {page}, stopping: {err}");
+                break;
+            }
+        };
+        if users.is_empty() {
+            report.completed = true;
+            break;
+        }
+
+        for user in users.into_iter().filter(|user| !user.is_admin) {
+            if reqs + max_user_reqs > exprs.req_limit || cancellation_token.is_cancelled() {
+                if utils::wait_interval(exprs.req_interval, cancellation_token).await {
+                    break 'main_loop;
+                }
+                reqs = 0;
+            }
+
+            let (id, username) = (user.id, user.username.clone());
+            let (current_verdict, candidate_verdict, user_reqs) =
+                verdicts(&client, current, candidate, user).await;
+            reqs += user_reqs;
+            report.checked += 1;
+
+            let (current_outcome, current_reasons) = Outcome::of(current_verdict);
+            let (candidate_outcome, candidate_reasons) = Outcome::of(candidate_verdict);
+            let Some(change) = Change::of(current_outcome, candidate_outcome) else {
+                continue;
+            };
+            let reasons = if change == Change::NoLongerMatched {
+                current_reasons
+            } else {
+                candidate_reasons
+            };
+            tracing::info!("@{username}: {current_outcome} -> {candidate_outcome}");
+            report.users.push(ReportEntry {
+                id,
+                username,
+                change,
+                current: current_outcome,
+                candidate: candidate_outcome,
+                reasons: reasons.iter().map(ToString::to_string).collect(),
+            });
+        }
+
+        tracing::info!("Simulated page {page}, {} users checked", report.checked);
+        page += 1;
+    }
+    Ok(report)
+}
+
+/// Run the `simulate` subcommand with its arguments
+pub async fn run(args: impl Iterator<Item = String>) -> GuardResult<()> {
+    let Some(args) = SimulateArgs::parse(args)? else {
+        println!("{USAGE}");
+        return Ok(());
+    };
+    let instance = args.instance.as_deref();
+    let current = check::instance_config(utils::get_configs()?, instance)?;
+    tracing::info!("Candidate config path: {}", args.candidate);
+    let candidate =
+        check::instance_config(utils::load_configs(Path::new(&args.candidate))?, instance)?;
+
+    let client = reqwest::Client::new();
+    feeds::fetch_all(&client, &current.expressions.feeds).await;
+    feeds::fetch_all(&client, &candidate.expressions.feeds).await;
+
+    // Interrupting the simulation writes the report of the checked users
+    let cancellation_token = CancellationToken::new();
+    let token = cancellation_token.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            tracing::info!("Interrupted, writing the report of the checked users");
+            token.cancel();
+        }
+    });
+
+    let report = simulate(&current, &candidate, &cancellation_token).await?;
+    tracing::info!(
+        "Checked {} users, {} users verdict changes",
+        report.checked,
+        report.users.len()
+    );
+    match args.output {
+        Some(ref path) => report.write(args.format, std::fs::File::create(path)?),
+        None => report.write(args.format, std::io::stdout().lock()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verdict_changes() {
+        use Outcome::{Ban, None, Sus};
+
+        assert_eq!(Change::of(Ban, Ban), Option::None);
+        assert_eq!(Change::of(Sus, Sus), Option::None);
+        assert_eq!(Change::of(None, None), Option::None);
+        assert_eq!(Change::of(None, Ban), Some(Change::NewlyBanned));
+        assert_eq!(Change::of(None, Sus), Some(Change::NewlySuspected));
+        assert_eq!(Change::of(Sus, Ban), Some(Change::Upgraded));
+        assert_eq!(Change::of(Ban, Sus), Some(Change::Downgraded));
+        assert_eq!(Change::of(Ban, None), Some(Change::NoLongerMatched));
+        assert_eq!(Change::of(Sus, None), Some(Change::NoLongerMatched));
+    }
+
+    #[test]
+    fn csv_report() {
+        let entry = |id, username: &str, change, current, candidate, reasons: &[&str]| {
+            ReportEntry {
+                id,
+                username: username.to_owned(),
+                change,
+                current,
+                candidate,
+                reasons: reasons.iter().map(ToString::to_string).collect(),
+            }
+        };
+        let report = Report {
+            instance:  Option::None,
+            checked:   3,
+            completed: true,
+            users:     vec![
+                entry(
+                    1,
+                    "spammer",
+                    Change::Upgraded,
+                    Outcome::Sus,
+                    Outcome::Ban,
+                    &["^spam in their username"],
+                ),
+                entry(
+                    2,
+                    "bot",
+                    Change::Downgraded,
+                    Outcome::Ban,
+                    Outcome::Sus,
+                    &["a, b in their bio", "\"quoted\" in their bio"],
+                ),
+                entry(
+                    3,
+                    "user",
+                    Change::NoLongerMatched,
+                    Outcome::Sus,
+                    Outcome::None,
+                    &[],
+                ),
+            ],
+        };
+
+        let mut csv = Vec::new();
+        report.write_csv(&mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "id,username,change,current,candidate,reasons\n1,spammer,upgraded,sus,ban,^spam in \
+             their username\n2,bot,downgraded,ban,sus,\"a, b in their bio; \"\"quoted\"\" in \
+             their bio\"\n3,user,no_longer_matched,sus,none,\n"
+        );
+    }
+}
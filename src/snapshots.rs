@@ -0,0 +1,240 @@
+// SPDX-License-Identifier: AGPL-3.0-or-later
+// Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
+
+//! The users profiles snapshots, taken before purging them to be used in the
+//! appeals and to restore the wrongly purged users.
+
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+use url::Url;
+
+use crate::{
+    config::{Config, ConfigReceiver, Snapshots},
+    db::{GuardDb, SnapshotsTableTrait},
+    error::{GuardError, GuardResult},
+    forgejo_api::{self, Activity, ForgejoRepo, ForgejoUser},
+};
+
+/// Maximum number of the repositories and activities to include in the
+/// snapshot
+const CONTENT_LIMIT: u32 = 50;
+
+/// Interval between each expired snapshots cleanup, an hour
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// The user profile snapshot
+#[derive(Serialize)]
+struct Snapshot<'a> {
+    /// When the snapshot has been taken
+    taken_at:   DateTime<Utc>,
+    /// The Forgejo instance of the user
+    instance:   &'a Url,
+    /// The user profile
+    user:       &'a ForgejoUser,
+    /// The user repositories metadata, if the content is enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    repos:      Option<Vec<ForgejoRepo>>,
+    /// The user latest activities, if the content is enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    activities: Option<Vec<Activity>>,
+}
+
+/// Returns the current timestamp
+fn timestamp_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("SystemTime before UNIX EPOCH!")
+        .as_secs()
+}
+
+/// Returns the snapshots directory of the instance, if the snapshots are
+/// stored in a directory
+fn instance_directory(config: &Config, snapshots: &Snapshots) -> Option<PathBuf> {
+    snapshots.directory.as_ref().map(|directory| {
+        match &config.forgejo.name {
+            Some(name) => directory.join(name),
+            None => directory.clone(),
+        }
+    })
+}
+
+/// Returns `false` if the username can't be used as a file name
+fn is_valid_username(username: &str) -> bool {
+    !(username.is_empty() || username.starts_with('.') || username.contains(['/', '\\']))
+}
+
+/// Store the snapshot, in the snapshots directory or in the database
+fn store(
+    config: &Config,
+    snapshots: &Snapshots,
+    database: &GuardDb,
+    username: &str,
+    snapshot: &str,
+) -> GuardResult<()> {
+    match instance_directory(config, snapshots) {
+        Some(directory) => {
+            std::fs::create_dir_all(&directory)?;
+            std::fs::write(directory.join(format!("{username}.json")), snapshot)?;
+            Ok(())
+        }
+        None => database.add_snapshot(username, timestamp_now(), snapshot),
+    }
+}
+
+/// Take the user profile snapshot, the errors will be logged. Does nothing if
+/// the snapshots are disabled. Returns the number of sended requests
+pub async fn take(client: &Client, config: &Config, database: &GuardDb, user: &ForgejoUser) -> u32 {
+    let Some(snapshots) = config.snapshots() else {
+        return 0;
+    };
+    let username = &user.username;
+    if !is_valid_username(username) {
+        tracing::error!("Can't take a snapshot of @{username}, invalid username");
+        return 0;
+    }
+    let (instance, token) = (&config.forgejo.instance, &config.forgejo.token);
+
+    let (mut repos, mut activities, mut reqs) = (None, None, 0);
+    if snapshots.content {
+        reqs += 2;
+        repos = forgejo_api::get_user_repos(client, instance, token, username, CONTENT_LIMIT, 1)
+            .await
+            .inspect_err(|err| {
+                tracing::error!("Failed to fetch @{username} repositories for the snapshot: {err}")
+            })
+            .ok();
+        activities =
+            forgejo_api::get_user_activities(client, instance, token, username, CONTENT_LIMIT, 1)
+                .await
+                .inspect_err(|err| {
+                    tracing::error!(
+                        "Failed to fetch @{username} activities for the snapshot: {err}"
+                    )
+                })
+                .ok();
+    }
+
+    let snapshot = Snapshot {
+        taken_at: Utc::now(),
+        instance,
+        user,
+        repos,
+        activities,
+    };
+    let result = serde_json::to_string_pretty(&snapshot)
+        .map_err(|err| GuardError::Other(format!("Failed to serialize the snapshot: {err}")))
+        .and_then(|snapshot| store(config, snapshots, database, username, &snapshot));
+    match result {
+        Ok(()) => tracing::info!("A snapshot of @{username} has been taken"),
+        Err(err) => tracing::error!("Failed to take a snapshot of @{username}: {err}"),
+    }
+    reqs
+}
+
+/// Fetch the user then take their profile snapshot, see [`take`]. Returns the
+/// number of sended requests
+pub async fn take_by_username(
+    client: &Client,
+    config: &Config,
+    database: &GuardDb,
+    username: &str,
+) -> u32 {
+    if config.snapshots().is_none() {
+        return 0;
+    }
+    match forgejo_api::get_user(username, &config.forgejo).await {
+        Ok(user) => 1 + take(client, config, database, &user).await,
+        Err(err) => {
+            tracing::error!("Failed to fetch @{username} to take a snapshot: {err}");
+            1
+        }
+    }
+}
+
+/// Returns the user snapshot JSON, `None` if there is no snapshot or the
+/// snapshots are disabled
+pub fn get(config: &Config, database: &GuardDb, username: &str) -> GuardResult<Option<String>> {
+    let Some(snapshots) = config.snapshots() else {
+        return Ok(None);
+    };
+    if !is_valid_username(username) {
+        return Ok(None);
+    }
+
+    match instance_directory(config, snapshots) {
+        Some(directory) => {
+            match std::fs::read_to_string(directory.join(format!("{username}.json"))) {
+                Ok(snapshot) => Ok(Some(snapshot)),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(err.into()),
+            }
+        }
+        None => database.snapshot(username),
+    }
+}
+
+/// Remove the expired snapshots, returns the number of the removed snapshots
+fn remove_expired(
+    config: &Config,
+    snapshots: &Snapshots,
+    database: &GuardDb,
+) -> GuardResult<usize> {
+    let expired_before = timestamp_now().saturating_sub(snapshots.retention.into());
+    let Some(directory) = instance_directory(config, snapshots) else {
+        return database.remove_snapshots_before(expired_before);
+    };
+    if !directory.exists() {
+        return Ok(0);
+    }
+
+    let expired_before = UNIX_EPOCH + Duration::from_secs(expired_before);
+    let mut removed = 0;
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        if std::fs::metadata(&path)?.modified()? < expired_before {
+            std::fs::remove_file(path)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// The expired snapshots cleaner
+pub async fn cleaner(
+    config_receiver: ConfigReceiver,
+    database: Arc<GuardDb>,
+    cancellation_token: CancellationToken,
+) {
+    tracing::info!("Starting the expired snapshots cleaner");
+
+    loop {
+        // The retention of the latest reloaded config
+        let config = Arc::clone(&config_receiver.borrow());
+        if let Some(snapshots) = config.snapshots() {
+            match remove_expired(&config, snapshots, &database) {
+                Ok(0) => {}
+                Ok(removed) => tracing::info!("Removed {removed} expired snapshots"),
+                Err(err) => tracing::error!("Failed to remove the expired snapshots: {err}"),
+            }
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(CLEANUP_INTERVAL) => {}
+            _ = cancellation_token.cancelled() => {
+                tracing::info!("Snapshots cleaner has been stopped successfully.");
+                break
+            }
+        };
+    }
+}
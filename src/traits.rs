@@ -1,6 +1,8 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
 
+use std::borrow::Cow;
+
 use crate::{
     config::{locations::Locations, Expr, RegexReason},
     forgejo_api::ForgejoUser,
@@ -8,38 +10,119 @@ use crate::{
 
 /// Trait for checking if a user matches one of the expressions
 pub trait ExprChecker {
-    /// Returns the first matching expression, if any
-    fn is_match(&self, user: &ForgejoUser) -> Option<RegexReason>;
+    /// Returns all the matching expressions, in all user fields
+    fn matches(&self, user: &ForgejoUser) -> Vec<RegexReason>;
+}
+
+/// Trait for scoring a user based on the weights of the matched expressions
+pub trait ScoreChecker {
+    /// Returns the sum of the matched expressions weights, and the matched
+    /// expressions
+    fn score(&self, user: &ForgejoUser) -> (f64, Vec<RegexReason>);
+}
+
+/// Join the hay lines into a single line
+///
+/// ref: https://git.4rs.nl/awiteb/forgejo-guardian/issues/2
+fn single_line(hay: &str) -> Cow<'_, str> {
+    if hay.contains('\n') {
+        Cow::Owned(hay.split('\n').collect::<Vec<_>>().join(" "))
+    } else {
+        Cow::Borrowed(hay)
+    }
+}
+
+/// Returns the matched substrings if all the regex of the expression are
+/// matched
+fn re_match(re_re: &RegexReason, hay: &str) -> Option<Vec<String>> {
+    re_re
+        .re_vec
+        .iter()
+        .map(|re| re.find(hay).map(|m| m.as_str().to_owned()))
+        .collect()
+}
+
+/// Returns the matched expression with its location and matched substrings
+fn matched(re_re: &RegexReason, hay: &str, location: &Locations) -> Option<RegexReason> {
+    re_match(re_re, hay).map(|matched| re_re.clone().location(location.clone()).matched(matched))
+}
+
+/// Returns the matched expression in the first matched hay
+fn matched_any(re_re: &RegexReason, hays: &[&str], location: &Locations) -> Option<RegexReason> {
+    hays.iter()
+        .find_map(|hay| matched(re_re, &single_line(hay), location))
+}
+
+/// Convert the strings to a vector of string slices
+fn as_strs(strings: &[String]) -> Vec<&str> {
+    strings.iter().map(String::as_str).collect()
+}
+
+impl Expr {
+    /// Returns the user fields with their expressions and locations, the
+    /// content fields may have multiple hays, e.g. a hay for each repository
+    fn fields<'a>(
+        &'a self,
+        user: &'a ForgejoUser,
+    ) -> [(Vec<&'a str>, &'a Vec<RegexReason>, Locations); 11] {
+        let content = &user.content;
+        [
+            (vec![&user.username], &self.usernames, Locations::Username),
+            (vec![&user.full_name], &self.full_names, Locations::FullName),
+            (
+                vec![&user.biography],
+                &self.biographies,
+                Locations::Biographie,
+            ),
+            (vec![&user.email], &self.emails, Locations::Email),
+            (vec![&user.website], &self.websites, Locations::Website),
+            (vec![&user.location], &self.locations, Locations::Location),
+            (
+                as_strs(&content.repo_names),
+                &self.repo_names,
+                Locations::RepoName,
+            ),
+            (
+                as_strs(&content.repo_descriptions),
+                &self.repo_descriptions,
+                Locations::RepoDescription,
+            ),
+            (as_strs(&content.readmes), &self.readmes, Locations::Readme),
+            (
+                as_strs(&content.issue_titles),
+                &self.issue_titles,
+                Locations::IssueTitle,
+            ),
+            (
+                as_strs(&content.comment_bodies),
+                &self.comment_bodies,
+                Locations::CommentBody,
+            ),
+        ]
+    }
 }
 
 impl ExprChecker for Expr {
-    fn is_match<'a>(&'a self, user: &ForgejoUser) -> Option<RegexReason> {
+    fn matches(&self, user: &ForgejoUser) -> Vec<RegexReason> {
         if !self.enabled {
-            return None;
+            return Vec::new();
         }
 
-        let one_of = |hay: &str, exprs: &'a Vec<RegexReason>| {
-            // Join the user bio into a single line
-            // ref: https://git.4rs.nl/awiteb/forgejo-guardian/issues/2
-            let hay = if hay.contains('\n') {
-                hay.split('\n').collect::<Vec<_>>().join(" ")
-            } else {
-                hay.to_string()
-            };
-            exprs
-                .iter()
-                .find(|re_re| re_re.re_vec.iter().all(|re| re.is_match(&hay)))
-                .cloned()
-        };
-        [
-            one_of(&user.username, &self.usernames).map(|r| r.location(Locations::Username)),
-            one_of(&user.full_name, &self.full_names).map(|r| r.location(Locations::FullName)),
-            one_of(&user.biography, &self.biographies).map(|r| r.location(Locations::Biographie)),
-            one_of(&user.email, &self.emails).map(|r| r.location(Locations::Email)),
-            one_of(&user.website, &self.websites).map(|r| r.location(Locations::Website)),
-            one_of(&user.location, &self.locations).map(|r| r.location(Locations::Location)),
-        ]
-        .into_iter()
-        .find_map(|v| v)
+        self.fields(user)
+            .into_iter()
+            .flat_map(|(hays, exprs, location)| {
+                exprs
+                    .iter()
+                    .filter_map(|re_re| matched_any(re_re, &hays, &location))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
+
+impl ScoreChecker for Expr {
+    fn score(&self, user: &ForgejoUser) -> (f64, Vec<RegexReason>) {
+        let matched = self.matches(user);
+        (matched.iter().map(RegexReason::score_weight).sum(), matched)
     }
 }
@@ -3,20 +3,32 @@
 
 use std::{sync::Arc, time::Duration};
 
-use redb::Database;
-use tokio::sync::{mpsc::Sender, Mutex};
+use tokio::sync::Mutex;
 use tokio::time::sleep as tokio_sleep;
 use tokio_util::sync::CancellationToken;
 
 use crate::inactive_users::is_inactive;
 use crate::{
-    bots::UserAlert,
-    config::Config,
-    db::{AlertedUsersTableTrait, IgnoredUsersTableTrait, PurgedUsersTableTrait},
+    bots::{notifier::AlertSender, UserAlert},
+    config::{BanAction, Config, ConfigReceiver, RegexReason},
+    db::{
+        AlertedUsersTableTrait,
+        AuditAction,
+        AuditEntry,
+        AuditLogTableTrait,
+        BannedUsersTableTrait,
+        CursorsTableTrait,
+        GuardDb,
+        IgnoredUsersTableTrait,
+        PurgedUsersTableTrait,
+    },
+    email_domains::{self, DohResolver},
     error::GuardResult,
     forgejo_api::{self, ForgejoUser, Sort},
     inactive_users,
-    traits::ExprChecker,
+    metrics::{FetcherLabels, METRICS},
+    snapshots,
+    traits::{ExprChecker, ScoreChecker},
     utils,
 };
 
@@ -145,14 +157,43 @@ async fn get_least_users_ids(
     Ok(ids)
 }
 
-/// Check if the user is protected from being banned
+/// Returns the matched expressions. If there is no matched expressions and the
+/// score reached the threshold, the score expressions matches will be returned
+/// with the score
+fn matches_or_score(
+    matches: Vec<RegexReason>,
+    (score, score_matches): &(f64, Vec<RegexReason>),
+    is_score_reached: bool,
+) -> (Vec<RegexReason>, Option<f64>) {
+    if matches.is_empty() && is_score_reached {
+        return (score_matches.clone(), Some(*score));
+    }
+    (matches, None)
+}
+
+/// Join the matched expressions to be logged
+pub(crate) fn join_reasons(reasons: &[RegexReason], score: Option<f64>) -> String {
+    let reasons = reasons
+        .iter()
+        .map(|re| format!("`{re}`"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    match score {
+        Some(score) => format!("the score is {score} ({reasons})"),
+        None => reasons,
+    }
+}
+
+/// Check if the user is protected from being purged
 async fn is_user_protected(
     request_client: &reqwest::Client,
     config: &Config,
     user: &ForgejoUser,
-    ban_sender: &Option<&Sender<UserAlert>>,
+    ban_action: &BanAction,
+    ban_sender: &Option<&AlertSender>,
 ) -> GuardResult<bool> {
     Ok(config.expressions.safe_mode
+        && ban_action.is_purge()
         && ban_sender.is_some()
         && !inactive_users::is_inactive(
             request_client,
@@ -165,17 +206,143 @@ async fn is_user_protected(
         .await?)
 }
 
+/// Send the suspicious user alert to the moderation team, returns the number of
+/// sended requests
+async fn suspect_user(
+    alert: UserAlert,
+    username: &str,
+    database: &GuardDb,
+    request_client: &reqwest::Client,
+    config: &Config,
+    sus_sender: &AlertSender,
+) -> u32 {
+    database.add_alerted_user(username).ok();
+    database
+        .set_alert_action(username, alert.action(config), &alert.rule())
+        .ok();
+
+    let is_active = config.expressions.active_sus_notice
+        && !is_inactive(
+            request_client,
+            &config.forgejo.instance,
+            &config.forgejo.token,
+            username,
+            config.check_tokens,
+            config.check_oauth2,
+        )
+        .await
+        .is_ok_and(|y| y);
+
+    if sus_sender.send(alert.is_active(is_active)).await.is_err() {
+        // No notifier received the alert, so the user will be alerted again
+        database.remove_alerted_user(username).ok();
+    }
+
+    if config.expressions.active_sus_notice {
+        return 3;
+    }
+    0
+}
+
+/// Fetch the user content that needed by the expressions, returns the number
+/// of sended requests
+pub(crate) async fn fetch_user_content(
+    sort: &str,
+    request_client: &reqwest::Client,
+    config: &Config,
+    user: &mut ForgejoUser,
+) -> u32 {
+    let (instance, token) = (&config.forgejo.instance, &config.forgejo.token);
+    let username = user.username.clone();
+    let content = &mut user.content;
+    let mut reqs = 0;
+
+    if config.expressions.needs_repos() {
+        reqs += 1;
+        match forgejo_api::get_user_repos(
+            request_client,
+            instance,
+            token,
+            &username,
+            config.expressions.repos_limit,
+            1,
+        )
+        .await
+        {
+            Ok(repos) => {
+                for repo in repos {
+                    if config.expressions.needs_readmes() && !repo.empty {
+                        reqs += 2;
+                        match forgejo_api::get_readme(
+                            request_client,
+                            instance,
+                            token,
+                            &username,
+                            &repo.name,
+                        )
+                        .await
+                        {
+                            Ok(readme) => content.readmes.extend(readme),
+                            Err(err) => {
+                                tracing::error!(
+                                    "({sort}) Failed to fetch the README of `{username}/{}`: {err}",
+                                    repo.name
+                                );
+                            }
+                        }
+                    }
+                    content.repo_names.push(repo.name);
+                    content.repo_descriptions.push(repo.description);
+                }
+            }
+            Err(err) => {
+                tracing::error!("({sort}) Failed to fetch @{username} repositories: {err}");
+            }
+        }
+    }
+
+    if config.expressions.needs_activities() {
+        reqs += 1;
+        match forgejo_api::get_user_activities(
+            request_client,
+            instance,
+            token,
+            &username,
+            config.expressions.activities_limit,
+            1,
+        )
+        .await
+        {
+            Ok(activities) => {
+                for activity in activities {
+                    content
+                        .issue_titles
+                        .extend(activity.issue_title().map(String::from));
+                    content
+                        .comment_bodies
+                        .extend(activity.comment_body().map(String::from));
+                }
+            }
+            Err(err) => {
+                tracing::error!("({sort}) Failed to fetch @{username} activities: {err}");
+            }
+        }
+    }
+
+    reqs
+}
+
 /// Check if ban or suspect a user, returns the number of sended requests
 #[allow(clippy::too_many_arguments)]
-async fn check_user(
+pub(crate) async fn check_user(
     sort: &str,
-    user: ForgejoUser,
-    database: &Database,
+    mut user: ForgejoUser,
+    database: &GuardDb,
     request_client: &reqwest::Client,
     config: &Config,
     overwrite_ban_alert: bool,
-    sus_sender: Option<&Sender<UserAlert>>,
-    ban_sender: Option<&Sender<UserAlert>>,
+    sus_sender: Option<&AlertSender>,
+    ban_sender: Option<&AlertSender>,
 ) -> u32 {
     let username = user.username.clone();
     let (is_ignored, is_alerted, is_lazy_purged) = (
@@ -184,14 +351,22 @@ async fn check_user(
         database.is_lazy_purged(&username).is_ok_and(|y| y),
     );
 
-    if is_ignored || is_alerted || is_lazy_purged {
+    // Banning a banned user again does nothing, it will only send a duplicate
+    // alert. This happens when the user is checked by the webhook and then
+    // fetched by the users fetcher. The users suspended outside the guardian
+    // are skipped after their verdict, see `check_user_exprs`
+    let is_banned = database.banned_action(&username).is_ok_and(|a| a.is_some());
+
+    if is_ignored || is_alerted || is_lazy_purged || is_banned {
         tracing::info!(
             "({sort}) Skipped {a_an} {reason} user `@{username}`",
-            a_an = if is_lazy_purged { "a" } else { "an" },
+            a_an = if is_ignored || is_alerted { "an" } else { "a" },
             reason = if is_ignored {
                 "ignored"
             } else if is_alerted {
                 "alerted"
+            } else if is_banned {
+                "banned"
             } else {
                 "lazy purged"
             }
@@ -199,90 +374,385 @@ async fn check_user(
         return 0;
     }
 
-    if let Some(re) = config.expressions.ban.is_match(&user) {
-        if is_user_protected(request_client, config, &user, &ban_sender)
-            .await
-            .unwrap_or_default()
-        {
-            if !is_alerted {
-                database.add_alerted_user(&username).ok();
-                ban_sender
-                    .unwrap()
-                    .send(UserAlert::new(user, re).is_active(true))
-                    .await
-                    .ok();
-            }
-            return 3;
+    METRICS
+        .users_checked
+        .get_or_create(&FetcherLabels::new(config, sort))
+        .inc();
+    let content_reqs = fetch_user_content(sort, request_client, config, &mut user).await;
+    content_reqs
+        + check_user_exprs(
+            sort,
+            user,
+            database,
+            request_client,
+            config,
+            overwrite_ban_alert,
+            sus_sender,
+            ban_sender,
+        )
+        .await
+}
+
+/// The verdict of checking a user against the expressions
+pub(crate) enum Verdict {
+    /// The user matches the ban expressions
+    Ban {
+        reasons: Vec<RegexReason>,
+        score:   Option<f64>,
+    },
+    /// The user matches the ban expressions, but it's allowed by the allow
+    /// expressions and the `allow_action` is `downgrade`, so it will be
+    /// suspected instead
+    Downgrade {
+        reasons:    Vec<RegexReason>,
+        score:      Option<f64>,
+        allowed_by: Vec<RegexReason>,
+    },
+    /// The user matches the sus expressions
+    Sus {
+        reasons: Vec<RegexReason>,
+        score:   Option<f64>,
+    },
+    /// The user matches the ban or the sus expressions, but it's allowed by the
+    /// allow expressions
+    Allow {
+        /// The matched expressions, `ban` or `sus`
+        expressions: &'static str,
+        reasons:     Vec<RegexReason>,
+        score:       Option<f64>,
+        allowed_by:  Vec<RegexReason>,
+    },
+    /// The user doesn't match any expression
+    Clean,
+}
+
+impl Verdict {
+    /// Returns the ban action of the matched expressions, `None` if the user
+    /// is allowed or clean
+    fn ban_action(&self, default: BanAction) -> Option<BanAction> {
+        match self {
+            Self::Ban { reasons, .. }
+            | Self::Downgrade { reasons, .. }
+            | Self::Sus { reasons, .. } => Some(BanAction::of_reasons(reasons, default)),
+            Self::Allow { .. } | Self::Clean => None,
         }
+    }
+}
 
-        tracing::info!("({sort}) @{} has been banned because `{re}`", username);
-        if config.dry_run {
-            // If it's a dry run, we don't need to ban the user
-            if config.expressions.ban_alert && ban_sender.is_some() {
-                ban_sender
-                    .unwrap()
-                    .send(UserAlert::new(user, re))
-                    .await
-                    .ok();
-            }
-            return 0;
+/// Returns the verdict of checking the user against the expressions, without
+/// taking any action. The user content must be fetched before, see
+/// [`fetch_user_content`]
+pub(crate) async fn user_verdict(
+    user: &ForgejoUser,
+    request_client: &reqwest::Client,
+    config: &Config,
+) -> Verdict {
+    let score = config.expressions.score.score(user);
+    if score.0 > 0.0 {
+        tracing::debug!("@{} score is {}", user.username, score.0);
+    }
+
+    let email_domain = match config.expressions.email_domains {
+        Some(ref email_domains) => {
+            email_domains::check_email(
+                email_domains,
+                &DohResolver::new(request_client, &email_domains.mx_resolver),
+                &user.email,
+            )
+            .await
+            .map(|re| (re, email_domains.action.is_ban()))
         }
+        None => None,
+    };
+    let (ban_domain, sus_domain) = match email_domain {
+        Some((re, true)) => (Some(re), None),
+        Some((re, false)) => (None, Some(re)),
+        None => (None, None),
+    };
 
-        match forgejo_api::ban_user(
-            request_client,
-            &config.forgejo.instance,
-            &config.forgejo.token,
-            &username,
-            &config.expressions.ban_action,
-        )
-        .await
-        {
-            Ok(_) => {
-                if config.expressions.ban_alert && ban_sender.is_some() && !overwrite_ban_alert {
-                    ban_sender
-                        .unwrap()
-                        .send(UserAlert::new(user, re))
-                        .await
-                        .ok();
-                }
-                database.remove_alerted_user(&username).ok();
-            }
-            Err(err) => {
-                tracing::error!("({sort}) Error while banning a user: {err}");
-            }
+    let (mut ban_reasons, ban_score) = matches_or_score(
+        config.expressions.ban_matches(user),
+        &score,
+        config.expressions.is_ban_score(score.0),
+    );
+    ban_reasons.extend(ban_domain);
+    if !ban_reasons.is_empty() {
+        let allowed_by = config.expressions.allow.matches(user);
+        if allowed_by.is_empty() {
+            return Verdict::Ban {
+                reasons: ban_reasons,
+                score:   ban_score,
+            };
         }
-        return if config.expressions.safe_mode && ban_sender.is_some() {
-            4
-        } else {
-            1
+        if config.expressions.allow_action.is_downgrade() {
+            return Verdict::Downgrade {
+                reasons: ban_reasons,
+                score: ban_score,
+                allowed_by,
+            };
+        }
+        return Verdict::Allow {
+            expressions: "ban",
+            reasons: ban_reasons,
+            score: ban_score,
+            allowed_by,
         };
-    } else if let Some(re) = sus_sender.and(config.expressions.sus.is_match(&user)) {
-        tracing::info!("({sort}) @{} has been suspected because `{re}`", username);
-        database.add_alerted_user(&username).ok();
+    }
+
+    let (mut sus_reasons, sus_score) = matches_or_score(
+        config.expressions.sus_matches(user),
+        &score,
+        config.expressions.is_sus_score(score.0),
+    );
+    sus_reasons.extend(sus_domain);
+    if sus_reasons.is_empty() {
+        return Verdict::Clean;
+    }
+
+    let allowed_by = config.expressions.allow.matches(user);
+    if !allowed_by.is_empty() {
+        return Verdict::Allow {
+            expressions: "sus",
+            reasons: sus_reasons,
+            score: sus_score,
+            allowed_by,
+        };
+    }
+    Verdict::Sus {
+        reasons: sus_reasons,
+        score:   sus_score,
+    }
+}
+
+/// Fetch the user content and returns its verdict, without taking any action
+pub(crate) async fn dry_check_user(
+    mut user: ForgejoUser,
+    request_client: &reqwest::Client,
+    config: &Config,
+) -> (ForgejoUser, Verdict) {
+    fetch_user_content("dry-run", request_client, config, &mut user).await;
+    let verdict = user_verdict(&user, request_client, config).await;
+    (user, verdict)
+}
+
+/// Check the user against the expressions, to ban or suspect it. Returns the
+/// number of sended requests
+#[allow(clippy::too_many_arguments)]
+async fn check_user_exprs(
+    sort: &str,
+    user: ForgejoUser,
+    database: &GuardDb,
+    request_client: &reqwest::Client,
+    config: &Config,
+    overwrite_ban_alert: bool,
+    sus_sender: Option<&AlertSender>,
+    ban_sender: Option<&AlertSender>,
+) -> u32 {
+    let username = user.username.clone();
+    let verdict = user_verdict(&user, request_client, config).await;
 
-        let is_active = config.expressions.active_sus_notice
-            && !is_inactive(
+    // The users suspended outside the guardian can't be suspended again, they
+    // are skipped unless their matched expressions action is purge
+    if let Some(ban_action) = verdict
+        .ban_action(config.expressions.ban_action)
+        .filter(|action| user.prohibit_login && !action.is_purge())
+    {
+        tracing::info!(
+            "({sort}) Skipped a suspended user `@{username}`, the action of its matched \
+             expressions is `{ban_action}`"
+        );
+        return 0;
+    }
+
+    let (ban_reasons, ban_score) = match verdict {
+        Verdict::Ban { reasons, score } => (reasons, score),
+        Verdict::Downgrade {
+            reasons,
+            score,
+            allowed_by,
+        } => {
+            let Some(sus_sender) = sus_sender else {
+                tracing::info!(
+                    "({sort}) @{username} has been skipped because it's allowed by {}, it was \
+                     matching the ban expressions {}",
+                    join_reasons(&allowed_by, None),
+                    join_reasons(&reasons, score)
+                );
+                return 0;
+            };
+
+            tracing::info!(
+                "({sort}) @{username} has been downgraded from ban to sus because it's allowed by \
+                 {}, it was matching the ban expressions {}",
+                join_reasons(&allowed_by, None),
+                join_reasons(&reasons, score)
+            );
+            METRICS
+                .users_suspected
+                .get_or_create(&FetcherLabels::new(config, sort))
+                .inc();
+            let ban_action = BanAction::of_reasons(&reasons, config.expressions.ban_action);
+            return suspect_user(
+                UserAlert::new(user, reasons)
+                    .score(score)
+                    .allowed_by(allowed_by)
+                    .ban_action(ban_action),
+                &username,
+                database,
                 request_client,
-                &config.forgejo.instance,
-                &config.forgejo.token,
+                config,
+                sus_sender,
+            )
+            .await;
+        }
+        Verdict::Sus { reasons, score } => {
+            let Some(sus_sender) = sus_sender else {
+                return 0;
+            };
+            tracing::info!(
+                "({sort}) @{} has been suspected because {}",
+                username,
+                join_reasons(&reasons, score)
+            );
+            METRICS
+                .users_suspected
+                .get_or_create(&FetcherLabels::new(config, sort))
+                .inc();
+            let ban_action = BanAction::of_reasons(&reasons, config.expressions.ban_action);
+            return suspect_user(
+                UserAlert::new(user, reasons)
+                    .score(score)
+                    .ban_action(ban_action),
                 &username,
-                config.check_tokens,
-                config.check_oauth2,
+                database,
+                request_client,
+                config,
+                sus_sender,
             )
-            .await
-            .is_ok_and(|y| y);
+            .await;
+        }
+        Verdict::Allow {
+            expressions,
+            reasons,
+            score,
+            allowed_by,
+        } => {
+            tracing::info!(
+                "({sort}) @{username} has been skipped because it's allowed by {}, it was \
+                 matching the {expressions} expressions {}",
+                join_reasons(&allowed_by, None),
+                join_reasons(&reasons, score)
+            );
+            return 0;
+        }
+        Verdict::Clean => return 0,
+    };
+    let ban_action = BanAction::of_reasons(&ban_reasons, config.expressions.ban_action);
 
-        sus_sender
-            .unwrap()
-            .send(UserAlert::new(user, re).is_active(is_active))
-            .await
+    if is_user_protected(request_client, config, &user, &ban_action, &ban_sender)
+        .await
+        .unwrap_or_default()
+    {
+        METRICS
+            .ban_requests
+            .get_or_create(&FetcherLabels::new(config, sort))
+            .inc();
+        database.add_alerted_user(&username).ok();
+        database
+            .set_alert_action(
+                &username,
+                ban_action,
+                &join_reasons(&ban_reasons, ban_score),
+            )
             .ok();
+        let sent = ban_sender
+            .unwrap()
+            .send(
+                UserAlert::new(user, ban_reasons)
+                    .score(ban_score)
+                    .is_active(true)
+                    .ban_action(ban_action),
+            )
+            .await;
+        if sent.is_err() {
+            // No notifier received the ban request, so the user will be checked again
+            database.remove_alerted_user(&username).ok();
+        }
+        return 3;
+    }
 
-        if config.expressions.active_sus_notice {
-            return 3;
+    let rule = join_reasons(&ban_reasons, ban_score);
+    tracing::info!("({sort}) @{username} has been banned ({ban_action}) because {rule}");
+    let audit_entry = AuditEntry::guardian(
+        config,
+        &username,
+        Some(user.id),
+        AuditAction::Banned,
+        Some(ban_action),
+    )
+    .rule(rule);
+    if config.dry_run {
+        database.audit(audit_entry);
+        // If it's a dry run, we don't need to ban the user
+        if let Some(ban_sender) = ban_sender.filter(|_| config.expressions.ban_alert) {
+            ban_sender
+                .send(
+                    UserAlert::new(user, ban_reasons)
+                        .score(ban_score)
+                        .ban_action(ban_action),
+                )
+                .await
+                .ok();
         }
+        return 0;
     }
-    0
+
+    // The safe mode inactive check requests, see `is_user_protected`
+    let mut reqs = if config.expressions.safe_mode && ban_action.is_purge() && ban_sender.is_some()
+    {
+        3
+    } else {
+        0
+    };
+    if ban_action.is_purge() {
+        reqs += snapshots::take(request_client, config, database, &user).await;
+    }
+    match forgejo_api::ban_user(
+        request_client,
+        &config.forgejo.instance,
+        &config.forgejo.token,
+        &username,
+        &ban_action,
+        &mut reqs,
+    )
+    .await
+    {
+        Ok(_) => {
+            database.audit(audit_entry);
+            database.add_banned_user(&username, ban_action).ok();
+            METRICS
+                .users_banned
+                .get_or_create(&FetcherLabels::new(config, sort))
+                .inc();
+            if let Some(ban_sender) =
+                ban_sender.filter(|_| config.expressions.ban_alert && !overwrite_ban_alert)
+            {
+                ban_sender
+                    .send(
+                        UserAlert::new(user, ban_reasons)
+                            .score(ban_score)
+                            .ban_action(ban_action),
+                    )
+                    .await
+                    .ok();
+            }
+            database.remove_alerted_user(&username).ok();
+        }
+        Err(err) => {
+            tracing::error!("({sort}) Error while banning a user: {err}");
+        }
+    }
+    reqs
 }
 
 /// Check for users and send the suspected users to the channel and ban the
@@ -292,12 +762,13 @@ async fn check_users(
     sort: Sort,
     last_users_ids: Arc<Mutex<Vec<usize>>>,
     request_client: Arc<reqwest::Client>,
-    database: Arc<Database>,
+    database: Arc<GuardDb>,
     config: Arc<Config>,
     cancellation_token: CancellationToken,
-    sus_sender: Sender<UserAlert>,
-    ban_sender: Sender<UserAlert>,
+    sus_sender: AlertSender,
+    ban_sender: AlertSender,
 ) {
+    let labels = FetcherLabels::new(&config, sort.as_str());
     let mut reqs = 0;
     let users = get_users(
         &sort,
@@ -309,10 +780,14 @@ async fn check_users(
     .await;
 
     if users.is_empty() {
+        METRICS
+            .fetcher_last_success
+            .get_or_create(&labels)
+            .set(chrono::Utc::now().timestamp() as f64);
         return;
     }
 
-    {
+    let cursor = {
         let mut ids = last_users_ids.lock().await;
 
         match sort {
@@ -332,12 +807,17 @@ async fn check_users(
             }
             _ => unreachable!(),
         }
-    }
+        ids.clone()
+    };
 
     for user in users {
-        if (reqs + 4) > config.expressions.req_limit || cancellation_token.is_cancelled() {
+        if (reqs + 4 + config.expressions.max_content_reqs()) > config.expressions.req_limit
+            || cancellation_token.is_cancelled()
+        {
             if utils::wait_interval(config.expressions.req_interval, &cancellation_token).await {
-                break;
+                // Don't store the cursor, so the unchecked users will be checked after the
+                // restart
+                return;
             }
             reqs = 0;
         }
@@ -347,6 +827,13 @@ async fn check_users(
             continue;
         }
 
+        if sort.is_newest() {
+            let lag = chrono::Utc::now() - user.created;
+            METRICS
+                .fetcher_lag
+                .get_or_create(&labels)
+                .observe(lag.num_milliseconds().max(0) as f64 / 1000.0);
+        }
         reqs += check_user(
             sort.as_str(),
             user,
@@ -354,34 +841,63 @@ async fn check_users(
             &request_client,
             &config,
             false,
-            (config.telegram.is_enabled() || config.matrix.is_enabled()).then_some(&sus_sender),
-            (config.telegram.is_enabled() || config.matrix.is_enabled()).then_some(&ban_sender),
+            config.has_notifiers().then_some(&sus_sender),
+            config.has_notifiers().then_some(&ban_sender),
         )
         .await;
+        METRICS
+            .fetcher_requests
+            .get_or_create(&labels)
+            .set(reqs.into());
+    }
+
+    if let Err(err) = database.set_users_ids_cursor(&sort, &cursor) {
+        tracing::error!("Failed to store the {sort} cursor: {err}");
     }
+    METRICS
+        .fetcher_last_success
+        .get_or_create(&labels)
+        .set(chrono::Utc::now().timestamp() as f64);
 }
 
 /// The users fetcher, it will check for users every period and send the
 /// suspected users to the channel
 pub async fn users_fetcher(
     sort: Sort,
-    config: Arc<Config>,
-    database: Arc<Database>,
+    config_receiver: ConfigReceiver,
+    database: Arc<GuardDb>,
     cancellation_token: CancellationToken,
-    sus_sender: Sender<UserAlert>,
-    ban_sender: Sender<UserAlert>,
+    sus_sender: AlertSender,
+    ban_sender: AlertSender,
 ) {
     let request_client = Arc::new(reqwest::Client::new());
-    let last_users_ids =
-        if let Ok(last_ids) = get_least_users_ids(&sort, &request_client, &config).await {
+    let config = Arc::clone(&config_receiver.borrow());
+    let last_users_ids = match database.users_ids_cursor(&sort) {
+        Ok(Some(last_ids)) => {
+            tracing::info!("Resuming {sort} users fetcher from the stored cursor: {last_ids:?}");
             Arc::new(Mutex::new(last_ids))
-        } else {
-            tracing::error!("Failed to get {sort} user id");
-            return;
-        };
+        }
+        _ => {
+            if let Ok(last_ids) = get_least_users_ids(&sort, &request_client, &config).await {
+                if let Err(err) = database.set_users_ids_cursor(&sort, &last_ids) {
+                    tracing::error!("Failed to store the {sort} cursor: {err}");
+                }
+                Arc::new(Mutex::new(last_ids))
+            } else {
+                tracing::error!("Failed to get {sort} user id");
+                return;
+            }
+        }
+    };
 
     tracing::info!("Starting {sort} users fetcher");
+    METRICS
+        .fetcher_req_limit
+        .get_or_create(&FetcherLabels::new(&config, sort.as_str()))
+        .set(config.expressions.req_limit.into());
     loop {
+        // Each check uses the latest reloaded config
+        let config = Arc::clone(&config_receiver.borrow());
         tokio::select! {
             _ = tokio::time::sleep(Duration::from_secs(config.expressions.interval.into())) => {
                 tokio::spawn(check_users(
@@ -404,22 +920,34 @@ pub async fn users_fetcher(
 }
 
 /// Check for old users and ban them if they match the ban expressions. This
-/// will not sned any alerts
+/// will not sned any alerts. Returns `true` if all the instance users are
+/// checked
 pub async fn old_users(
-    config: Arc<Config>,
-    database: Arc<Database>,
-    ban_sender: Sender<UserAlert>,
-    sus_sender: Sender<UserAlert>,
+    config_receiver: ConfigReceiver,
+    database: Arc<GuardDb>,
+    ban_sender: AlertSender,
+    sus_sender: AlertSender,
     cancellation_token: CancellationToken,
-) {
+) -> bool {
     tracing::info!("Starting old users fetcher");
+    let config = Arc::clone(&config_receiver.borrow());
+    let labels = FetcherLabels::new(&config, "oldest");
+    METRICS
+        .fetcher_req_limit
+        .get_or_create(&labels)
+        .set(config.expressions.req_limit.into());
 
     let client = reqwest::Client::new();
     let mut retries = 0;
     let mut reqs = 0;
-    let mut page = 1;
+    let mut page = database.old_users_page().ok().flatten().unwrap_or(1);
+    if page != 1 {
+        tracing::info!("Resuming old users fetcher from page {page}");
+    }
 
     'main_loop: loop {
+        // Each page uses the latest reloaded config
+        let config = Arc::clone(&config_receiver.borrow());
         // Enter the block if we cancelled, so will break
         if reqs >= config.expressions.req_limit || cancellation_token.is_cancelled() {
             if utils::wait_interval(config.expressions.req_interval, &cancellation_token).await {
@@ -443,7 +971,7 @@ pub async fn old_users(
             retries += 1;
             if retries >= MAX_RETRIES {
                 tracing::error!("Failed to fetch old users after {MAX_RETRIES} retries.");
-                return;
+                return false;
             }
             tracing::info!("Retrying in {} seconds.", RETRY_INTERVAL * retries);
             tokio_sleep(Duration::from_secs(RETRY_INTERVAL * retries)).await;
@@ -452,12 +980,14 @@ pub async fn old_users(
         retries = 0;
         if users.is_empty() {
             tracing::info!("No more old users to check, all instance users are checked.");
-            break;
+            return true;
         }
 
         for user in users {
             tokio_sleep(Duration::from_secs(2)).await;
-            if (reqs + 4) > config.expressions.req_limit || cancellation_token.is_cancelled() {
+            if (reqs + 4 + config.expressions.max_content_reqs()) > config.expressions.req_limit
+                || cancellation_token.is_cancelled()
+            {
                 if utils::wait_interval(config.expressions.req_interval, &cancellation_token).await
                 {
                     break 'main_loop;
@@ -479,8 +1009,228 @@ pub async fn old_users(
                 Some(&ban_sender),
             )
             .await;
+            METRICS
+                .fetcher_requests
+                .get_or_create(&labels)
+                .set(reqs.into());
         }
 
         page += 1;
+        if let Err(err) = database.set_old_users_page(page) {
+            tracing::error!("Failed to store the old users page: {err}");
+        }
+        METRICS
+            .fetcher_last_success
+            .get_or_create(&labels)
+            .set(chrono::Utc::now().timestamp() as f64);
+    }
+    // Cancelled
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exprs_config(exprs: &str) -> Config {
+        toml::from_str(&format!(
+            r#"
+            [forgejo]
+            instance_url = "https://forgejo.example"
+            token = "token"
+
+            [expressions]
+            ban_score = 3
+            sus_score = 1.5
+            {exprs}
+
+            [expressions.score]
+            biographies = [{{ re = "followers", weight = 1 }}, {{ re = "cheap", weight = 0.5 }}]
+            websites = [{{ re = "\\.xyz", weight = 2 }}]
+            "#
+        ))
+        .unwrap()
+    }
+
+    fn user(biography: &str, website: &str) -> ForgejoUser {
+        let mut user: ForgejoUser = serde_json::from_value(serde_json::json!({
+            "id": 42,
+            "avatar_url": "https://forgejo.example/avatar.png",
+            "html_url": "https://forgejo.example/spammer",
+            "is_admin": false,
+            "source_id": 0,
+            "login": "spammer",
+            "full_name": "",
+            "description": "",
+            "email": "spammer@example.com",
+            "website": "",
+            "location": "",
+            "created": "2025-01-01T00:00:00Z"
+        }))
+        .unwrap();
+        user.biography = biography.to_owned();
+        user.website = website.to_owned();
+        user
+    }
+
+    async fn verdict(config: &Config, user: &ForgejoUser) -> Verdict {
+        user_verdict(user, &reqwest::Client::new(), config).await
+    }
+
+    #[test]
+    fn summed_weights() {
+        let config = exprs_config("");
+        let score = &config.expressions.score;
+
+        assert_eq!(score.score(&user("", "")).0, 0.0);
+        assert_eq!(score.score(&user("cheap followers", "")).0, 1.5);
+        let (total, matches) = score.score(&user("cheap followers", "https://spam.xyz"));
+        assert_eq!(total, 3.5);
+        assert_eq!(matches.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn score_thresholds() {
+        let config = exprs_config("");
+
+        assert!(matches!(
+            verdict(&config, &user("followers", "")).await,
+            Verdict::Clean
+        ));
+        assert!(matches!(
+            verdict(&config, &user("cheap followers", "")).await,
+            Verdict::Sus {
+                score: Some(1.5),
+                ..
+            }
+        ));
+        // The ban score has the priority over the sus score
+        assert!(matches!(
+            verdict(&config, &user("followers", "https://spam.xyz")).await,
+            Verdict::Ban {
+                score: Some(3.0),
+                ..
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn expressions_before_score() {
+        let config = exprs_config(
+            r#"
+            [expressions.ban]
+            usernames = ["^spammer$"]
+            "#,
+        );
+
+        // The score is used only if no `ban` or `sus` expression matched
+        let Verdict::Ban { reasons, score } = verdict(&config, &user("cheap followers", "")).await
+        else {
+            panic!("The user should be banned");
+        };
+        assert_eq!(score, None);
+        assert_eq!(reasons.len(), 1);
+        assert_eq!(reasons[0].to_string(), "^spammer$ in their username");
+    }
+
+    #[tokio::test]
+    async fn verdict_ban_action() {
+        let config = exprs_config(
+            r#"
+            ban_action = "suspend"
+
+            [expressions.ban]
+            biographies = [{ re = "casino", action = "purge" }, "followers"]
+            "#,
+        );
+        let default = config.expressions.ban_action;
+
+        // The most severe action of the matched expressions, so a suspended
+        // user matching a purge expression isn't skipped
+        let action = |verdict: Verdict| verdict.ban_action(default);
+        assert_eq!(
+            action(verdict(&config, &user("casino followers", "")).await),
+            Some(BanAction::Purge)
+        );
+        assert_eq!(
+            action(verdict(&config, &user("followers", "")).await),
+            Some(BanAction::Suspend)
+        );
+        assert_eq!(action(verdict(&config, &user("", "")).await), None);
+    }
+
+    #[tokio::test]
+    async fn allowed_score_ban() {
+        let allow = r#"
+            [expressions.allow]
+            emails = ["@example\\.com$"]
+        "#;
+
+        let config = exprs_config(allow);
+        assert!(matches!(
+            verdict(&config, &user("followers", "https://spam.xyz")).await,
+            Verdict::Allow {
+                expressions: "ban",
+                score: Some(3.0),
+                ..
+            }
+        ));
+
+        let config = exprs_config(&format!("allow_action = \"downgrade\"\n{allow}"));
+        assert!(matches!(
+            verdict(&config, &user("followers", "https://spam.xyz")).await,
+            Verdict::Downgrade {
+                score: Some(3.0),
+                ..
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn allowed_by_locations() {
+        let config = exprs_config(
+            r#"
+            [expressions.ban]
+            biographies = [{ re = "casino", reason = "Gambling" }]
+
+            [expressions.allow]
+            biographies = [{ re = ["casino", "games?"], reason = "Game developer" }]
+            websites = ["gamedev", "itch\\.io"]
+            "#,
+        );
+
+        // All the matched allow expressions are reported, each with its
+        // location and matched substrings
+        let Verdict::Allow {
+            expressions,
+            reasons,
+            allowed_by,
+            ..
+        } = verdict(
+            &config,
+            &user("Making casino\ngames", "https://gamedev.example"),
+        )
+        .await
+        else {
+            panic!("The user should be allowed");
+        };
+        assert_eq!(expressions, "ban");
+        assert_eq!(reasons.len(), 1);
+        assert_eq!(
+            reasons[0].to_string(),
+            "casino (Gambling) in their biography"
+        );
+        assert_eq!(
+            allowed_by
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            [
+                "casino, games? (Game developer) in their biography",
+                "gamedev in their website"
+            ]
+        );
+        assert_eq!(allowed_by[0].matched, ["casino", "games"]);
+        assert_eq!(allowed_by[1].matched, ["gamedev"]);
     }
 }
@@ -1,16 +1,63 @@
 // SPDX-License-Identifier: AGPL-3.0-or-later
 // Copyright (C) 2024-2025 Awiteb <a@4rs.nl>
 
-use std::{env, fs, path::PathBuf, str::FromStr, time::Duration};
+use std::{
+    cell::RefCell,
+    env,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
 
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
 use tokio_util::sync::CancellationToken;
 use tracing::level_filters::LevelFilter;
 
 use crate::{
-    config::{parse_invalid, Config, Matrix, Telegram, CONFIG_PATH_ENV, DEFAULT_CONFIG_PATH},
+    config::{
+        instances,
+        parse_invalid,
+        Config,
+        Expr,
+        Matrix,
+        Telegram,
+        CONFIG_PATH_ENV,
+        DEFAULT_CONFIG_PATH,
+    },
     error::{GuardError, GuardResult},
 };
 
+thread_local! {
+    /// The directory of the config file being loaded, the relative rules files
+    /// patterns are resolved against it
+    static CONFIG_DIR: RefCell<PathBuf> = RefCell::default();
+    /// The rules files loaded by the config being loaded, they are watched by
+    /// the config reloader
+    static RULES_FILES: RefCell<Vec<PathBuf>> = RefCell::default();
+}
+
+/// Adds the rules file to the loaded rules files of the config being loaded
+pub fn add_rules_file(path: &Path) {
+    RULES_FILES.with_borrow_mut(|files| files.push(path.to_path_buf()));
+}
+
+/// Returns the glob pattern resolved against the directory of the config file
+/// being loaded, the absolute patterns are returned as is
+pub fn resolve_pattern(pattern: &str) -> String {
+    CONFIG_DIR.with_borrow(|dir| {
+        if dir.as_os_str().is_empty() || Path::new(pattern).is_absolute() {
+            pattern.to_owned()
+        } else {
+            format!(
+                "{}/{pattern}",
+                glob::Pattern::escape(&dir.to_string_lossy())
+            )
+        }
+    })
+}
+
 /// Checks for warnings in the config
 fn check_warnings(config: &Config) {
     if config.inactive.enabled && (config.inactive.req_interval > config.inactive.interval) {
@@ -21,13 +68,61 @@ fn check_warnings(config: &Config) {
         );
     }
 
-    if config.expressions.sus.enabled
-        && !config.telegram.is_enabled()
-        && !config.matrix.is_enabled()
+    if config.expressions.sus.enabled && !config.has_notifiers() {
+        tracing::warn!(
+            "The suspicious users expressions are enabled but no notifier is enabled, the \
+             suspicious users will not be alerted"
+        );
+    }
+
+    if config.expressions.score.enabled
+        && config.expressions.sus_score.is_some()
+        && !config.has_notifiers()
     {
         tracing::warn!(
-            "The suspicious users expressions are enabled but the Telegram and Matrix bot is \
-             disabled, the suspicious users will not be alerted"
+            "The `sus_score` is set but no notifier is enabled, the suspicious users will not be \
+             alerted"
+        );
+    }
+
+    if config.expressions.req_limit < 4 + config.expressions.max_content_reqs() {
+        tracing::warn!(
+            "The `expressions.req_limit` is less than the maximum requests of checking a user \
+             with its content ({}), the guardian will wait `req_interval` after each user",
+            4 + config.expressions.max_content_reqs()
+        );
+    }
+
+    if !config.expressions.feeds.is_empty()
+        && !config.expressions.ban.enabled
+        && !config.expressions.sus.enabled
+    {
+        tracing::warn!(
+            "The rule feeds are set but the ban and sus expressions are disabled, the feeds rules \
+             will not be used"
+        );
+    }
+
+    if config.server.webhook.as_ref().is_some_and(|w| w.enabled) && !config.server.enabled {
+        tracing::warn!(
+            "The webhooks receiver is enabled but the server is disabled, the webhooks will not \
+             be received"
+        );
+    }
+
+    if config.server.dashboard.as_ref().is_some_and(|d| d.enabled) && !config.server.enabled {
+        tracing::warn!(
+            "The dashboard is enabled but the server is disabled, the dashboard will not be served"
+        );
+    }
+
+    if config.server.api.as_ref().is_some_and(|a| a.enabled) && !config.server.enabled {
+        tracing::warn!("The API is enabled but the server is disabled, the API will not be served");
+    }
+
+    if config.server.metrics && !config.server.enabled {
+        tracing::warn!(
+            "The metrics are enabled but the server is disabled, the metrics will not be served"
         );
     }
 
@@ -69,19 +164,40 @@ fn check_errors(config: &Config) -> GuardResult<()> {
                     .to_owned(),
             ));
         }
-        if !config.telegram.is_enabled() && !config.matrix.is_enabled() {
+        if !config.has_notifiers() {
             return Err(GuardError::Other(
-                "Safe mode is enabled, but Telegram and Matrix bot is disabled, the safe mode \
-                 need to send a ban request to the moderation team"
+                "Safe mode is enabled, but no notifier is enabled, the safe mode need to send a \
+                 ban request to the moderation team"
                     .to_owned(),
             ));
         }
     }
 
-    if config.telegram.is_enabled() && config.matrix.is_enabled() {
-        return Err(GuardError::Other(
-            "Both Telegram and Matrix bot is enabled, only one can be enabled at a time".to_owned(),
-        ));
+    if config.expressions.score.enabled {
+        let (ban_score, sus_score) = (config.expressions.ban_score, config.expressions.sus_score);
+        if ban_score.is_none() && sus_score.is_none() {
+            return Err(GuardError::Other(
+                "The score expressions are enabled, but neither `ban_score` nor `sus_score` is set"
+                    .to_owned(),
+            ));
+        }
+        if ban_score
+            .into_iter()
+            .chain(sus_score)
+            .any(|score| !score.is_finite() || score <= 0.0)
+        {
+            return Err(GuardError::Other(
+                "The `ban_score` and `sus_score` must be positive numbers".to_owned(),
+            ));
+        }
+        if let (Some(ban_score), Some(sus_score)) = (ban_score, sus_score) {
+            if sus_score >= ban_score {
+                return Err(GuardError::Other(format!(
+                    "The `sus_score` ({sus_score}) must be less than the `ban_score` \
+                     ({ban_score}), otherwise the user will be banned before being suspected"
+                )));
+            }
+        }
     }
 
     if !config.expressions.ban_action.is_purge() && config.lazy_purge.enabled {
@@ -90,6 +206,59 @@ fn check_errors(config: &Config) -> GuardResult<()> {
         ));
     }
 
+    // The weights are summed only in the `score` expressions, a weight in the
+    // other expressions would be ignored
+    let exprs = &config.expressions;
+    for (name, expr) in [
+        ("ban", &exprs.ban),
+        ("sus", &exprs.sus),
+        ("allow", &exprs.allow),
+    ] {
+        if expr.regexes().any(|re| re.weight.is_some()) {
+            return Err(GuardError::Other(format!(
+                "The `expressions.{name}` expressions can't have a `weight`, the weights are only \
+                 used in the `score` expressions"
+            )));
+        }
+    }
+
+    // The expressions actions override the global ban action, so they have the
+    // same restrictions
+    if let Some(action) = [&exprs.ban, &exprs.sus, &exprs.score]
+        .into_iter()
+        .flat_map(Expr::regexes)
+        .filter_map(|re| re.action)
+        .find(|action| !action.is_purge())
+    {
+        if exprs.safe_mode {
+            return Err(GuardError::Other(format!(
+                "Safe mode is enabled, but an expression action is set to `{action}`, the \
+                 expressions actions must be `purge` when the safe mode is enabled"
+            )));
+        }
+        if config.lazy_purge.enabled {
+            return Err(GuardError::Other(format!(
+                "Lazy purge is enabled, but an expression action is set to `{action}`, the \
+                 expressions actions must be `purge` when the lazy purge is enabled"
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Replace the value with the environment variable value, if the value starts
+/// with the prefix `env.`. The remainder of the value is treated as the name
+/// of the environment variable.
+fn env_value(value: &mut String) -> GuardResult<()> {
+    if value.starts_with("env.") {
+        let (_, env_var) = value.split_once('.').expect("unreachable");
+        let env_var = env::var(env_var).map_err(|_| {
+            GuardError::Other(format!("Environment variable `{env_var}` not found"))
+        })?;
+        *value = env_var;
+    }
+
     Ok(())
 }
 
@@ -99,17 +268,172 @@ fn check_errors(config: &Config) -> GuardResult<()> {
 /// treated as the name of an environment variable from which the actual token
 /// value is retrieved.
 fn check_forgejo_token(config: &mut Config) -> GuardResult<()> {
-    if config.forgejo.token.starts_with("env.") {
-        let (_, env_var) = config.forgejo.token.split_once('.').expect("unreachable");
-        let env_var = env::var(env_var).map_err(|_| {
-            GuardError::Other(format!("Environment variable `{env_var}` not found"))
-        })?;
-        config.forgejo.token = env_var;
+    env_value(&mut config.forgejo.token)
+}
+
+/// Checks if the webhooks secrets are specified as an environment variable,
+/// the same as [`check_forgejo_token`]
+fn check_webhook_secret(config: &mut Config) -> GuardResult<()> {
+    if let Some(webhook) = config.server.webhook.as_mut() {
+        env_value(&mut webhook.secret)?;
+        if webhook.enabled && webhook.secret.is_empty() {
+            return Err(GuardError::Other(
+                "The webhook secret is empty, the webhooks can't be verified".to_owned(),
+            ));
+        }
+    }
+    if let Some(webhook) = config.webhook.as_mut() {
+        env_value(&mut webhook.secret)?;
+        if webhook.enabled && webhook.secret.is_empty() {
+            return Err(GuardError::Other(
+                "The outgoing webhook secret is empty, the requests can't be signed".to_owned(),
+            ));
+        }
     }
 
     Ok(())
 }
 
+/// Checks if the dashboard and the API tokens are specified as an environment
+/// variable, the same as [`check_forgejo_token`]
+fn check_server_tokens(config: &mut Config) -> GuardResult<()> {
+    if let Some(dashboard) = config.server.dashboard.as_mut() {
+        env_value(&mut dashboard.token)?;
+        if dashboard.enabled && dashboard.token.is_empty() {
+            return Err(GuardError::Other(
+                "The dashboard token is empty, the dashboard can't be protected".to_owned(),
+            ));
+        }
+    }
+    if let Some(api) = config.server.api.as_mut() {
+        env_value(&mut api.token)?;
+        if api.enabled && api.token.is_empty() {
+            return Err(GuardError::Other(
+                "The API token is empty, the API can't be protected".to_owned(),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks if the rule feeds secrets are specified as an environment variable,
+/// the same as [`check_forgejo_token`]
+fn check_feeds(config: &mut Config) -> GuardResult<()> {
+    for feed in &mut config.expressions.feeds {
+        env_value(&mut feed.secret)?;
+        if feed.secret.is_empty() {
+            return Err(GuardError::Other(format!(
+                "The secret of the feed `{}` is empty, the feed can't be verified",
+                feed.url
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the email notifier configuration, the secrets can be specified as an
+/// environment variable, the same as [`check_forgejo_token`]
+fn check_email(config: &mut Config) -> GuardResult<()> {
+    let server_enabled = config.server.enabled;
+    let Some(email) = config.email.as_mut().filter(|e| e.enabled) else {
+        return Ok(());
+    };
+
+    env_value(&mut email.secret)?;
+    if let Some(password) = email.password.as_mut() {
+        env_value(password)?;
+    }
+    if email.secret.is_empty() {
+        return Err(GuardError::Other(
+            "The email secret is empty, the emails links can't be signed".to_owned(),
+        ));
+    }
+    if email.to.is_empty() {
+        return Err(GuardError::Other(
+            "The email notifier is enabled but there is no recipients in `email.to`".to_owned(),
+        ));
+    }
+    if !server_enabled {
+        tracing::warn!(
+            "The email notifier is enabled but the server is disabled, the emails links will not \
+             work"
+        );
+    }
+
+    Ok(())
+}
+
+/// Loads the included rules files of the expression, merging their regexes
+/// into it
+fn load_expr_includes(expr: &mut Expr, name: &str) -> GuardResult<()> {
+    for pattern in std::mem::take(&mut expr.include) {
+        let mut paths = glob::glob(&resolve_pattern(&pattern))
+            .map_err(|err| {
+                GuardError::Other(format!(
+                    "Invalid `expressions.{name}.include` pattern `{pattern}`: {err}"
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| GuardError::Other(err.to_string()))?;
+        if paths.is_empty() {
+            tracing::warn!(
+                "The `expressions.{name}.include` pattern `{pattern}` doesn't match any file"
+            );
+        }
+        paths.sort();
+
+        for path in paths {
+            add_rules_file(&path);
+            let rules: Expr = toml::from_str(&fs::read_to_string(&path)?).map_err(|err| {
+                GuardError::Other(format!(
+                    "Failed to deserialize the rules file `{}`: {err}",
+                    path.display()
+                ))
+            })?;
+            if !rules.include.is_empty() {
+                return Err(GuardError::Other(format!(
+                    "The rules file `{}` can't include other files",
+                    path.display()
+                )));
+            }
+            expr.extend(rules);
+        }
+    }
+    Ok(())
+}
+
+/// Loads the included rules files of the expressions
+fn load_includes(config: &mut Config) -> GuardResult<()> {
+    let exprs = &mut config.expressions;
+    load_expr_includes(&mut exprs.ban, "ban")?;
+    load_expr_includes(&mut exprs.sus, "sus")?;
+    load_expr_includes(&mut exprs.score, "score")?;
+    load_expr_includes(&mut exprs.allow, "allow")
+}
+
+/// Loads the email domains blocklist, if it's enabled
+fn load_email_domains(config: &Config) -> GuardResult<()> {
+    if let Some(email_domains) = config
+        .expressions
+        .email_domains
+        .as_ref()
+        .filter(|e| e.enabled)
+    {
+        email_domains
+            .list
+            .load(&email_domains.blocklist)
+            .map_err(|err| {
+                GuardError::Other(format!(
+                    "Failed to load the email domains blocklist `{}`: {err}",
+                    email_domains.blocklist.display()
+                ))
+            })?;
+    }
+    Ok(())
+}
+
 /// Returns the log level from `RUST_LOG` environment variable
 pub fn get_log_level() -> LevelFilter {
     std::env::var("RUST_LOG")
@@ -118,25 +442,78 @@ pub fn get_log_level() -> LevelFilter {
         .unwrap_or(LevelFilter::INFO)
 }
 
-/// Returns the guard config
-pub fn get_config() -> GuardResult<Config> {
-    let config_path = if let Ok(path) = std::env::var(CONFIG_PATH_ENV) {
-        PathBuf::from(path)
+/// Returns the config file path
+pub fn config_path() -> GuardResult<PathBuf> {
+    if let Ok(path) = std::env::var(CONFIG_PATH_ENV) {
+        Ok(PathBuf::from(path))
     } else if matches!(fs::exists(DEFAULT_CONFIG_PATH), Ok(true)) {
-        PathBuf::from(DEFAULT_CONFIG_PATH)
+        Ok(PathBuf::from(DEFAULT_CONFIG_PATH))
     } else {
-        return Err(GuardError::CantGetConfigFile);
-    };
+        Err(GuardError::CantGetConfigFile)
+    }
+}
+
+/// Returns the guard configs, a config for each instance
+pub fn get_configs() -> GuardResult<Vec<Config>> {
+    let config_path = config_path()?;
 
     tracing::info!("Config path: {}", config_path.display());
-    let mut config =
-        toml::from_str(&fs::read_to_string(&config_path)?).map_err(GuardError::from)?;
+    load_configs(&config_path)
+}
+
+/// Loads the guard configs from the given config file, a config for each
+/// instance
+pub fn load_configs(config_path: &Path) -> GuardResult<Vec<Config>> {
+    CONFIG_DIR.set(
+        config_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default(),
+    );
+    let root: toml::Table =
+        toml::from_str(&fs::read_to_string(config_path)?).map_err(GuardError::from)?;
 
-    check_errors(&config)?;
-    check_warnings(&config);
-    check_forgejo_token(&mut config)?;
+    instances::split_instances(root)?
+        .into_iter()
+        .map(|(name, table)| {
+            RULES_FILES.take();
+            let mut config: Config = table.try_into().map_err(|err| {
+                match &name {
+                    Some(name) => {
+                        GuardError::Other(format!(
+                            "Failed to deserialize the `{name}` instance config: {err}"
+                        ))
+                    }
+                    None => GuardError::from(err),
+                }
+            })?;
+            config.forgejo.name = name;
 
-    Ok(config)
+            load_includes(&mut config)?;
+            config.expressions.rules_files = RULES_FILES.take();
+            check_errors(&config)?;
+            check_warnings(&config);
+            check_forgejo_token(&mut config)?;
+            check_webhook_secret(&mut config)?;
+            check_server_tokens(&mut config)?;
+            check_email(&mut config)?;
+            check_feeds(&mut config)?;
+            load_email_domains(&config)?;
+
+            Ok(config)
+        })
+        .collect()
+}
+
+/// Returns `true` if the signature is a valid HMAC-SHA256 of the body
+pub fn is_valid_signature(secret: &str, signature: &str, body: &[u8]) -> bool {
+    let Ok(signature) = hex::decode(signature.trim()) else {
+        return false;
+    };
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size");
+    mac.update(body);
+    mac.verify_slice(&signature).is_ok()
 }
 
 /// Wait for the interval to pass, if the cancellation token is cancelled,
@@ -147,3 +524,105 @@ pub async fn wait_interval(req_interval: u32, cancellation_token: &CancellationT
         _ = cancellation_token.cancelled() => true,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A temporary directory, removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir =
+                env::temp_dir().join(format!("forgejo-guardian-{}-{name}", std::process::id()));
+            fs::create_dir_all(dir.join("rules")).unwrap();
+            Self(dir)
+        }
+
+        fn write(&self, path: &str, content: &str) -> PathBuf {
+            let path = self.0.join(path);
+            fs::write(&path, content).unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            fs::remove_dir_all(&self.0).ok();
+        }
+    }
+
+    const CONFIG: &str = r#"
+        [forgejo]
+        instance_url = "https://forgejo.example"
+        token = "token"
+
+        [expressions.ban]
+        include = ["rules/*.toml"]
+        usernames = [{ file = "rules/usernames.txt", reason = "Spam username" }]
+    "#;
+
+    fn ban_regexes(expr: &Expr) -> Vec<(String, Option<String>)> {
+        expr.usernames
+            .iter()
+            .chain(&expr.biographies)
+            .map(|re| (re.re_vec[0].to_string(), re.reason.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn rules_files() {
+        let dir = TempDir::new("rules-files");
+        let config_path = dir.write("config.toml", CONFIG);
+        let usernames =
+            dir.write(
+                "rules/usernames.txt",
+                "# Casino spam waves\n\n^casino\\d+$ # Casino spam\n^spam # [0-9]+$ # Numbered \
+                 spam\n  ^bot_  \n",
+            );
+        let spam = dir.write(
+            "rules/spam.toml",
+            "biographies = [{ re = '(?i)cheap followers', reason = 'Followers spam' }]",
+        );
+
+        // The relative paths are resolved against the config directory, not
+        // the working directory
+        let configs = load_configs(&config_path).unwrap();
+        let exprs = &configs[0].expressions;
+        assert_eq!(
+            ban_regexes(&exprs.ban),
+            [
+                ("^casino\\d+$".to_owned(), Some("Casino spam".to_owned())),
+                // Split at the last ` # `
+                (
+                    "^spam # [0-9]+$".to_owned(),
+                    Some("Numbered spam".to_owned())
+                ),
+                // The file reason is the default reason of its lines
+                ("^bot_".to_owned(), Some("Spam username".to_owned())),
+                (
+                    "(?i)cheap followers".to_owned(),
+                    Some("Followers spam".to_owned())
+                ),
+            ]
+        );
+        assert_eq!(exprs.rules_files, [usernames, spam]);
+    }
+
+    #[test]
+    fn nested_includes() {
+        let dir = TempDir::new("nested-includes");
+        let config_path = dir.write("config.toml", CONFIG);
+        dir.write("rules/usernames.txt", "^spam");
+        dir.write("rules/spam.toml", "include = ['other/*.toml']");
+
+        let Err(err) = load_configs(&config_path) else {
+            panic!("The nested includes should be rejected");
+        };
+        assert!(
+            err.to_string().contains("can't include other files"),
+            "{err}"
+        );
+    }
+}